@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE book_copy(
+  book_copy_id uuid primary key default uuid_generate_v1mc(),
+  master_book_id uuid not null references master_book(master_book_id) on delete cascade,
+  barcode text not null,
+  condition text null,
+  acquisition_date date null,
+  price_cents bigint null,
+  withdrawn_at timestamptz null,
+  create_at timestamptz not null default now(),
+  updated_at timestamptz,
+  constraint book_copy_barcode_key unique (barcode),
+  constraint book_copy_price_cents_check check (price_cents >= 0)
+);
+
+CREATE INDEX book_copy_master_book_id_idx ON book_copy(master_book_id);
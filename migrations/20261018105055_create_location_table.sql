@@ -0,0 +1,19 @@
+-- Add migration script here
+CREATE TABLE location(
+  location_id uuid primary key default uuid_generate_v1mc(),
+  parent_location_id uuid null references location(location_id) on delete restrict,
+  name text not null,
+  floor text null,
+  shelf_start text null,
+  shelf_end text null,
+  create_at timestamptz not null default now(),
+  updated_at timestamptz,
+  constraint location_parent_check check (parent_location_id <> location_id)
+);
+
+CREATE INDEX location_parent_location_id_idx ON location(parent_location_id);
+
+ALTER TABLE book_copy
+  ADD COLUMN location_id uuid null references location(location_id) on delete set null;
+
+CREATE INDEX book_copy_location_id_idx ON book_copy(location_id);
@@ -0,0 +1,29 @@
+-- Add migration script here
+CREATE TABLE patron_category(
+  patron_category text primary key,
+  description text not null
+);
+
+INSERT INTO patron_category (patron_category, description) VALUES
+  ('adult', 'Adult'),
+  ('child', 'Child'),
+  ('staff', 'Staff');
+
+CREATE TABLE patron(
+  patron_id uuid primary key default uuid_generate_v1mc(),
+  card_number text not null,
+  first_name text not null,
+  last_name text not null,
+  email text null,
+  phone text null,
+  address text null,
+  patron_category text not null references patron_category(patron_category),
+  expires_on date null,
+  blocked boolean not null default false,
+  blocked_reason text null,
+  create_at timestamptz not null default now(),
+  updated_at timestamptz,
+  constraint patron_card_number_key unique (card_number)
+);
+
+CREATE INDEX patron_last_name_idx ON patron(last_name, first_name);
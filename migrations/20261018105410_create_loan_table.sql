@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE loan(
+  loan_id uuid primary key default uuid_generate_v1mc(),
+  book_copy_id uuid not null references book_copy(book_copy_id) on delete restrict,
+  patron_id uuid not null references patron(patron_id) on delete restrict,
+  checked_out_at timestamptz not null default now(),
+  due_on date not null,
+  returned_at timestamptz null,
+  renewals integer not null default 0,
+  create_at timestamptz not null default now(),
+  updated_at timestamptz
+);
+
+-- A copy can only be out on one loan at a time
+CREATE UNIQUE INDEX loan_book_copy_id_open_key ON loan(book_copy_id) WHERE returned_at IS NULL;
+CREATE INDEX loan_patron_id_idx ON loan(patron_id) WHERE returned_at IS NULL;
@@ -0,0 +1,33 @@
+-- Add migration script here
+CREATE TABLE item_type(
+  item_type text primary key,
+  description text not null
+);
+
+INSERT INTO item_type (item_type, description) VALUES
+  ('book', 'Book'),
+  ('periodical', 'Periodical'),
+  ('audiovisual', 'Audiovisual'),
+  ('reference', 'Reference');
+
+ALTER TABLE book_copy
+  ADD COLUMN item_type text not null default 'book' references item_type(item_type);
+
+-- A null key matches anything, so the all-null row is the library wide default
+CREATE TABLE loan_policy(
+  loan_policy_id uuid primary key default uuid_generate_v1mc(),
+  patron_category text null references patron_category(patron_category),
+  item_type text null references item_type(item_type),
+  location_id uuid null references location(location_id) on delete cascade,
+  loan_period_days integer not null,
+  max_renewals integer not null,
+  max_items integer not null,
+  create_at timestamptz not null default now(),
+  updated_at timestamptz,
+  constraint loan_policy_key unique nulls not distinct (patron_category, item_type, location_id),
+  constraint loan_policy_loan_period_days_check check (loan_period_days > 0),
+  constraint loan_policy_max_renewals_check check (max_renewals >= 0),
+  constraint loan_policy_max_items_check check (max_items > 0)
+);
+
+INSERT INTO loan_policy (loan_period_days, max_renewals, max_items) VALUES (21, 2, 10);
@@ -0,0 +1,27 @@
+-- Add migration script here
+CREATE TABLE hold(
+  hold_id uuid primary key default uuid_generate_v1mc(),
+  master_book_id uuid not null references master_book(master_book_id) on delete cascade,
+  patron_id uuid not null references patron(patron_id) on delete cascade,
+  pickup_location_id uuid not null references location(location_id) on delete restrict,
+  queue_position integer not null,
+  status text not null default 'waiting',
+  placed_at timestamptz not null default now(),
+  expires_on date null,
+  suspended_from date null,
+  suspended_until date null,
+  book_copy_id uuid null references book_copy(book_copy_id) on delete set null,
+  trapped_at timestamptz null,
+  pickup_by date null,
+  create_at timestamptz not null default now(),
+  updated_at timestamptz,
+  constraint hold_status_check check (status in ('waiting', 'trapped', 'fulfilled', 'cancelled')),
+  constraint hold_suspension_check check (suspended_until is null or suspended_from is not null),
+  constraint hold_suspension_order_check check (suspended_until >= suspended_from)
+);
+
+-- A patron holds a title once, and a copy sits on the hold shelf for one hold at a time
+CREATE UNIQUE INDEX hold_patron_id_master_book_id_active_key
+  ON hold(patron_id, master_book_id) WHERE status in ('waiting', 'trapped');
+CREATE UNIQUE INDEX hold_book_copy_id_trapped_key ON hold(book_copy_id) WHERE status = 'trapped';
+CREATE INDEX hold_master_book_id_queue_idx ON hold(master_book_id, queue_position) WHERE status = 'waiting';
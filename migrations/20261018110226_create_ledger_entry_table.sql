@@ -0,0 +1,42 @@
+-- Add migration script here
+ALTER TABLE loan_policy
+  ADD COLUMN daily_fine_cents integer not null default 0,
+  ADD COLUMN max_fine_cents integer null,
+  ADD COLUMN fine_threshold_cents integer null,
+  ADD CONSTRAINT loan_policy_daily_fine_cents_check check (daily_fine_cents >= 0),
+  ADD CONSTRAINT loan_policy_max_fine_cents_check check (max_fine_cents >= 0),
+  ADD CONSTRAINT loan_policy_fine_threshold_cents_check check (fine_threshold_cents >= 0);
+
+-- Charges are positive and credits negative, so a patron's balance is the sum
+CREATE TABLE ledger_entry(
+  ledger_entry_id uuid primary key default uuid_generate_v1mc(),
+  patron_id uuid not null references patron(patron_id) on delete restrict,
+  loan_id uuid null references loan(loan_id) on delete restrict,
+  reference_entry_id uuid null references ledger_entry(ledger_entry_id) on delete restrict,
+  kind text not null,
+  amount_cents bigint not null,
+  note text null,
+  created_by text null,
+  created_at timestamptz not null default now(),
+  constraint ledger_entry_kind_check check (
+    kind in ('overdue', 'lost', 'damaged', 'manual', 'payment', 'waiver')
+  ),
+  constraint ledger_entry_amount_cents_check check (
+    (kind in ('overdue', 'lost', 'damaged', 'manual') and amount_cents > 0)
+    or (kind in ('payment', 'waiver') and amount_cents < 0)
+  )
+);
+
+CREATE INDEX ledger_entry_patron_id_idx ON ledger_entry(patron_id, created_at);
+CREATE INDEX ledger_entry_loan_id_idx ON ledger_entry(loan_id) WHERE loan_id IS NOT NULL;
+
+-- The ledger is an audit trail; mistakes are corrected with new entries
+CREATE FUNCTION ledger_entry_immutable() RETURNS trigger AS $$
+BEGIN
+  RAISE EXCEPTION 'ledger entries cannot be changed or removed';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER ledger_entry_immutable
+  BEFORE UPDATE OR DELETE ON ledger_entry
+  FOR EACH ROW EXECUTE FUNCTION ledger_entry_immutable();
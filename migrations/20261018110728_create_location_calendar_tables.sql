@@ -0,0 +1,24 @@
+-- Add migration script here
+-- Weekdays are ISO numbered, 1 being Monday. A weekday without hours is a
+-- day the location is closed.
+CREATE TABLE location_opening_hours(
+  location_id uuid not null references location(location_id) on delete cascade,
+  weekday smallint not null,
+  opens_at time not null,
+  closes_at time not null,
+  primary key (location_id, weekday),
+  constraint location_opening_hours_weekday_check check (weekday between 1 and 7),
+  constraint location_opening_hours_order_check check (closes_at > opens_at)
+);
+
+CREATE TABLE location_closure(
+  location_closure_id uuid primary key default uuid_generate_v1mc(),
+  location_id uuid not null references location(location_id) on delete cascade,
+  starts_on date not null,
+  ends_on date not null,
+  reason text null,
+  create_at timestamptz not null default now(),
+  constraint location_closure_order_check check (ends_on >= starts_on)
+);
+
+CREATE INDEX location_closure_location_id_idx ON location_closure(location_id, ends_on);
@@ -0,0 +1,9 @@
+-- Add migration script here
+-- Titles weigh more than authors when ranking search results
+ALTER TABLE master_book
+  ADD COLUMN search_vector tsvector generated always as (
+    setweight(to_tsvector('english'::regconfig, coalesce(title, '')), 'A') ||
+    setweight(to_tsvector('english'::regconfig, coalesce(author, '')), 'B')
+  ) stored;
+
+CREATE INDEX master_book_search_vector_idx ON master_book USING gin(search_vector);
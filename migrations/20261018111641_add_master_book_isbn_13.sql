@@ -0,0 +1,9 @@
+-- Add migration script here
+-- `isbn` keeps the ISBN as it was entered for display; `isbn_13` is the bare
+-- 13 digit form used for matching. Existing rows are filled in by running
+-- `library-api-rir backfill`.
+ALTER TABLE master_book
+  ADD COLUMN isbn_13 text null,
+  ADD CONSTRAINT master_book_isbn_13_check check (isbn_13 ~ '^97[89][0-9]{10}$');
+
+CREATE INDEX master_book_isbn_13_idx ON master_book(isbn_13);
@@ -0,0 +1,11 @@
+-- Add migration script here
+-- `lccn` keeps the LCCN as it was entered for display; `lccn_normalized` is
+-- the Library of Congress normalized form used for lookup. Existing rows are
+-- filled in by running `library-api-rir backfill`.
+ALTER TABLE master_book
+  ADD COLUMN lccn_normalized text null,
+  ADD CONSTRAINT master_book_lccn_normalized_check check (
+    lccn_normalized ~ '^[a-z]{0,3}([0-9]{8}|[0-9]{10})$'
+  );
+
+CREATE INDEX master_book_lccn_normalized_idx ON master_book(lccn_normalized);
@@ -0,0 +1,17 @@
+-- Add migration script here
+-- `publish_date` keeps the date as the cataloger wrote it. `published_on` is
+-- the first day of the period it covers, to a `published_precision` of
+-- century, decade, year, month or day. Existing rows are filled in the next
+-- time they are written.
+ALTER TABLE master_book
+  ADD COLUMN published_on date null,
+  ADD COLUMN published_precision text null,
+  ADD COLUMN published_approximate boolean not null default false,
+  ADD CONSTRAINT master_book_published_precision_check check (
+    published_precision in ('century', 'decade', 'year', 'month', 'day')
+  ),
+  ADD CONSTRAINT master_book_published_check check (
+    (published_on is null) = (published_precision is null)
+  );
+
+CREATE INDEX master_book_published_on_idx ON master_book(published_on);
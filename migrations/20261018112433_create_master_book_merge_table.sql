@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX master_book_title_trgm_idx ON master_book USING gin(title gin_trgm_ops);
+
+-- Each merge keeps a snapshot of the record it removed. Merging the kept
+-- record away later moves its history along to the new survivor.
+CREATE TABLE master_book_merge(
+  master_book_merge_id uuid primary key default uuid_generate_v1mc(),
+  kept_master_book_id uuid not null references master_book(master_book_id) on delete cascade,
+  merged_master_book_id uuid not null,
+  merged_record jsonb not null,
+  copies_moved integer not null,
+  holds_moved integer not null,
+  holds_cancelled integer not null,
+  merged_by text null,
+  merged_at timestamptz not null default now()
+);
+
+CREATE INDEX master_book_merge_kept_master_book_id_idx ON master_book_merge(kept_master_book_id);
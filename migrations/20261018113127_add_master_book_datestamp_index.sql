@@ -0,0 +1,4 @@
+-- Add migration script here
+-- A book's datestamp, for selective harvesting, is when it last changed
+CREATE INDEX master_book_datestamp_idx
+  ON master_book ((coalesce(updated_at, create_at)), master_book_id);
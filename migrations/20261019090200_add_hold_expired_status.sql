@@ -0,0 +1,10 @@
+-- Add migration script here
+-- A trapped hold its patron didn't collect by `pickup_by` is `expired`, and
+-- its copy goes on to the next hold in the queue
+ALTER TABLE hold
+  DROP CONSTRAINT hold_status_check,
+  ADD CONSTRAINT hold_status_check check (
+    status in ('waiting', 'trapped', 'fulfilled', 'cancelled', 'expired')
+  );
+
+CREATE INDEX hold_pickup_by_trapped_idx ON hold(pickup_by) WHERE status = 'trapped';
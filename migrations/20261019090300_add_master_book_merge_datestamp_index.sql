@@ -0,0 +1,8 @@
+-- Add migration script here
+-- A merged book is harvested as a deleted record, datestamped when it was
+-- merged, and looked up by its own id
+CREATE INDEX master_book_merge_datestamp_idx
+  ON master_book_merge(merged_at, merged_master_book_id);
+
+CREATE INDEX master_book_merge_merged_master_book_id_idx
+  ON master_book_merge(merged_master_book_id);
@@ -0,0 +1,86 @@
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::catalog::{isbn, lccn, publish_date, text};
+use crate::db::Db;
+
+/// How many books a backfill reads and writes in one transaction
+pub const BACKFILL_BATCH_SIZE: i64 = 500;
+
+/// What a backfill did: how many books it filled in, and how many it left
+/// something unread in because what was typed can't be read
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Backfilled {
+    pub filled: u64,
+    pub unreadable: u64,
+}
+
+/// Database Object for a book with a column still to be read out of what
+/// was typed
+pub struct BackfillFromQuery {
+    pub master_book_id: Uuid,
+    pub isbn: Option<String>,
+    pub lccn: Option<String>,
+    pub publish_date: Option<String>,
+}
+
+/// Fills in the columns read out of what was typed for books written before
+/// each was kept: the ISBN-13, the normalized LCCN and the structured
+/// publication date. These are read by the same rules as when a book is
+/// written. This is run once, from the command line, rather than by the
+/// server.
+pub struct Backfills;
+
+impl Backfills {
+    /// Walks the books still to fill in, in id order, a batch at a time.
+    /// Each batch is its own transaction, so no lock is held for long and a
+    /// run stopped part way picks up where it left off the next time. Books
+    /// that can't be read are passed over once and left as they are.
+    pub async fn run(connection_pool: &PgPool) -> Result<Backfilled, sqlx::Error> {
+        let mut backfilled = Backfilled::default();
+        let mut after = None;
+
+        loop {
+            let books = Db::get_backfill_list(after, BACKFILL_BATCH_SIZE, connection_pool).await?;
+            let Some(last) = books.last() else {
+                break;
+            };
+            after = Some(last.master_book_id);
+
+            let mut transaction = connection_pool.begin().await?;
+            for book in &books {
+                // `None` if there is nothing to read, `Some(None)` if it
+                // can't be read
+                let isbn_13 = text(&book.isbn).map(|isbn| isbn::normalize(isbn).ok());
+                let lccn_normalized = text(&book.lccn).map(|lccn| lccn::normalize(lccn).ok());
+                let published = text(&book.publish_date).map(publish_date::parse);
+
+                if matches!(isbn_13, Some(None))
+                    || matches!(lccn_normalized, Some(None))
+                    || matches!(published, Some(None))
+                {
+                    backfilled.unreadable += 1;
+                }
+                let isbn_13 = isbn_13.flatten();
+                let lccn_normalized = lccn_normalized.flatten();
+                let published = published.flatten();
+                if isbn_13.is_none() && lccn_normalized.is_none() && published.is_none() {
+                    continue;
+                }
+                backfilled.filled += Db::backfill_book(
+                    book.master_book_id,
+                    isbn_13.as_deref(),
+                    lccn_normalized.as_deref(),
+                    published.as_ref(),
+                    &mut transaction,
+                )
+                .await?;
+            }
+            transaction.commit().await?;
+
+            tracing::debug!("backfilled books up to {}", last.master_book_id);
+        }
+
+        Ok(backfilled)
+    }
+}
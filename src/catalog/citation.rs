@@ -0,0 +1,250 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::catalog::publish_date::DatePrecision;
+use crate::catalog::text;
+use crate::routes::{Book, Published};
+
+/// Books as BibTeX `@book` entries, one after another.
+///
+/// Each entry is keyed by its author's surname, year and the first word of
+/// its title, as in `leguin1969left`. A key the set has already used gets a
+/// letter on the end, so a set of results never has two entries alike.
+pub fn bibtex(books: &[Book]) -> String {
+    let mut keys: HashMap<String, usize> = HashMap::new();
+
+    books
+        .iter()
+        .map(|book| {
+            let key = citation_key(book);
+            let seen = keys.entry(key.clone()).or_default();
+            *seen += 1;
+            let key = match *seen {
+                1 => key,
+                seen => format!("{key}{}", suffix_letters(seen - 1)),
+            };
+
+            let mut fields = Vec::new();
+            if let Some(author) = text(&book.author) {
+                fields.push(("author", author.to_string()));
+            }
+            if let Some(title) = text(&book.title) {
+                fields.push(("title", title.to_string()));
+            }
+            if let Some(year) = year(book) {
+                fields.push(("year", year));
+            }
+            if let Some(month) = book
+                .published
+                .as_ref()
+                .filter(|published| published.precision >= DatePrecision::Month)
+            {
+                fields.push(("month", u8::from(month.on.month()).to_string()));
+            }
+            if let Some(isbn) = isbn(book) {
+                fields.push(("isbn", isbn.to_string()));
+            }
+            if let Some(lccn) = text(&book.lccn_normalized) {
+                fields.push(("lccn", lccn.to_string()));
+            }
+
+            let fields: Vec<String> = fields
+                .into_iter()
+                .map(|(name, value)| format!("  {name} = {{{}}}", bibtex_escape(&value)))
+                .collect();
+            format!("@book{{{key},\n{}\n}}\n", fields.join(",\n"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Books as RIS records, each running from `TY` to `ER`, with the CRLF line
+/// endings the format calls for
+pub fn ris(books: &[Book]) -> String {
+    books
+        .iter()
+        .map(|book| {
+            let mut tags = vec![("TY", "BOOK".to_string())];
+            if let Some(author) = text(&book.author) {
+                tags.push(("AU", author.to_string()));
+            }
+            if let Some(title) = text(&book.title) {
+                tags.push(("TI", title.to_string()));
+            }
+            if let Some(published) = &book.published {
+                let date = published.on;
+                let date = match published.precision {
+                    DatePrecision::Century | DatePrecision::Decade => None,
+                    DatePrecision::Year => Some(date.year().to_string()),
+                    DatePrecision::Month => {
+                        Some(format!("{}/{:02}", date.year(), u8::from(date.month())))
+                    }
+                    DatePrecision::Day => Some(format!(
+                        "{}/{:02}/{:02}",
+                        date.year(),
+                        u8::from(date.month()),
+                        date.day()
+                    )),
+                };
+                if let Some(date) = date {
+                    tags.push(("PY", date));
+                }
+            }
+            if let Some(isbn) = isbn(book) {
+                tags.push(("SN", isbn.to_string()));
+            }
+            tags.push(("ID", book.id.to_string()));
+            tags.push(("ER", String::new()));
+
+            tags.into_iter()
+                .map(|(tag, value)| format!("{tag}  - {value}\r\n"))
+                .collect::<String>()
+        })
+        .collect()
+}
+
+/// A book as a CSL-JSON item, what citation processors such as citeproc
+/// format into a reference in any citation style
+pub fn csl_json(book: &Book) -> Value {
+    let mut item = json!({
+        "id": book.id,
+        "type": "book",
+    });
+
+    if let Some(title) = text(&book.title) {
+        item["title"] = json!(title);
+    }
+    if let Some(author) = text(&book.author) {
+        item["author"] = json!([csl_name(author)]);
+    }
+    if let Some(issued) = issued(book) {
+        item["issued"] = issued;
+    }
+    if let Some(isbn) = isbn(book) {
+        item["ISBN"] = json!(isbn);
+    }
+
+    item
+}
+
+/// A name as CSL-JSON. A name written surname first is split into its parts;
+/// any other is left whole for the citation processor.
+fn csl_name(author: &str) -> Value {
+    match author.split_once(',') {
+        Some((family, given)) if !given.trim().is_empty() => json!({
+            "family": family.trim(),
+            "given": given.trim(),
+        }),
+        _ => json!({ "literal": author }),
+    }
+}
+
+/// When a book was published, as precisely as it is known
+fn issued(book: &Book) -> Option<Value> {
+    let Some(Published {
+        on,
+        precision,
+        approximate,
+    }) = &book.published
+    else {
+        return text(&book.publish_date).map(|publish_date| json!({ "literal": publish_date }));
+    };
+
+    let mut issued = match precision {
+        DatePrecision::Century | DatePrecision::Decade => {
+            json!({ "literal": text(&book.publish_date).unwrap_or_default() })
+        }
+        DatePrecision::Year => json!({ "date-parts": [[on.year()]] }),
+        DatePrecision::Month => json!({ "date-parts": [[on.year(), u8::from(on.month())]] }),
+        DatePrecision::Day => {
+            json!({ "date-parts": [[on.year(), u8::from(on.month()), on.day()]] })
+        }
+    };
+    if *approximate {
+        issued["circa"] = json!(true);
+    }
+
+    Some(issued)
+}
+
+/// The year a book was published, or the publish date as written when only
+/// its decade or century is known
+fn year(book: &Book) -> Option<String> {
+    match &book.published {
+        Some(published) if published.precision >= DatePrecision::Year => {
+            Some(published.on.year().to_string())
+        }
+        _ => text(&book.publish_date).map(str::to_string),
+    }
+}
+
+fn isbn(book: &Book) -> Option<&str> {
+    text(&book.isbn_13).or_else(|| text(&book.isbn))
+}
+
+/// `surname`, `year` and first title word, lowercased ASCII letters and
+/// digits only
+fn citation_key(book: &Book) -> String {
+    let surname = text(&book.author)
+        .map(|author| match author.split_once(',') {
+            Some((family, _)) => family,
+            None => author.split_whitespace().last().unwrap_or_default(),
+        })
+        .unwrap_or_default();
+    let year = book
+        .published
+        .as_ref()
+        .map(|published| published.on.year().to_string())
+        .unwrap_or_default();
+    let title_word = text(&book.title)
+        .and_then(|title| {
+            title
+                .split_whitespace()
+                .map(key_part)
+                .find(|word| !word.is_empty() && !["a", "an", "the"].contains(&word.as_str()))
+        })
+        .unwrap_or_default();
+
+    let key = format!("{}{year}{title_word}", key_part(surname));
+    if key.is_empty() {
+        "book".to_string()
+    } else {
+        key
+    }
+}
+
+fn key_part(word: &str) -> String {
+    word.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// `1` is `a`, `26` is `z`, `27` is `aa`
+fn suffix_letters(mut number: usize) -> String {
+    let mut letters = Vec::new();
+    while number > 0 {
+        number -= 1;
+        letters.push(char::from(b'a' + (number % 26) as u8));
+        number /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Escapes the characters BibTeX and LaTeX treat specially
+fn bibtex_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '{' | '}' | '&' | '%' | '$' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
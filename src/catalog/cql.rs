@@ -0,0 +1,329 @@
+/// The index a term searches when a query names none
+pub const SERVER_CHOICE: &str = "cql.serverchoice";
+
+/// How deeply parentheses may nest in a query
+pub const MAX_NESTING: usize = 32;
+
+/// How many tokens a query may run to. Booleans chain without nesting, so
+/// this is what bounds how deep a parsed query goes.
+pub const MAX_TOKENS: usize = 256;
+
+/// Relations written as words rather than symbols
+const NAMED_RELATIONS: [&str; 6] = ["adj", "all", "any", "encloses", "exact", "within"];
+
+/// Why a CQL query couldn't be read
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum CqlError {
+    #[error("{0}")]
+    Syntax(String),
+
+    #[error("prefix assignments are not supported")]
+    PrefixAssignment,
+
+    #[error("sortBy is not supported")]
+    SortBy,
+
+    #[error("query too complex")]
+    TooComplex,
+}
+
+/// A parsed CQL query: search clauses joined by booleans. Index names,
+/// relations, booleans and modifier names are lowercased, as CQL compares
+/// them without regard to case.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CqlQuery {
+    Clause(SearchClause),
+    Boolean {
+        boolean: Boolean,
+        modifiers: Vec<Modifier>,
+        left: Box<CqlQuery>,
+        right: Box<CqlQuery>,
+    },
+}
+
+/// `index relation term`. A bare term searches `cql.serverchoice` with `=`.
+/// The term is kept as written, its masking characters and backslash
+/// escapes still in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchClause {
+    pub index: String,
+    pub relation: String,
+    pub relation_modifiers: Vec<Modifier>,
+    pub term: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Boolean {
+    And,
+    Or,
+    Not,
+    Prox,
+}
+
+/// A `/name` or `/name=value` modifier on a relation or boolean
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Modifier {
+    pub name: String,
+    pub comparison: Option<(String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Slash,
+    Symbol(String),
+    Word(String),
+    Quoted(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Open => write!(f, "("),
+            Self::Close => write!(f, ")"),
+            Self::Slash => write!(f, "/"),
+            Self::Symbol(string) | Self::Word(string) => write!(f, "{string}"),
+            Self::Quoted(string) => write!(f, "\"{string}\""),
+        }
+    }
+}
+
+impl Token {
+    /// A word or quoted string, either of which can be a term or index
+    fn string(&self) -> Option<&str> {
+        match self {
+            Self::Word(string) | Self::Quoted(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// A bare word matching `word`, case aside
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self, Self::Word(string) if string.eq_ignore_ascii_case(word))
+    }
+}
+
+/// Parses a CQL query. Booleans bind left to right with equal precedence,
+/// so `a or b and c` is `(a or b) and c`.
+///
+/// Queries longer than `MAX_TOKENS` or nested deeper than `MAX_NESTING` are
+/// refused as too complex, so nothing reading the parsed query can run out
+/// of stack.
+pub fn parse(query: &str) -> Result<CqlQuery, CqlError> {
+    let tokens = tokenize(query)?;
+    if tokens.first() == Some(&Token::Symbol(">".to_string())) {
+        return Err(CqlError::PrefixAssignment);
+    }
+
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        depth: 0,
+    };
+    let query = parser.scoped_clause()?;
+    match parser.peek() {
+        None => Ok(query),
+        Some(token) if token.is_word("sortby") => Err(CqlError::SortBy),
+        Some(token) => Err(CqlError::Syntax(format!("unexpected {token}"))),
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, CqlError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if tokens.len() == MAX_TOKENS && !c.is_whitespace() {
+            return Err(CqlError::TooComplex);
+        }
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '/' => tokens.push(Token::Slash),
+            '=' | '<' | '>' => {
+                let mut symbol = c.to_string();
+                if let Some(&next) = chars.peek() {
+                    if matches!((c, next), ('=', '=') | ('<', '>') | ('<', '=') | ('>', '=')) {
+                        symbol.push(next);
+                        chars.next();
+                    }
+                }
+                tokens.push(Token::Symbol(symbol));
+            }
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // Escapes are kept for the term to be read with
+                        Some('\\') => {
+                            string.push('\\');
+                            string.extend(chars.next());
+                        }
+                        Some(c) => string.push(c),
+                        None => {
+                            return Err(CqlError::Syntax("unterminated quoted string".to_string()))
+                        }
+                    }
+                }
+                tokens.push(Token::Quoted(string));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "()/=<>\"".contains(next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn scoped_clause(&mut self) -> Result<CqlQuery, CqlError> {
+        let mut query = self.search_clause()?;
+
+        while let Some(boolean) = self.peek().and_then(boolean) {
+            self.position += 1;
+            let modifiers = self.modifiers()?;
+            let right = self.search_clause()?;
+            query = CqlQuery::Boolean {
+                boolean,
+                modifiers,
+                left: Box::new(query),
+                right: Box::new(right),
+            };
+        }
+
+        Ok(query)
+    }
+
+    fn search_clause(&mut self) -> Result<CqlQuery, CqlError> {
+        match self.next() {
+            Some(Token::Open) => {
+                if self.depth == MAX_NESTING {
+                    return Err(CqlError::TooComplex);
+                }
+                self.depth += 1;
+                let query = self.scoped_clause()?;
+                self.depth -= 1;
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(CqlError::Syntax("missing )".to_string())),
+                }
+            }
+            Some(token) => {
+                let first = token
+                    .string()
+                    .ok_or_else(|| CqlError::Syntax(format!("unexpected {token}")))?
+                    .to_string();
+
+                let Some(relation) = self.peek().and_then(relation) else {
+                    return Ok(CqlQuery::Clause(SearchClause {
+                        index: SERVER_CHOICE.to_string(),
+                        relation: "=".to_string(),
+                        relation_modifiers: Vec::new(),
+                        term: first,
+                    }));
+                };
+                self.position += 1;
+                let relation_modifiers = self.modifiers()?;
+                let term = self
+                    .next()
+                    .as_ref()
+                    .and_then(Token::string)
+                    .ok_or_else(|| CqlError::Syntax(format!("{first} {relation} needs a term")))?
+                    .to_string();
+
+                Ok(CqlQuery::Clause(SearchClause {
+                    index: first.to_lowercase(),
+                    relation,
+                    relation_modifiers,
+                    term,
+                }))
+            }
+            None => Err(CqlError::Syntax("query ends too soon".to_string())),
+        }
+    }
+
+    fn modifiers(&mut self) -> Result<Vec<Modifier>, CqlError> {
+        let mut modifiers = Vec::new();
+
+        while self.peek() == Some(&Token::Slash) {
+            self.position += 1;
+            let name = match self.next() {
+                Some(Token::Word(name)) => name.to_lowercase(),
+                _ => return Err(CqlError::Syntax("/ needs a modifier name".to_string())),
+            };
+            let comparison = match self.peek() {
+                Some(Token::Symbol(symbol)) => {
+                    let symbol = symbol.clone();
+                    self.position += 1;
+                    let value = self
+                        .next()
+                        .as_ref()
+                        .and_then(Token::string)
+                        .ok_or_else(|| CqlError::Syntax(format!("/{name}{symbol} needs a value")))?
+                        .to_string();
+                    Some((symbol, value))
+                }
+                _ => None,
+            };
+            modifiers.push(Modifier { name, comparison });
+        }
+
+        Ok(modifiers)
+    }
+}
+
+fn boolean(token: &Token) -> Option<Boolean> {
+    let Token::Word(word) = token else {
+        return None;
+    };
+
+    match word.to_lowercase().as_str() {
+        "and" => Some(Boolean::And),
+        "or" => Some(Boolean::Or),
+        "not" => Some(Boolean::Not),
+        "prox" => Some(Boolean::Prox),
+        _ => None,
+    }
+}
+
+/// The relation a token is, if it is one. A named relation may carry the
+/// `cql.` prefix of the context set defining it.
+fn relation(token: &Token) -> Option<String> {
+    match token {
+        Token::Symbol(symbol) => Some(symbol.clone()),
+        Token::Word(word) => {
+            let word = word.to_lowercase();
+            let name = word.strip_prefix("cql.").unwrap_or(&word);
+            NAMED_RELATIONS.contains(&name).then(|| name.to_string())
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,225 @@
+use futures::future::BoxFuture;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Where books are looked up when `METADATA_SOURCE_URL` isn't set
+pub const DEFAULT_METADATA_SOURCE_URL: &str = "https://openlibrary.org";
+
+/// How long a lookup is remembered, whether or not it found the book
+pub const METADATA_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How many lookups are remembered at once
+pub const METADATA_CACHE_CAPACITY: usize = 10_000;
+
+/// How long to wait on the metadata source before giving up
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long creating a book waits on enriching it. Asking for a book to be
+/// enriched on its own waits the full `REQUEST_TIMEOUT`.
+pub const ENRICH_ON_CREATE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What a metadata source knows about a book, in the form the catalog
+/// records it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BookMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub publish_date: Option<String>,
+}
+
+/// Why a lookup couldn't be answered. A source that simply doesn't know the
+/// book isn't an error.
+#[derive(thiserror::Error, Debug)]
+pub enum EnrichmentError {
+    #[error("the metadata source could not be reached")]
+    Unreachable(#[source] reqwest::Error),
+
+    #[error("the metadata source answered {0}")]
+    Status(reqwest::StatusCode),
+
+    #[error("the metadata source sent a response we couldn't read")]
+    Malformed,
+}
+
+/// Somewhere to look books up by ISBN. The app holds one behind this trait
+/// so tests can point it at a stub, or swap it out entirely.
+pub trait MetadataSource: Send + Sync {
+    /// What the source knows about the book with this ISBN-13, or `None`
+    /// if it doesn't know it
+    fn lookup_isbn<'a>(
+        &'a self,
+        isbn_13: &'a str,
+    ) -> BoxFuture<'a, Result<Option<BookMetadata>, EnrichmentError>>;
+}
+
+/// A client for the Open Library Books API, or anything serving the same
+/// `/api/books?bibkeys=ISBN:<isbn>&format=json&jscmd=data` responses at
+/// `base_url`
+pub struct OpenLibrary {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl OpenLibrary {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("the HTTP client always builds");
+
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            client,
+        }
+    }
+}
+
+impl MetadataSource for OpenLibrary {
+    fn lookup_isbn<'a>(
+        &'a self,
+        isbn_13: &'a str,
+    ) -> BoxFuture<'a, Result<Option<BookMetadata>, EnrichmentError>> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .get(format!("{}/api/books", self.base_url))
+                .query(&[
+                    ("bibkeys", format!("ISBN:{isbn_13}").as_str()),
+                    ("format", "json"),
+                    ("jscmd", "data"),
+                ])
+                .send()
+                .await
+                .map_err(EnrichmentError::Unreachable)?;
+            if !response.status().is_success() {
+                return Err(EnrichmentError::Status(response.status()));
+            }
+            let body: Value = response
+                .json()
+                .await
+                .map_err(|_| EnrichmentError::Malformed)?;
+
+            read_books_response(isbn_13, &body)
+        })
+    }
+}
+
+/// Reads the record for an ISBN out of a Books API response, which is an
+/// object keyed by the bibkeys asked for, missing those it doesn't know.
+///
+/// A subtitle is added to the title after a colon. Several authors are
+/// joined by semicolons, as they come.
+pub fn read_books_response(
+    isbn_13: &str,
+    body: &Value,
+) -> Result<Option<BookMetadata>, EnrichmentError> {
+    let books = body.as_object().ok_or(EnrichmentError::Malformed)?;
+    let Some(book) = books.get(&format!("ISBN:{isbn_13}")) else {
+        return Ok(None);
+    };
+    let book = book.as_object().ok_or(EnrichmentError::Malformed)?;
+
+    let text = |value: Option<&Value>| {
+        value
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let title = match (text(book.get("title")), text(book.get("subtitle"))) {
+        (Some(title), Some(subtitle)) => Some(format!("{title}: {subtitle}")),
+        (title, _) => title,
+    };
+    let authors: Vec<String> = book
+        .get("authors")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|author| text(author.get("name")))
+        .collect();
+
+    Ok(Some(BookMetadata {
+        title,
+        author: (!authors.is_empty()).then(|| authors.join("; ")),
+        publish_date: text(book.get("publish_date")),
+    }))
+}
+
+/// Remembers what a source said about each ISBN for `ttl`, so enriching the
+/// same book again, or a run of copies of it, asks the source once. Failed
+/// lookups aren't remembered.
+///
+/// At most `capacity` lookups are kept. Once it is full, remembering another
+/// first drops those that have expired, then the oldest if none have.
+pub struct CachedSource<S> {
+    source: S,
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, (Instant, Option<BookMetadata>)>>,
+}
+
+impl<S> CachedSource<S> {
+    pub fn new(source: S, ttl: Duration, capacity: usize) -> Self {
+        Self {
+            source,
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cached(&self, isbn_13: &str) -> Option<Option<BookMetadata>> {
+        let mut entries = self
+            .entries
+            .lock()
+            .expect("the cache lock is never poisoned");
+        match entries.get(isbn_13) {
+            Some((fetched, metadata)) if fetched.elapsed() < self.ttl => Some(metadata.clone()),
+            Some(_) => {
+                entries.remove(isbn_13);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn remember(&self, isbn_13: &str, metadata: Option<BookMetadata>) {
+        let mut entries = self
+            .entries
+            .lock()
+            .expect("the cache lock is never poisoned");
+        if entries.len() >= self.capacity && !entries.contains_key(isbn_13) {
+            entries.retain(|_, (fetched, _)| fetched.elapsed() < self.ttl);
+        }
+        if entries.len() >= self.capacity && !entries.contains_key(isbn_13) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (fetched, _))| *fetched)
+                .map(|(isbn_13, _)| isbn_13.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(isbn_13.to_string(), (Instant::now(), metadata));
+    }
+}
+
+impl<S: MetadataSource> MetadataSource for CachedSource<S> {
+    fn lookup_isbn<'a>(
+        &'a self,
+        isbn_13: &'a str,
+    ) -> BoxFuture<'a, Result<Option<BookMetadata>, EnrichmentError>> {
+        Box::pin(async move {
+            if let Some(metadata) = self.cached(isbn_13) {
+                return Ok(metadata);
+            }
+
+            let metadata = self.source.lookup_isbn(isbn_13).await?;
+            self.remember(isbn_13, metadata.clone());
+
+            Ok(metadata)
+        })
+    }
+}
@@ -0,0 +1,164 @@
+use axum::body::Bytes;
+use futures::channel::mpsc;
+use futures::{SinkExt, TryStreamExt};
+use sqlx::PgPool;
+use std::io;
+
+use crate::catalog::marc::{
+    marcxml_collection_start, MarcError, MarcRecord, MARCXML_COLLECTION_END,
+};
+use crate::db::Db;
+use crate::routes::{BookFromQuery, ExportFormat};
+
+/// How many bytes of records are gathered before they are sent on to the
+/// client
+pub const EXPORT_CHUNK_SIZE: usize = 16 * 1024;
+
+/// How many chunks of an export may be written ahead of what the client has
+/// read
+const EXPORT_CHUNKS_AHEAD: usize = 4;
+
+/// The columns of a CSV export. Those named for book fields are the ones a
+/// CSV import reads.
+pub const CSV_EXPORT_COLUMNS: [&str; 11] = [
+    "id",
+    "author",
+    "title",
+    "isbn",
+    "isbn_13",
+    "lccn",
+    "lccn_normalized",
+    "publish_date",
+    "published_on",
+    "published_precision",
+    "published_approximate",
+];
+
+/// The body of an export: chunks of it as they are written, or the error
+/// that cut it short
+pub type ExportStream = mpsc::Receiver<Result<Bytes, io::Error>>;
+
+/// Writing the whole catalog out.
+///
+/// Books are streamed from the database row by row and sent on in chunks
+/// to the response body. Only `EXPORT_CHUNKS_AHEAD` chunks are held at a
+/// time, so a slow client slows the query down rather than the export
+/// piling up in memory, however large the catalog grows. That also means an
+/// export holds one of the pool's connections for as long as the client
+/// takes to download it.
+///
+/// A book too long to write as ISO 2709 is left out of a `marc` export and
+/// logged. MARCXML has no such limit, so it can still be exported that way.
+pub struct Exports;
+
+impl Exports {
+    /// Every book in the catalog as `format`
+    pub fn books(format: ExportFormat, connection_pool: PgPool) -> ExportStream {
+        let (mut sender, receiver) = mpsc::channel(EXPORT_CHUNKS_AHEAD);
+
+        tokio::spawn(async move {
+            if let Err(error) = Self::write_books(format, &connection_pool, &mut sender).await {
+                tracing::error!("catalog export stopped early: {error}");
+                // The response has already started, so the error goes down
+                // the body, where it aborts the response rather than letting
+                // a truncated export end as if it were complete
+                let _ = sender.send(Err(io::Error::other(error))).await;
+            }
+        });
+
+        receiver
+    }
+
+    /// A single book as `format`, unless it is too long for ISO 2709
+    pub fn book(format: ExportFormat, book: &BookFromQuery) -> Result<Vec<u8>, MarcError> {
+        Ok([header(format), record(format, book)?, footer(format)].concat())
+    }
+
+    /// Writes the export, stopping quietly if the client goes away
+    async fn write_books(
+        format: ExportFormat,
+        connection_pool: &PgPool,
+        sender: &mut mpsc::Sender<Result<Bytes, io::Error>>,
+    ) -> Result<(), sqlx::Error> {
+        let mut chunk = header(format);
+
+        let mut books = Db::get_book_stream(connection_pool);
+        while let Some(book) = books.try_next().await? {
+            match record(format, &book) {
+                Ok(record) => chunk.extend(record),
+                Err(error) => tracing::error!(
+                    "left book {} out of the export: {error}",
+                    book.master_book_id
+                ),
+            }
+            if chunk.len() >= EXPORT_CHUNK_SIZE
+                && sender
+                    .send(Ok(std::mem::take(&mut chunk).into()))
+                    .await
+                    .is_err()
+            {
+                return Ok(());
+            }
+        }
+
+        chunk.extend(footer(format));
+        let _ = sender.send(Ok(chunk.into())).await;
+
+        Ok(())
+    }
+}
+
+/// What comes before the first record
+fn header(format: ExportFormat) -> Vec<u8> {
+    match format {
+        ExportFormat::Marc | ExportFormat::Jsonl => Vec::new(),
+        ExportFormat::Marcxml => marcxml_collection_start().into_bytes(),
+        ExportFormat::Csv => csv_row(&CSV_EXPORT_COLUMNS),
+    }
+}
+
+fn record(format: ExportFormat, book: &BookFromQuery) -> Result<Vec<u8>, MarcError> {
+    Ok(match format {
+        ExportFormat::Marc => MarcRecord::from_book(book).to_iso2709()?,
+        ExportFormat::Marcxml => MarcRecord::from_book(book).to_marcxml().into_bytes(),
+        ExportFormat::Csv => {
+            let text = |value: &Option<String>| value.clone().unwrap_or_default();
+            csv_row(&[
+                book.master_book_id.to_string(),
+                text(&book.author),
+                text(&book.title),
+                text(&book.isbn),
+                text(&book.isbn_13),
+                text(&book.lccn),
+                text(&book.lccn_normalized),
+                text(&book.publish_date),
+                book.published_on
+                    .map(|published_on| published_on.to_string())
+                    .unwrap_or_default(),
+                text(&book.published_precision),
+                book.published_approximate.to_string(),
+            ])
+        }
+        ExportFormat::Jsonl => {
+            let mut line = serde_json::to_vec(&book.to_book()).expect("books always serialize");
+            line.push(b'\n');
+            line
+        }
+    })
+}
+
+/// What comes after the last record
+fn footer(format: ExportFormat) -> Vec<u8> {
+    match format {
+        ExportFormat::Marcxml => MARCXML_COLLECTION_END.as_bytes().to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+fn csv_row<T: AsRef<[u8]>>(fields: &[T]) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(fields)
+        .expect("writing to memory can't fail");
+    writer.into_inner().expect("writing to memory can't fail")
+}
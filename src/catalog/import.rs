@@ -0,0 +1,284 @@
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashMap;
+
+use crate::catalog::{isbn, lccn, marc, publish_date};
+use crate::db::Db;
+use crate::routes::{
+    BookFromQuery, BookQuery, BookUpdateQuery, CsvImportQuery, CsvImportReport, CsvImportRow,
+    Error, ImportReport, ImportedRecord, RowAction,
+};
+
+/// The book fields a CSV column can be read into
+pub const CSV_FIELDS: [&str; 5] = ["author", "title", "isbn", "lccn", "publish_date"];
+
+/// Bulk loading of catalog records. Each MARC record is checked and created
+/// on its own, so one bad record is reported without holding up the rest. A
+/// CSV file is imported whole or not at all.
+pub struct Imports;
+
+impl Imports {
+    /// Imports a MARC file, ISO 2709 or MARCXML, as new books
+    pub async fn marc(bytes: &[u8], connection_pool: &PgPool) -> Result<ImportReport, Error> {
+        let records = marc::parse(bytes)
+            .map_err(|error| Error::unprocessable_entity([("file", error.to_string())]))?;
+
+        let mut report = ImportReport::default();
+        for (index, record) in records.into_iter().enumerate() {
+            let imported = match record {
+                Ok(record) => {
+                    let book = record.to_book_query();
+                    let mut imported = Self::book(book, connection_pool).await;
+                    imported.control_number = record
+                        .control_fields
+                        .iter()
+                        .find(|field| field.tag == "001")
+                        .map(|field| field.value.trim().to_string());
+                    imported
+                }
+                Err(error) => ImportedRecord::failed(vec![error.to_string()]),
+            };
+            report.push(index + 1, imported);
+        }
+
+        Ok(report)
+    }
+
+    /// Imports a CSV file with a header row in one transaction, committed
+    /// only if every row succeeds and this isn't a dry run. A dry run makes
+    /// the same changes and rolls them back, so rows later in the file see
+    /// the books earlier rows would create.
+    pub async fn csv(
+        bytes: &[u8],
+        query: CsvImportQuery,
+        connection_pool: &PgPool,
+    ) -> Result<CsvImportReport, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(bytes);
+        let headers = reader
+            .headers()
+            .map_err(|error| Error::unprocessable_entity([("file", error.to_string())]))?
+            .clone();
+
+        let mut columns = HashMap::new();
+        let mut errors = Vec::new();
+        for field in CSV_FIELDS {
+            let header = query.columns.get(field).map_or(field, String::as_str);
+            match headers
+                .iter()
+                .position(|column| column_key(column) == column_key(header))
+            {
+                Some(index) => {
+                    columns.insert(field, index);
+                }
+                None if query.columns.contains_key(field) => {
+                    errors.push((field, format!("no column is named {header}")));
+                }
+                None => {}
+            }
+        }
+        if !columns.contains_key("title") && errors.is_empty() {
+            errors.push(("file", "has no title column".to_string()));
+        }
+        if !errors.is_empty() {
+            return Err(Error::unprocessable_entity(errors));
+        }
+
+        let mut transaction = connection_pool.begin().await?;
+        let mut report = CsvImportReport {
+            dry_run: query.dry_run,
+            ..CsvImportReport::default()
+        };
+        for (index, record) in reader.records().enumerate() {
+            let imported = match record {
+                Ok(record) => {
+                    let value = |field| {
+                        columns
+                            .get(field)
+                            .and_then(|index| record.get(*index))
+                            .unwrap_or_default()
+                            .to_string()
+                    };
+                    let book = BookQuery {
+                        author: value("author"),
+                        title: value("title"),
+                        isbn: value("isbn"),
+                        lccn: value("lccn"),
+                        publish_date: value("publish_date"),
+                    };
+                    Self::csv_row(book, &mut transaction).await?
+                }
+                Err(error) => CsvImportRow::failed(HashMap::from([(
+                    "row".to_string(),
+                    vec![error.to_string()],
+                )])),
+            };
+            // The header is row 1
+            report.push(index + 2, imported);
+        }
+
+        if report.failed == 0 && !query.dry_run {
+            transaction.commit().await?;
+            report.committed = true;
+        }
+
+        Ok(report)
+    }
+
+    /// Creates the book in a CSV row, or fills in the fields of the book it
+    /// shares an ISBN or LCCN with. A blank row, or one that would change
+    /// nothing, is skipped.
+    async fn csv_row(
+        book: BookQuery,
+        connection: &mut PgConnection,
+    ) -> Result<CsvImportRow, sqlx::Error> {
+        let fields = [
+            &book.author,
+            &book.title,
+            &book.isbn,
+            &book.lccn,
+            &book.publish_date,
+        ];
+        if fields.iter().all(|field| field.is_empty()) {
+            return Ok(CsvImportRow::new(RowAction::Skipped, None));
+        }
+
+        let mut errors: HashMap<String, Vec<String>> = HashMap::new();
+        let mut error = |field: &str, message: String| {
+            errors.entry(field.to_string()).or_default().push(message);
+        };
+        let isbn_13 = isbn::normalize_field(&book.isbn)
+            .map_err(|isbn_error| error("isbn", isbn_error.to_string()))
+            .ok()
+            .flatten();
+        let lccn_normalized = lccn::normalize_field(&book.lccn)
+            .map_err(|lccn_error| error("lccn", lccn_error.to_string()))
+            .ok()
+            .flatten();
+        let existing = if isbn_13.is_some() || lccn_normalized.is_some() {
+            Db::find_book_by_identifier(
+                isbn_13.as_deref(),
+                lccn_normalized.as_deref(),
+                &mut *connection,
+            )
+            .await?
+        } else {
+            None
+        };
+        if existing.is_none() && book.title.is_empty() {
+            error("title", "is missing".to_string());
+        }
+        if !errors.is_empty() {
+            return Ok(CsvImportRow::failed(errors));
+        }
+
+        let published = publish_date::parse(&book.publish_date);
+        let Some(existing) = existing else {
+            let id = Db::create_book(book, isbn_13, lccn_normalized, published, connection).await?;
+            return Ok(CsvImportRow::new(RowAction::Created, Some(id)));
+        };
+
+        let update = changes(
+            &existing,
+            book,
+            isbn_13.as_deref(),
+            lccn_normalized.as_deref(),
+        );
+        let id = existing.master_book_id;
+        if [
+            &update.author,
+            &update.title,
+            &update.isbn,
+            &update.lccn,
+            &update.publish_date,
+        ]
+        .iter()
+        .all(|field| field.is_none())
+        {
+            return Ok(CsvImportRow::new(RowAction::Skipped, Some(id)));
+        }
+
+        Db::update_book(update, isbn_13, lccn_normalized, published, connection).await?;
+
+        Ok(CsvImportRow::new(RowAction::Updated, Some(id)))
+    }
+
+    /// Checks a book the way `create_book` does and creates it, or reports
+    /// what is wrong with it. A database failure is reported against the
+    /// record too, so the records around it still import and the report
+    /// still says which ones did.
+    pub(crate) async fn book(book: BookQuery, connection_pool: &PgPool) -> ImportedRecord {
+        let mut errors = Vec::new();
+        if book.title.trim().is_empty() {
+            errors.push("title: is missing".to_string());
+        }
+        let isbn_13 = isbn::normalize_field(&book.isbn)
+            .map_err(|error| errors.push(format!("isbn: {error}")))
+            .ok()
+            .flatten();
+        let lccn_normalized = lccn::normalize_field(&book.lccn)
+            .map_err(|error| errors.push(format!("lccn: {error}")))
+            .ok()
+            .flatten();
+        if !errors.is_empty() {
+            let mut failed = ImportedRecord::failed(errors);
+            failed.title = Some(book.title);
+            return failed;
+        }
+
+        let published = publish_date::parse(&book.publish_date);
+        let title = book.title.clone();
+        let id: Uuid =
+            match Db::create_book(book, isbn_13, lccn_normalized, published, connection_pool).await
+            {
+                Ok(id) => id,
+                Err(error) => {
+                    tracing::error!("importing MARC record {title:?} failed: {error}");
+                    let mut failed = ImportedRecord::failed(vec![
+                        "could not be saved to the catalog".to_string(),
+                    ]);
+                    failed.title = Some(title);
+                    return failed;
+                }
+            };
+
+        ImportedRecord {
+            record: 0,
+            control_number: None,
+            title: Some(title),
+            id: Some(id),
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// The fields of `book` that are filled in and differ from `existing`. An
+/// ISBN or LCCN only differs if its normalized form does.
+fn changes(
+    existing: &BookFromQuery,
+    book: BookQuery,
+    isbn_13: Option<&str>,
+    lccn_normalized: Option<&str>,
+) -> BookUpdateQuery {
+    let changed = |value: String, current: Option<&str>| {
+        (!value.is_empty() && current != Some(value.as_str())).then_some(value)
+    };
+
+    BookUpdateQuery {
+        id: existing.master_book_id,
+        author: changed(book.author, existing.author.as_deref()),
+        title: changed(book.title, existing.title.as_deref()),
+        isbn: (isbn_13.is_some() && isbn_13 != existing.isbn_13.as_deref()).then_some(book.isbn),
+        lccn: (lccn_normalized.is_some() && lccn_normalized != existing.lccn_normalized.as_deref())
+            .then_some(book.lccn),
+        publish_date: changed(book.publish_date, existing.publish_date.as_deref()),
+    }
+}
+
+/// A CSV header as it is matched against a field name, so `Publish Date`
+/// finds `publish_date`
+fn column_key(header: &str) -> String {
+    header.trim().to_lowercase().replace([' ', '-'], "_")
+}
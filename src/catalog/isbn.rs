@@ -0,0 +1,127 @@
+/// Why a string isn't an ISBN
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum IsbnError {
+    #[error("must have 10 or 13 digits")]
+    Length,
+
+    #[error("may only contain digits, hyphens and spaces, and a final X on an ISBN-10")]
+    Characters,
+
+    #[error("check digit does not match")]
+    CheckDigit,
+
+    #[error("must start with 978 or 979 to be an ISBN-13")]
+    Prefix,
+}
+
+/// Normalizes an ISBN-10 or ISBN-13 as typed, hyphens and all, to the bare
+/// 13 digits of its ISBN-13
+pub fn normalize(input: &str) -> Result<String, IsbnError> {
+    let compact: String = input
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if !compact.is_ascii() {
+        return Err(IsbnError::Characters);
+    }
+
+    match compact.len() {
+        10 => {
+            validate_isbn10(&compact)?;
+            Ok(isbn10_to_isbn13(&compact))
+        }
+        13 => {
+            validate_isbn13(&compact)?;
+            Ok(compact)
+        }
+        _ => Err(IsbnError::Length),
+    }
+}
+
+/// Normalizes an optional ISBN field, where a blank value means there is none
+pub fn normalize_field(input: &str) -> Result<Option<String>, IsbnError> {
+    if input.trim().is_empty() {
+        Ok(None)
+    } else {
+        normalize(input).map(Some)
+    }
+}
+
+/// The ISBN-10 for a bare ISBN-13, if it has one. Only `978` ISBNs do.
+pub fn isbn13_to_isbn10(isbn13: &str) -> Option<String> {
+    let body = isbn13.strip_prefix("978")?.get(..9)?;
+    let digits = digits(body)?;
+    let sum: u32 = digits
+        .iter()
+        .zip((2..=10).rev())
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+
+    let check = match (11 - sum % 11) % 11 {
+        10 => 'X',
+        check => char::from_digit(check, 10)?,
+    };
+
+    Some(format!("{body}{check}"))
+}
+
+/// Converts a bare, valid ISBN-10 to its ISBN-13
+fn isbn10_to_isbn13(isbn10: &str) -> String {
+    let body = format!("978{}", &isbn10[..9]);
+    let check = isbn13_check_digit(&digits(&body).expect("validated as digits"));
+
+    format!("{body}{check}")
+}
+
+fn validate_isbn10(isbn10: &str) -> Result<(), IsbnError> {
+    let body = digits(&isbn10[..9]).ok_or(IsbnError::Characters)?;
+    let check = match isbn10.as_bytes()[9] {
+        b'X' => 10,
+        c @ b'0'..=b'9' => u32::from(c - b'0'),
+        _ => return Err(IsbnError::Characters),
+    };
+
+    let sum: u32 = body
+        .iter()
+        .chain(std::iter::once(&check))
+        .zip((1..=10).rev())
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+
+    if sum.is_multiple_of(11) {
+        Ok(())
+    } else {
+        Err(IsbnError::CheckDigit)
+    }
+}
+
+/// An ISBN-13 is an EAN-13 in the Bookland prefixes; other EANs can have a
+/// good check digit and still not be ISBNs
+fn validate_isbn13(isbn13: &str) -> Result<(), IsbnError> {
+    let digits = digits(isbn13).ok_or(IsbnError::Characters)?;
+    if !(isbn13.starts_with("978") || isbn13.starts_with("979")) {
+        return Err(IsbnError::Prefix);
+    }
+
+    if isbn13_check_digit(&digits[..12]) == digits[12] {
+        Ok(())
+    } else {
+        Err(IsbnError::CheckDigit)
+    }
+}
+
+/// The check digit for the first 12 digits of an ISBN-13
+fn isbn13_check_digit(body: &[u32]) -> u32 {
+    let sum: u32 = body
+        .iter()
+        .zip([1, 3].iter().cycle())
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+
+    (10 - sum % 10) % 10
+}
+
+fn digits(s: &str) -> Option<Vec<u32>> {
+    s.chars().map(|c| c.to_digit(10)).collect()
+}
@@ -0,0 +1,63 @@
+/// Why a string isn't an LCCN
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum LccnError {
+    #[error("serial number after the hyphen must be 1 to 6 digits")]
+    Serial,
+
+    #[error("must be an optional 1 to 3 letter prefix followed by a 2 or 4 digit year and a 6 digit serial")]
+    Format,
+}
+
+/// Normalizes a Library of Congress Control Number as typed, following the
+/// Library of Congress algorithm:
+///
+/// 1. Remove all blanks.
+/// 2. Drop a forward slash and everything after it.
+/// 3. Remove a hyphen, left padding the serial number after it with zeros to
+///    six digits.
+///
+/// The result must then be a lowercase prefix of up to three letters, a year
+/// of two or four digits, and the six digit serial, e.g. `n78-890351` becomes
+/// `n78890351` and `85-2` becomes `85000002`.
+pub fn normalize(input: &str) -> Result<String, LccnError> {
+    let compact: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    let compact = match compact.split_once('/') {
+        Some((before, _)) => before,
+        None => &compact,
+    };
+
+    let normalized = match compact.split_once('-') {
+        Some((prefix_and_year, serial)) => {
+            if serial.is_empty() || serial.len() > 6 || !serial.chars().all(|c| c.is_ascii_digit())
+            {
+                return Err(LccnError::Serial);
+            }
+            format!("{prefix_and_year}{serial:0>6}")
+        }
+        None => compact.to_string(),
+    }
+    .to_ascii_lowercase();
+
+    let digits_at = normalized
+        .find(|c: char| c.is_ascii_digit())
+        .ok_or(LccnError::Format)?;
+    let (prefix, number) = normalized.split_at(digits_at);
+    let prefix_ok = prefix.len() <= 3 && prefix.chars().all(|c| c.is_ascii_lowercase());
+    let number_ok =
+        (number.len() == 8 || number.len() == 10) && number.chars().all(|c| c.is_ascii_digit());
+
+    if prefix_ok && number_ok {
+        Ok(normalized)
+    } else {
+        Err(LccnError::Format)
+    }
+}
+
+/// Normalizes an optional LCCN field, where a blank value means there is none
+pub fn normalize_field(input: &str) -> Result<Option<String>, LccnError> {
+    if input.trim().is_empty() {
+        Ok(None)
+    } else {
+        normalize(input).map(Some)
+    }
+}
@@ -0,0 +1,105 @@
+use quick_xml::escape::escape;
+use serde_json::{json, Value};
+
+use crate::catalog::publish_date::DatePrecision;
+use crate::catalog::text;
+use crate::routes::Book;
+
+/// The namespace of Dublin Core's elements
+pub const DUBLIN_CORE_NAMESPACE: &str = "http://purl.org/dc/elements/1.1/";
+
+/// The namespace of the `oai_dc:dc` element Dublin Core records are wrapped
+/// in, as OAI-PMH harvesters expect them
+pub const OAI_DC_NAMESPACE: &str = "http://www.openarchives.org/OAI/2.0/oai_dc/";
+
+const OAI_DC_SCHEMA: &str = "http://www.openarchives.org/OAI/2.0/oai_dc.xsd";
+
+/// Where the Library of Congress publishes the record for an LCCN
+const LCCN_PERMALINK: &str = "https://lccn.loc.gov/";
+
+/// A book as a schema.org `Book` in JSON-LD
+pub fn schema_org(book: &Book) -> Value {
+    let mut item = json!({
+        "@context": "https://schema.org",
+        "@type": "Book",
+        "@id": format!("urn:uuid:{}", book.id),
+        "identifier": book.id,
+    });
+
+    if let Some(title) = text(&book.title) {
+        item["name"] = json!(title);
+    }
+    if let Some(author) = text(&book.author) {
+        item["author"] = json!({ "@type": "Person", "name": author });
+    }
+    if let Some(isbn) = text(&book.isbn_13) {
+        item["isbn"] = json!(isbn);
+    }
+    if let Some(date) = iso_date(book) {
+        item["datePublished"] = json!(date);
+    }
+    if let Some(lccn) = text(&book.lccn_normalized) {
+        item["sameAs"] = json!(format!("{LCCN_PERMALINK}{lccn}"));
+    }
+
+    item
+}
+
+/// A book as a simple Dublin Core record: an `oai_dc:dc` element holding
+/// its `dublin_core_elements`
+pub fn dublin_core(book: &Book) -> String {
+    format!(
+        "<oai_dc:dc xmlns:oai_dc=\"{OAI_DC_NAMESPACE}\" xmlns:dc=\"{DUBLIN_CORE_NAMESPACE}\" \
+         xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xsi:schemaLocation=\"{OAI_DC_NAMESPACE} {OAI_DC_SCHEMA}\">{}</oai_dc:dc>",
+        dublin_core_elements(book)
+    )
+}
+
+/// The `dc:` elements describing a book, for wrapping in whatever element
+/// a protocol expects. A date only known to its decade or century is given
+/// as written.
+pub fn dublin_core_elements(book: &Book) -> String {
+    let mut elements = Vec::new();
+    if let Some(title) = text(&book.title) {
+        elements.push(("title", title.to_string()));
+    }
+    if let Some(author) = text(&book.author) {
+        elements.push(("creator", author.to_string()));
+    }
+    if let Some(date) = iso_date(book).or_else(|| text(&book.publish_date).map(str::to_string)) {
+        elements.push(("date", date));
+    }
+    elements.push(("type", "Text".to_string()));
+    elements.push(("identifier", format!("urn:uuid:{}", book.id)));
+    if let Some(isbn) = text(&book.isbn_13) {
+        elements.push(("identifier", format!("urn:isbn:{isbn}")));
+    }
+    if let Some(lccn) = text(&book.lccn_normalized) {
+        elements.push(("identifier", format!("{LCCN_PERMALINK}{lccn}")));
+    }
+
+    elements
+        .into_iter()
+        .map(|(name, value)| format!("<dc:{name}>{}</dc:{name}>", escape(&value)))
+        .collect()
+}
+
+/// The publication date in ISO 8601, to the precision it is known, if that
+/// is at least its year
+pub(crate) fn iso_date(book: &Book) -> Option<String> {
+    let published = book.published.as_ref()?;
+    let on = published.on;
+
+    match published.precision {
+        DatePrecision::Century | DatePrecision::Decade => None,
+        DatePrecision::Year => Some(format!("{:04}", on.year())),
+        DatePrecision::Month => Some(format!("{:04}-{:02}", on.year(), u8::from(on.month()))),
+        DatePrecision::Day => Some(format!(
+            "{:04}-{:02}-{:02}",
+            on.year(),
+            u8::from(on.month()),
+            on.day()
+        )),
+    }
+}
@@ -0,0 +1,546 @@
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::catalog::publish_date::DatePrecision;
+use crate::routes::{BookFromQuery, BookQuery};
+
+/// Ends a record in ISO 2709
+pub const RECORD_TERMINATOR: u8 = 0x1d;
+
+/// Ends the directory and each field in ISO 2709
+pub const FIELD_TERMINATOR: u8 = 0x1e;
+
+/// Starts each subfield in ISO 2709
+pub const SUBFIELD_DELIMITER: u8 = 0x1f;
+
+/// The MARCXML schema namespace
+pub const MARCXML_NAMESPACE: &str = "http://www.loc.gov/MARC21/slim";
+
+/// The leader written for a book: a new, complete record for a printed
+/// monograph, in UTF-8
+const BOOK_LEADER: &str = "00000nam a2200000   4500";
+
+const LEADER_LENGTH: usize = 24;
+const DIRECTORY_ENTRY_LENGTH: usize = 12;
+
+/// The longest field ISO 2709 can hold, terminator and all, its length
+/// being written in four digits
+const MAX_FIELD_LENGTH: usize = 9999;
+
+/// The longest record ISO 2709 can hold, its length being written in five
+/// digits. Every field starts within the record, so this bounds their
+/// offsets too.
+const MAX_RECORD_LENGTH: usize = 99999;
+
+/// Why a MARC record couldn't be read or written
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum MarcError {
+    #[error("leader must be 24 characters")]
+    Leader,
+
+    #[error("directory is malformed")]
+    Directory,
+
+    #[error("field {0} runs past the end of the record")]
+    Field(String),
+
+    #[error("not well formed XML: {0}")]
+    Xml(String),
+
+    #[error("field {0} is too long for ISO 2709")]
+    FieldTooLong(String),
+
+    #[error("record is too long for ISO 2709")]
+    RecordTooLong,
+}
+
+/// A MARC 21 bibliographic record
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MarcRecord {
+    pub leader: String,
+    pub control_fields: Vec<ControlField>,
+    pub data_fields: Vec<DataField>,
+}
+
+/// A field tagged `001` to `009`, holding a single value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ControlField {
+    pub tag: String,
+    pub value: String,
+}
+
+/// A field tagged `010` and up, holding two indicators and coded subfields
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataField {
+    pub tag: String,
+    pub ind1: char,
+    pub ind2: char,
+    pub subfields: Vec<(char, String)>,
+}
+
+impl DataField {
+    /// The first subfield with `code`
+    pub fn subfield(&self, code: char) -> Option<&str> {
+        self.subfields
+            .iter()
+            .find(|(subfield_code, _)| *subfield_code == code)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl MarcRecord {
+    pub fn fields<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a DataField> + 'a {
+        self.data_fields
+            .iter()
+            .filter(move |field| field.tag == tag)
+    }
+
+    /// The first subfield `code` of the first field tagged `tag` that has one
+    pub fn subfield(&self, tag: &str, code: char) -> Option<&str> {
+        self.data_fields
+            .iter()
+            .filter(|field| field.tag == tag)
+            .find_map(|field| field.subfield(code))
+    }
+
+    /// Maps the record onto a book:
+    ///
+    /// - `020 $a` the ISBN, less any qualifier like `(pbk.)`
+    /// - `010 $a` the LCCN
+    /// - `100 $a` the author
+    /// - `245 $a $b` the title
+    /// - `264 $c` for a publication, else `260 $c`, the date published
+    ///
+    /// The punctuation MARC leaves at the end of each field is dropped.
+    pub fn to_book_query(&self) -> BookQuery {
+        let isbn = self
+            .subfield("020", 'a')
+            .and_then(|isbn| isbn.split(|c: char| c == '(' || c.is_whitespace()).next())
+            .unwrap_or_default();
+        let title = self
+            .fields("245")
+            .next()
+            .map(|field| {
+                field
+                    .subfields
+                    .iter()
+                    .filter(|(code, _)| *code == 'a' || *code == 'b')
+                    .map(|(_, value)| value.trim())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .unwrap_or_default();
+        let publish_date = self
+            .fields("264")
+            .find(|field| field.ind2 == '1')
+            .and_then(|field| field.subfield('c'))
+            .or_else(|| self.subfield("260", 'c'))
+            .or_else(|| self.subfield("264", 'c'))
+            .unwrap_or_default();
+
+        BookQuery {
+            lccn: self
+                .subfield("010", 'a')
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+            isbn: isbn.to_string(),
+            title: trim_punctuation(&title).to_string(),
+            author: trim_punctuation(self.subfield("100", 'a').unwrap_or_default()).to_string(),
+            publish_date: publish_date.trim().trim_end_matches('.').to_string(),
+        }
+    }
+}
+
+impl MarcRecord {
+    /// A minimal record for a book, the reverse of `to_book_query`: the
+    /// book's id in `001`, its publication date coded in `008`, and `010`,
+    /// `020`, `100`, `245` and `264` for whichever of the LCCN, ISBN, author,
+    /// title and date it has
+    pub fn from_book(book: &BookFromQuery) -> Self {
+        let mut data_fields = Vec::new();
+        let mut push = |tag: &str, ind1, ind2, code, value: Option<&str>| {
+            if let Some(value) = value.filter(|value| !value.trim().is_empty()) {
+                data_fields.push(DataField {
+                    tag: tag.to_string(),
+                    ind1,
+                    ind2,
+                    subfields: vec![(code, value.to_string())],
+                });
+            }
+        };
+
+        let lccn = book.lccn_normalized.as_deref().map(lccn_field);
+        push(
+            "010",
+            ' ',
+            ' ',
+            'a',
+            lccn.as_deref().or(book.lccn.as_deref()),
+        );
+        push(
+            "020",
+            ' ',
+            ' ',
+            'a',
+            book.isbn_13.as_deref().or(book.isbn.as_deref()),
+        );
+        push("100", '1', ' ', 'a', book.author.as_deref());
+        let has_author = book
+            .author
+            .as_deref()
+            .is_some_and(|author| !author.trim().is_empty());
+        push(
+            "245",
+            if has_author { '1' } else { '0' },
+            '0',
+            'a',
+            book.title.as_deref(),
+        );
+        push("264", ' ', '1', 'c', book.publish_date.as_deref());
+
+        Self {
+            leader: BOOK_LEADER.to_string(),
+            control_fields: vec![
+                ControlField {
+                    tag: "001".to_string(),
+                    value: book.master_book_id.to_string(),
+                },
+                ControlField {
+                    tag: "008".to_string(),
+                    value: fixed_length_data(book),
+                },
+            ],
+            data_fields,
+        }
+    }
+
+    /// Writes the record in ISO 2709, working out the record length and base
+    /// address in the leader. A field or record too long for the format's
+    /// fixed-width lengths is refused rather than written with lengths that
+    /// overflow them; MARCXML has no such limits.
+    pub fn to_iso2709(&self) -> Result<Vec<u8>, MarcError> {
+        let mut directory = Vec::new();
+        let mut data = Vec::new();
+        let mut push = |tag: &str, field: Vec<u8>| {
+            let length = field.len() + 1;
+            if length > MAX_FIELD_LENGTH {
+                return Err(MarcError::FieldTooLong(tag.to_string()));
+            }
+            directory.extend(format!("{tag:.3}{length:04}{:05}", data.len()).bytes());
+            data.extend(field);
+            data.push(FIELD_TERMINATOR);
+            Ok(())
+        };
+
+        for field in &self.control_fields {
+            push(&field.tag, field.value.as_bytes().to_vec())?;
+        }
+        for field in &self.data_fields {
+            let mut bytes = format!("{}{}", field.ind1, field.ind2).into_bytes();
+            for (code, value) in &field.subfields {
+                bytes.push(SUBFIELD_DELIMITER);
+                bytes.extend(code.to_string().bytes());
+                bytes.extend(value.bytes());
+            }
+            push(&field.tag, bytes)?;
+        }
+        directory.push(FIELD_TERMINATOR);
+
+        let base_address = LEADER_LENGTH + directory.len();
+        let record_length = base_address + data.len() + 1;
+        if record_length > MAX_RECORD_LENGTH {
+            return Err(MarcError::RecordTooLong);
+        }
+        let leader = Some(self.leader.as_str())
+            .filter(|leader| leader.len() == LEADER_LENGTH && leader.is_ascii())
+            .unwrap_or(BOOK_LEADER);
+        let mut record = format!(
+            "{record_length:05}{}{base_address:05}{}",
+            &leader[5..12],
+            &leader[17..24]
+        )
+        .into_bytes();
+        record.extend(directory);
+        record.extend(data);
+        record.push(RECORD_TERMINATOR);
+        Ok(record)
+    }
+
+    /// Writes the record as a MARCXML `record` element, to go inside a
+    /// `collection`
+    pub fn to_marcxml(&self) -> String {
+        self.marcxml("<record>")
+    }
+
+    /// Writes the record as a MARCXML `record` element declaring its own
+    /// namespace, to go inside a document of another kind
+    pub fn to_namespaced_marcxml(&self) -> String {
+        self.marcxml(&format!("<record xmlns=\"{MARCXML_NAMESPACE}\">"))
+    }
+
+    fn marcxml(&self, start: &str) -> String {
+        let mut xml = format!("{start}<leader>{}</leader>", escape(&self.leader));
+        for field in &self.control_fields {
+            xml.push_str(&format!(
+                "<controlfield tag=\"{}\">{}</controlfield>",
+                escape(&field.tag),
+                escape(&field.value)
+            ));
+        }
+        for field in &self.data_fields {
+            xml.push_str(&format!(
+                "<datafield tag=\"{}\" ind1=\"{}\" ind2=\"{}\">",
+                escape(&field.tag),
+                escape(&field.ind1.to_string()),
+                escape(&field.ind2.to_string())
+            ));
+            for (code, value) in &field.subfields {
+                xml.push_str(&format!(
+                    "<subfield code=\"{}\">{}</subfield>",
+                    escape(&code.to_string()),
+                    escape(value)
+                ));
+            }
+            xml.push_str("</datafield>");
+        }
+        xml.push_str("</record>\n");
+        xml
+    }
+}
+
+/// Opens a MARCXML document of many records
+pub fn marcxml_collection_start() -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<collection xmlns=\"{MARCXML_NAMESPACE}\">\n"
+    )
+}
+
+/// Closes what `marcxml_collection_start` opened
+pub const MARCXML_COLLECTION_END: &str = "</collection>\n";
+
+/// The `010 $a` form of a normalized LCCN: the prefix padded to three
+/// characters before an 8 digit number and a trailing blank, or to two
+/// before a 10 digit one
+fn lccn_field(lccn_normalized: &str) -> String {
+    let digits_at = lccn_normalized
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(lccn_normalized.len());
+    let (prefix, digits) = lccn_normalized.split_at(digits_at);
+
+    if digits.len() == 8 {
+        format!("{prefix:<3}{digits} ")
+    } else {
+        format!("{prefix:<2}{digits}")
+    }
+}
+
+/// The 40 characters of `008`, coding only the type of date and when the
+/// book was published. The rest are left as fill characters.
+fn fixed_length_data(book: &BookFromQuery) -> String {
+    let precision = book
+        .published_precision
+        .as_deref()
+        .and_then(DatePrecision::parse);
+    let (date_type, date1) = match (book.published_on, precision) {
+        (Some(on), Some(precision)) => {
+            let year = format!("{:04}", on.year());
+            let date1 = match precision {
+                DatePrecision::Century => format!("{}uu", &year[..2]),
+                DatePrecision::Decade => format!("{}u", &year[..3]),
+                _ => year,
+            };
+            let date_type = if book.published_approximate { 'q' } else { 's' };
+            (date_type, date1)
+        }
+        _ => ('n', "uuuu".to_string()),
+    };
+
+    format!("||||||{date_type}{date1}    xx {}und|d", "|".repeat(17))
+}
+
+/// Drops the ISBD punctuation that separates one MARC field from the next
+fn trim_punctuation(value: &str) -> &str {
+    value
+        .trim()
+        .trim_end_matches([',', '/', ':', ';', '='])
+        .trim_end()
+}
+
+/// Reads a MARC file, either ISO 2709 or MARCXML, into its records. A
+/// record that can't be read doesn't stop the ones after it, but MARCXML
+/// that isn't well formed can't be read at all.
+pub fn parse(bytes: &[u8]) -> Result<Vec<Result<MarcRecord, MarcError>>, MarcError> {
+    let start = bytes
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace() && !matches!(byte, 0xef | 0xbb | 0xbf));
+
+    match start.map(|start| bytes[start]) {
+        Some(b'<') => parse_marcxml(&String::from_utf8_lossy(bytes)),
+        _ => Ok(parse_iso2709(bytes)),
+    }
+}
+
+/// Reads ISO 2709 records, as MARC 21 is exchanged. Text is read as UTF-8,
+/// so records in MARC-8 only come through cleanly where they are ASCII.
+pub fn parse_iso2709(bytes: &[u8]) -> Vec<Result<MarcRecord, MarcError>> {
+    bytes
+        .split(|byte| *byte == RECORD_TERMINATOR)
+        .map(|record| record.trim_ascii_start())
+        .filter(|record| !record.is_empty())
+        .map(parse_iso2709_record)
+        .collect()
+}
+
+fn parse_iso2709_record(record: &[u8]) -> Result<MarcRecord, MarcError> {
+    let leader = record.get(..LEADER_LENGTH).ok_or(MarcError::Leader)?;
+    let base_address: usize = number(&leader[12..17]).ok_or(MarcError::Leader)?;
+    let directory = record
+        .get(LEADER_LENGTH..base_address.saturating_sub(1))
+        .filter(|directory| directory.len() % DIRECTORY_ENTRY_LENGTH == 0)
+        .ok_or(MarcError::Directory)?;
+
+    let mut marc = MarcRecord {
+        leader: String::from_utf8_lossy(leader).into_owned(),
+        ..MarcRecord::default()
+    };
+    for entry in directory.chunks(DIRECTORY_ENTRY_LENGTH) {
+        let tag = String::from_utf8_lossy(&entry[..3]).into_owned();
+        let length: usize = number(&entry[3..7]).ok_or(MarcError::Directory)?;
+        let start: usize = number(&entry[7..12]).ok_or(MarcError::Directory)?;
+        let data = record
+            .get(base_address + start..base_address + start + length)
+            .ok_or_else(|| MarcError::Field(tag.clone()))?;
+        let data = data.strip_suffix(&[FIELD_TERMINATOR]).unwrap_or(data);
+
+        if tag.starts_with("00") {
+            marc.control_fields.push(ControlField {
+                tag,
+                value: String::from_utf8_lossy(data).into_owned(),
+            });
+            continue;
+        }
+
+        let mut parts = data.split(|byte| *byte == SUBFIELD_DELIMITER);
+        let indicators: Vec<char> = String::from_utf8_lossy(parts.next().unwrap_or_default())
+            .chars()
+            .collect();
+        let subfields = parts
+            .filter_map(|subfield| {
+                let subfield = String::from_utf8_lossy(subfield);
+                let mut chars = subfield.chars();
+                let code = chars.next()?;
+                Some((code, chars.as_str().to_string()))
+            })
+            .collect();
+
+        marc.data_fields.push(DataField {
+            tag,
+            ind1: indicators.first().copied().unwrap_or(' '),
+            ind2: indicators.get(1).copied().unwrap_or(' '),
+            subfields,
+        });
+    }
+
+    Ok(marc)
+}
+
+fn number(digits: &[u8]) -> Option<usize> {
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+/// Reads MARCXML, either a `collection` of records or a single `record`
+pub fn parse_marcxml(xml: &str) -> Result<Vec<Result<MarcRecord, MarcError>>, MarcError> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut records = Vec::new();
+    let mut record: Option<MarcRecord> = None;
+    let mut text = String::new();
+    let mut subfield_code = None;
+    let xml_error = |error: quick_xml::Error| MarcError::Xml(error.to_string());
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(element) => {
+                text.clear();
+                match element.local_name().as_ref() {
+                    b"record" => record = Some(MarcRecord::default()),
+                    b"controlfield" => {
+                        if let Some(record) = record.as_mut() {
+                            record.control_fields.push(ControlField {
+                                tag: attribute(&element, "tag").unwrap_or_default(),
+                                value: String::new(),
+                            });
+                        }
+                    }
+                    b"datafield" => {
+                        if let Some(record) = record.as_mut() {
+                            let ind = |name| {
+                                attribute(&element, name)
+                                    .and_then(|value| value.chars().next())
+                                    .unwrap_or(' ')
+                            };
+                            record.data_fields.push(DataField {
+                                tag: attribute(&element, "tag").unwrap_or_default(),
+                                ind1: ind("ind1"),
+                                ind2: ind("ind2"),
+                                subfields: Vec::new(),
+                            });
+                        }
+                    }
+                    b"subfield" => {
+                        subfield_code =
+                            attribute(&element, "code").and_then(|code| code.chars().next());
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(value) => text.push_str(&value.unescape().map_err(xml_error)?),
+            Event::CData(value) => text.push_str(&String::from_utf8_lossy(&value)),
+            Event::End(element) => {
+                let Some(current) = record.as_mut() else {
+                    continue;
+                };
+                match element.local_name().as_ref() {
+                    b"leader" => current.leader = std::mem::take(&mut text),
+                    b"controlfield" => {
+                        if let Some(field) = current.control_fields.last_mut() {
+                            field.value = std::mem::take(&mut text);
+                        }
+                    }
+                    b"subfield" => {
+                        if let (Some(field), Some(code)) =
+                            (current.data_fields.last_mut(), subfield_code.take())
+                        {
+                            field.subfields.push((code, std::mem::take(&mut text)));
+                        }
+                    }
+                    b"record" => {
+                        let finished = record.take().unwrap_or_default();
+                        if finished.leader.len() == LEADER_LENGTH {
+                            records.push(Ok(finished));
+                        } else {
+                            records.push(Err(MarcError::Leader));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(records)
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|attribute| attribute.unescape_value().ok())
+        .map(|value| value.into_owned())
+}
@@ -0,0 +1,102 @@
+use sqlx::PgPool;
+
+use crate::circulation::hold::Holds;
+use crate::circulation::today;
+use crate::db::Db;
+use crate::routes::{BookMergeFromQuery, Error, MergeQuery};
+
+/// How similar titles and authors must be, from 0 to 1, before two records
+/// are offered as duplicates when the client doesn't say
+pub const DEFAULT_DUPLICATE_THRESHOLD: f32 = 0.6;
+
+/// Merging duplicate bibliographic records into one
+pub struct Merges;
+
+impl Merges {
+    /// Merges one record into another. The copies and holds of the record
+    /// merged move to the record kept, its active holds joining the back of
+    /// the kept record's queue in their old order. A patron holding both
+    /// titles keeps only their hold on the record kept. The merged record is
+    /// then deleted, leaving a snapshot of it in the merge history.
+    pub async fn merge(
+        merge_query: MergeQuery,
+        connection_pool: &PgPool,
+    ) -> Result<BookMergeFromQuery, Error> {
+        let keep_id = merge_query.keep_id;
+        let merge_id = merge_query.merge_id;
+        if keep_id == merge_id {
+            return Err(Error::unprocessable_entity([(
+                "merge_id",
+                "must be a different book from keep_id",
+            )]));
+        }
+
+        let mut transaction = connection_pool.begin().await?;
+
+        // Lock in a fixed order so two merges of the same pair can't deadlock
+        let (first, second) = if keep_id < merge_id {
+            (keep_id, merge_id)
+        } else {
+            (merge_id, keep_id)
+        };
+        let first_book = Db::lock_book(&first, &mut transaction).await?;
+        let second_book = Db::lock_book(&second, &mut transaction).await?;
+        let (kept, merged) = if first == keep_id {
+            (first_book, second_book)
+        } else {
+            (second_book, first_book)
+        };
+        kept.ok_or_else(|| Error::unprocessable_entity([("keep_id", "does not exist")]))?;
+        merged.ok_or_else(|| Error::unprocessable_entity([("merge_id", "does not exist")]))?;
+
+        let copies_moved = Db::move_book_copies(&merge_id, &keep_id, &mut transaction).await?;
+
+        let kept_queue = Db::lock_hold_queue(&keep_id, &mut transaction).await?;
+        let merged_queue = Db::lock_hold_queue(&merge_id, &mut transaction).await?;
+        let mut queue_position = kept_queue
+            .iter()
+            .map(|hold| hold.queue_position)
+            .max()
+            .unwrap_or(0);
+        let mut holds_moved = 0;
+        let mut holds_cancelled = 0;
+        let mut freed_copies = Vec::new();
+        for hold in &merged_queue {
+            let duplicate = kept_queue
+                .iter()
+                .any(|kept_hold| kept_hold.patron_id == hold.patron_id);
+            if duplicate {
+                Db::set_hold_status(&hold.hold_id, "cancelled", &mut transaction).await?;
+                if let (Some(book_copy_id), "trapped") = (hold.book_copy_id, hold.status.as_str()) {
+                    freed_copies.push(book_copy_id);
+                }
+                holds_cancelled += 1;
+            } else {
+                queue_position += 1;
+                Db::move_hold(&hold.hold_id, &keep_id, queue_position, &mut transaction).await?;
+                holds_moved += 1;
+            }
+        }
+        Db::move_inactive_holds(&merge_id, &keep_id, &mut transaction).await?;
+
+        // A copy held for a cancelled hold goes on to the merged queue
+        for book_copy_id in freed_copies {
+            Holds::trap(&keep_id, &book_copy_id, today(), &mut transaction).await?;
+        }
+
+        let merge = Db::record_book_merge(
+            &keep_id,
+            &merge_id,
+            copies_moved as i32,
+            holds_moved,
+            holds_cancelled,
+            merge_query.merged_by,
+            &mut transaction,
+        )
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(merge)
+    }
+}
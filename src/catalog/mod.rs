@@ -0,0 +1,25 @@
+pub mod backfill;
+pub mod citation;
+pub mod cql;
+pub mod enrichment;
+pub mod export;
+pub mod import;
+pub mod isbn;
+pub mod lccn;
+pub mod linked_data;
+pub mod marc;
+pub mod merge;
+pub mod oai;
+pub mod opds;
+pub mod publish_date;
+pub mod search;
+pub mod sru;
+
+/// A field's text with surrounding whitespace trimmed, or `None` if that
+/// leaves nothing
+pub(crate) fn text(value: &Option<String>) -> Option<&str> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
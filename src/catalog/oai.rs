@@ -0,0 +1,526 @@
+use quick_xml::escape::escape;
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime};
+
+use crate::catalog::linked_data::{dublin_core, OAI_DC_NAMESPACE};
+use crate::catalog::marc::{MarcRecord, MARCXML_NAMESPACE};
+use crate::db::Db;
+use crate::routes::pagination::{decode_cursor, Page};
+use crate::routes::{BookFromQuery, OaiBookFromQuery};
+
+/// The namespace of OAI-PMH responses
+pub const OAI_PMH_NAMESPACE: &str = "http://www.openarchives.org/OAI/2.0/";
+
+const OAI_PMH_SCHEMA: &str = "http://www.openarchives.org/OAI/2.0/OAI-PMH.xsd";
+
+/// How many records or headers a list response holds before it hands out a
+/// resumption token
+pub const OAI_PAGE_SIZE: i64 = 100;
+
+/// What `Identify` calls the repository
+pub const REPOSITORY_NAME: &str = "Library Catalog";
+
+/// The datestamp granularity the repository supports, as `Identify` states
+/// it. `from` and `until` may also be given as days.
+const GRANULARITY: &str = "YYYY-MM-DDThh:mm:ssZ";
+
+const DAY_FORMAT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]");
+
+const SECOND_FORMAT: &[FormatItem<'static>] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z");
+
+/// An OAI-PMH error, reported to the harvester in the response rather than
+/// by HTTP status
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum OaiError {
+    #[error("{0}")]
+    BadArgument(String),
+
+    #[error("the resumption token is invalid or has expired")]
+    BadResumptionToken,
+
+    #[error("the verb is missing, repeated or not an OAI-PMH verb")]
+    BadVerb,
+
+    #[error("the metadata format {0} is not supported")]
+    CannotDisseminateFormat(String),
+
+    #[error("no record has the identifier {0}")]
+    IdDoesNotExist(String),
+
+    #[error("no records match the request")]
+    NoRecordsMatch,
+
+    #[error("the repository does not support sets")]
+    NoSetHierarchy,
+}
+
+impl OaiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadArgument(_) => "badArgument",
+            Self::BadResumptionToken => "badResumptionToken",
+            Self::BadVerb => "badVerb",
+            Self::CannotDisseminateFormat(_) => "cannotDisseminateFormat",
+            Self::IdDoesNotExist(_) => "idDoesNotExist",
+            Self::NoRecordsMatch => "noRecordsMatch",
+            Self::NoSetHierarchy => "noSetHierarchy",
+        }
+    }
+}
+
+/// The formats every book can be harvested in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataFormat {
+    /// Simple Dublin Core, which every OAI-PMH repository must offer
+    OaiDc,
+    /// MARC 21 in MARCXML
+    Marc21,
+}
+
+impl MetadataFormat {
+    const ALL: [Self; 2] = [Self::OaiDc, Self::Marc21];
+
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::OaiDc => "oai_dc",
+            Self::Marc21 => "marc21",
+        }
+    }
+
+    pub fn from_prefix(prefix: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.prefix() == prefix)
+    }
+
+    fn schema(self) -> &'static str {
+        match self {
+            Self::OaiDc => "http://www.openarchives.org/OAI/2.0/oai_dc.xsd",
+            Self::Marc21 => "http://www.loc.gov/standards/marcxml/schema/MARC21slim.xsd",
+        }
+    }
+
+    fn namespace(self) -> &'static str {
+        match self {
+            Self::OaiDc => OAI_DC_NAMESPACE,
+            Self::Marc21 => MARCXML_NAMESPACE,
+        }
+    }
+
+    fn metadata(self, book: &BookFromQuery) -> String {
+        match self {
+            Self::OaiDc => dublin_core(&book.to_book()),
+            Self::Marc21 => MarcRecord::from_book(book).to_namespaced_marcxml(),
+        }
+    }
+}
+
+/// An OAI-PMH request, its arguments checked against its verb
+#[derive(Debug, PartialEq, Eq)]
+pub enum OaiRequest {
+    Identify,
+    ListMetadataFormats {
+        identifier: Option<String>,
+    },
+    ListSets,
+    ListIdentifiers(Harvest),
+    ListRecords(Harvest),
+    GetRecord {
+        identifier: String,
+        format: MetadataFormat,
+    },
+}
+
+/// A selective harvest: the books with datestamps from `from` up to but not
+/// including `before`, resuming after the `after` datestamp and id
+#[derive(Debug, PartialEq, Eq)]
+pub struct Harvest {
+    pub format: MetadataFormat,
+    pub from: Option<OffsetDateTime>,
+    pub before: Option<OffsetDateTime>,
+    pub after: Option<(OffsetDateTime, Uuid)>,
+    pub resumed: bool,
+}
+
+/// Where an incomplete list left off, handed to the harvester as a
+/// resumption token to send back for the next part of the list. Tokens
+/// carry the whole harvest, so they never expire.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ResumptionToken {
+    metadata_prefix: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    from: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    before: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    datestamp: OffsetDateTime,
+    id: Uuid,
+}
+
+impl OaiRequest {
+    /// Reads a request's arguments, in the order given. An argument given
+    /// twice, or one its verb doesn't take, is a `badArgument`.
+    pub fn parse(arguments: &[(String, String)]) -> Result<Self, OaiError> {
+        let mut by_name = HashMap::new();
+        for (name, value) in arguments {
+            if by_name.insert(name.as_str(), value.as_str()).is_some() {
+                return Err(match name.as_str() {
+                    "verb" => OaiError::BadVerb,
+                    _ => OaiError::BadArgument(format!("{name} is repeated")),
+                });
+            }
+        }
+
+        let verb = by_name.remove("verb").ok_or(OaiError::BadVerb)?;
+        let allowed: &[&str] = match verb {
+            "Identify" => &[],
+            "ListMetadataFormats" => &["identifier"],
+            "ListSets" => &["resumptionToken"],
+            "ListIdentifiers" | "ListRecords" => {
+                &["metadataPrefix", "from", "until", "set", "resumptionToken"]
+            }
+            "GetRecord" => &["identifier", "metadataPrefix"],
+            _ => return Err(OaiError::BadVerb),
+        };
+        if let Some(name) = arguments
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .find(|name| *name != "verb" && !allowed.contains(name))
+        {
+            return Err(OaiError::BadArgument(format!(
+                "{name} is not an argument of {verb}"
+            )));
+        }
+
+        let required = |name: &str| {
+            by_name
+                .get(name)
+                .map(|value| value.to_string())
+                .ok_or_else(|| OaiError::BadArgument(format!("{name} is required")))
+        };
+        match verb {
+            "Identify" => Ok(Self::Identify),
+            "ListMetadataFormats" => Ok(Self::ListMetadataFormats {
+                identifier: by_name.get("identifier").map(|value| value.to_string()),
+            }),
+            "ListSets" if by_name.contains_key("resumptionToken") => {
+                Err(OaiError::BadResumptionToken)
+            }
+            "ListSets" => Ok(Self::ListSets),
+            "ListIdentifiers" => Ok(Self::ListIdentifiers(Harvest::parse(&by_name)?)),
+            "ListRecords" => Ok(Self::ListRecords(Harvest::parse(&by_name)?)),
+            _ => {
+                let identifier = required("identifier")?;
+                let prefix = required("metadataPrefix")?;
+                let format = MetadataFormat::from_prefix(&prefix)
+                    .ok_or(OaiError::CannotDisseminateFormat(prefix))?;
+                Ok(Self::GetRecord { identifier, format })
+            }
+        }
+    }
+}
+
+impl Harvest {
+    fn parse(arguments: &HashMap<&str, &str>) -> Result<Self, OaiError> {
+        if let Some(token) = arguments.get("resumptionToken") {
+            if arguments.len() > 1 {
+                return Err(OaiError::BadArgument(
+                    "resumptionToken may not be given with other arguments".to_string(),
+                ));
+            }
+            let token: ResumptionToken =
+                decode_cursor(token).map_err(|_| OaiError::BadResumptionToken)?;
+            let format = MetadataFormat::from_prefix(&token.metadata_prefix)
+                .ok_or(OaiError::BadResumptionToken)?;
+
+            return Ok(Self {
+                format,
+                from: token.from,
+                before: token.before,
+                after: Some((token.datestamp, token.id)),
+                resumed: true,
+            });
+        }
+
+        let prefix = arguments
+            .get("metadataPrefix")
+            .ok_or_else(|| OaiError::BadArgument("metadataPrefix is required".to_string()))?;
+        let from = arguments
+            .get("from")
+            .map(|from| parse_datestamp("from", from))
+            .transpose()?;
+        let until = arguments
+            .get("until")
+            .map(|until| parse_datestamp("until", until))
+            .transpose()?;
+        if let (Some((_, from_granularity)), Some((_, until_granularity))) = (from, until) {
+            if from_granularity != until_granularity {
+                return Err(OaiError::BadArgument(
+                    "from and until must have the same granularity".to_string(),
+                ));
+            }
+        }
+        if arguments.contains_key("set") {
+            return Err(OaiError::NoSetHierarchy);
+        }
+        let format = MetadataFormat::from_prefix(prefix)
+            .ok_or_else(|| OaiError::CannotDisseminateFormat(prefix.to_string()))?;
+
+        Ok(Self {
+            format,
+            from: from.map(|(from, _)| from),
+            // `until` takes in the whole day or second it names
+            before: until.map(|(until, granularity)| until + granularity),
+            after: None,
+            resumed: false,
+        })
+    }
+}
+
+/// Reads a `from` or `until` datestamp as the moment it starts and how long
+/// it lasts, a day or a second
+fn parse_datestamp(name: &str, value: &str) -> Result<(OffsetDateTime, Duration), OaiError> {
+    if let Ok(day) = Date::parse(value, DAY_FORMAT) {
+        return Ok((day.midnight().assume_utc(), Duration::DAY));
+    }
+
+    PrimitiveDateTime::parse(value, SECOND_FORMAT)
+        .map(|second| (second.assume_utc(), Duration::SECOND))
+        .map_err(|_| {
+            OaiError::BadArgument(format!(
+                "{name} must be a UTC datestamp as YYYY-MM-DD or {GRANULARITY}"
+            ))
+        })
+}
+
+/// A datestamp at the repository's granularity
+pub fn format_datestamp(datestamp: OffsetDateTime) -> String {
+    datestamp
+        .to_offset(time::UtcOffset::UTC)
+        .format(SECOND_FORMAT)
+        .expect("datestamps always format")
+}
+
+/// An OAI-PMH 2.0 data provider over the catalog.
+///
+/// Every book is a record, identified as `oai:<repository_identifier>:<id>`
+/// and datestamped with when it last changed, so harvesters can ask for
+/// what has changed since they last came by. Lists are read a page at a
+/// time in datestamp order, as the list endpoints read theirs, with the
+/// cursor handed out as the resumption token. There are no sets.
+///
+/// A book merged into another stays a deleted record, datestamped when it
+/// was merged, so harvesters learn to drop it. It lasts only as long as the
+/// merge history does, and a book deleted outright simply drops out, so the
+/// repository keeps deleted records `transient`ly.
+pub struct OaiPmh<'a> {
+    pub base_url: &'a str,
+    pub repository_identifier: &'a str,
+    pub admin_email: &'a str,
+}
+
+impl OaiPmh<'_> {
+    /// Answers a request, given its arguments in the order they came, with
+    /// a complete OAI-PMH response document
+    pub async fn respond(
+        &self,
+        arguments: &[(String, String)],
+        connection_pool: &PgPool,
+    ) -> Result<String, sqlx::Error> {
+        let (request, body) = match OaiRequest::parse(arguments) {
+            Ok(request) => {
+                let body = match self.verb(&request, connection_pool).await? {
+                    Ok(body) => body,
+                    Err(error) => error_element(&error),
+                };
+                (request_element(self.base_url, arguments), body)
+            }
+            // Arguments that didn't make a request aren't echoed back
+            Err(error) => (request_element(self.base_url, &[]), error_element(&error)),
+        };
+
+        Ok(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <OAI-PMH xmlns=\"{OAI_PMH_NAMESPACE}\" \
+             xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+             xsi:schemaLocation=\"{OAI_PMH_NAMESPACE} {OAI_PMH_SCHEMA}\">\n\
+             <responseDate>{}</responseDate>\n{request}\n{body}\n</OAI-PMH>\n",
+            format_datestamp(OffsetDateTime::now_utc())
+        ))
+    }
+
+    async fn verb(
+        &self,
+        request: &OaiRequest,
+        connection_pool: &PgPool,
+    ) -> Result<Result<String, OaiError>, sqlx::Error> {
+        match request {
+            OaiRequest::Identify => {
+                let earliest = Db::get_oai_earliest_datestamp(connection_pool)
+                    .await?
+                    .unwrap_or_else(OffsetDateTime::now_utc);
+                Ok(Ok(format!(
+                    "<Identify><repositoryName>{}</repositoryName><baseURL>{}</baseURL>\
+                     <protocolVersion>2.0</protocolVersion><adminEmail>{}</adminEmail>\
+                     <earliestDatestamp>{}</earliestDatestamp><deletedRecord>transient</deletedRecord>\
+                     <granularity>{GRANULARITY}</granularity></Identify>",
+                    escape(REPOSITORY_NAME),
+                    escape(self.base_url),
+                    escape(self.admin_email),
+                    format_datestamp(earliest)
+                )))
+            }
+            OaiRequest::ListMetadataFormats { identifier } => {
+                if let Some(identifier) = identifier {
+                    if let Err(error) = self.book(identifier, connection_pool).await? {
+                        return Ok(Err(error));
+                    }
+                }
+                let formats: String = MetadataFormat::ALL
+                    .into_iter()
+                    .map(|format| {
+                        format!(
+                            "<metadataFormat><metadataPrefix>{}</metadataPrefix>\
+                             <schema>{}</schema><metadataNamespace>{}</metadataNamespace>\
+                             </metadataFormat>",
+                            format.prefix(),
+                            format.schema(),
+                            format.namespace()
+                        )
+                    })
+                    .collect();
+                Ok(Ok(format!(
+                    "<ListMetadataFormats>{formats}</ListMetadataFormats>"
+                )))
+            }
+            OaiRequest::ListSets => Ok(Err(OaiError::NoSetHierarchy)),
+            OaiRequest::ListIdentifiers(harvest) => {
+                self.list("ListIdentifiers", harvest, false, connection_pool)
+                    .await
+            }
+            OaiRequest::ListRecords(harvest) => {
+                self.list("ListRecords", harvest, true, connection_pool)
+                    .await
+            }
+            OaiRequest::GetRecord { identifier, format } => Ok(self
+                .book(identifier, connection_pool)
+                .await?
+                .map(|book| format!("<GetRecord>{}</GetRecord>", self.record(&book, *format)))),
+        }
+    }
+
+    /// A page of a harvest, with a resumption token if there is more of it
+    async fn list(
+        &self,
+        verb: &str,
+        harvest: &Harvest,
+        with_metadata: bool,
+        connection_pool: &PgPool,
+    ) -> Result<Result<String, OaiError>, sqlx::Error> {
+        let rows = Db::get_oai_book_list(
+            harvest.from,
+            harvest.before,
+            harvest.after,
+            OAI_PAGE_SIZE + 1,
+            connection_pool,
+        )
+        .await?;
+        let page = Page::from_rows(rows, OAI_PAGE_SIZE);
+        if page.rows.is_empty() {
+            return Ok(Err(OaiError::NoRecordsMatch));
+        }
+
+        let mut list = format!("<{verb}>");
+        for book in &page.rows {
+            if with_metadata {
+                list.push_str(&self.record(book, harvest.format));
+            } else {
+                list.push_str(&self.header(book));
+            }
+        }
+        let next = page.next_cursor(|book| ResumptionToken {
+            metadata_prefix: harvest.format.prefix().to_string(),
+            from: harvest.from,
+            before: harvest.before,
+            datestamp: book.datestamp,
+            id: book.master_book_id,
+        });
+        match next {
+            Some(token) => list.push_str(&format!("<resumptionToken>{token}</resumptionToken>")),
+            // The last part of a list that was resumed says it is the last
+            None if harvest.resumed => list.push_str("<resumptionToken/>"),
+            None => {}
+        }
+        list.push_str(&format!("</{verb}>"));
+
+        Ok(Ok(list))
+    }
+
+    /// The book an OAI identifier names
+    async fn book(
+        &self,
+        identifier: &str,
+        connection_pool: &PgPool,
+    ) -> Result<Result<OaiBookFromQuery, OaiError>, sqlx::Error> {
+        let Some(id) = identifier
+            .strip_prefix(&format!("oai:{}:", self.repository_identifier))
+            .and_then(|id| id.parse().ok())
+        else {
+            return Ok(Err(OaiError::IdDoesNotExist(identifier.to_string())));
+        };
+
+        Ok(Db::get_oai_book(id, connection_pool)
+            .await?
+            .ok_or_else(|| OaiError::IdDoesNotExist(identifier.to_string())))
+    }
+
+    fn header(&self, book: &OaiBookFromQuery) -> String {
+        let status = if book.deleted {
+            " status=\"deleted\""
+        } else {
+            ""
+        };
+        format!(
+            "<header{status}><identifier>oai:{}:{}</identifier>\
+             <datestamp>{}</datestamp></header>",
+            escape(self.repository_identifier),
+            book.master_book_id,
+            format_datestamp(book.datestamp)
+        )
+    }
+
+    /// A record, its metadata in `format`, or only its header if deleted
+    fn record(&self, book: &OaiBookFromQuery, format: MetadataFormat) -> String {
+        if book.deleted {
+            return format!("<record>{}</record>", self.header(book));
+        }
+        format!(
+            "<record>{}<metadata>{}</metadata></record>",
+            self.header(book),
+            format.metadata(&book.to_book_from_query())
+        )
+    }
+}
+
+/// The `request` element, echoing the arguments of a request that made sense
+fn request_element(base_url: &str, arguments: &[(String, String)]) -> String {
+    let attributes: String = arguments
+        .iter()
+        .map(|(name, value)| format!(" {}=\"{}\"", escape(name), escape(value)))
+        .collect();
+
+    format!("<request{attributes}>{}</request>", escape(base_url))
+}
+
+fn error_element(error: &OaiError) -> String {
+    format!(
+        "<error code=\"{}\">{}</error>",
+        error.code(),
+        escape(&error.to_string())
+    )
+}
@@ -0,0 +1,323 @@
+use quick_xml::escape::escape;
+use time::OffsetDateTime;
+
+use crate::catalog::linked_data::iso_date;
+use crate::catalog::oai::format_datestamp;
+use crate::catalog::search::DEFAULT_SEARCH_LIMIT;
+use crate::catalog::text;
+use crate::routes::Book;
+
+/// The content type of a feed that leads on to other feeds
+pub const NAVIGATION_FEED_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+
+/// The content type of a feed listing books
+pub const ACQUISITION_FEED_TYPE: &str =
+    "application/atom+xml;profile=opds-catalog;kind=acquisition";
+
+/// The content type of an OpenSearch description
+pub const OPEN_SEARCH_TYPE: &str = "application/opensearchdescription+xml";
+
+const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+const OPDS_NAMESPACE: &str = "http://opds-spec.org/2010/catalog";
+const DC_TERMS_NAMESPACE: &str = "http://purl.org/dc/terms/";
+const OPEN_SEARCH_NAMESPACE: &str = "http://a9.com/-/spec/opensearch/1.1/";
+
+/// What the feeds call the catalog
+pub const CATALOG_TITLE: &str = "Library Catalog";
+
+/// Where the feeds live, under the server's base URL
+pub const OPDS_ROOT: &str = "/api/opds";
+const BOOKS_PATH: &str = "/api/opds/books";
+const SEARCH_PATH: &str = "/api/opds/search";
+const OPEN_SEARCH_PATH: &str = "/api/opds/opensearch.xml";
+
+/// Where a page of search results sits among them all
+pub struct SearchPage {
+    /// The position of the page's first result, counting from 1
+    pub start_index: i64,
+    pub items_per_page: i64,
+    pub total_results: i64,
+}
+
+/// The ways into the catalog the root feed offers: an id for the entry, its
+/// title, what it holds, and the query it reads the book list with
+const SHELVES: [(&str, &str, &str, &str); 3] = [
+    (
+        "title",
+        "By title",
+        "Every book, A to Z by title",
+        "sort=title",
+    ),
+    (
+        "author",
+        "By author",
+        "Every book, A to Z by author",
+        "sort=author",
+    ),
+    (
+        "newest",
+        "Newest",
+        "Every book, the most recently published first",
+        "sort=publish_date&order=desc",
+    ),
+];
+
+/// The catalog as an OPDS 1.2 catalog for e-reader apps.
+///
+/// The root is a navigation feed of shelves, each an acquisition feed over
+/// the book list read in some order and paged with its cursors. Search is
+/// described by an OpenSearch document and answered with acquisition feeds
+/// of the matches, best first, paged by OpenSearch's `startIndex`. Each
+/// entry is stamped with when its book last changed, as OAI-PMH datestamps
+/// it, and each feed with when it was generated.
+///
+/// The catalog's copies are on shelves rather than files, so a book's
+/// acquisition link is a borrow link to its record, which lists them.
+pub struct Opds<'a> {
+    pub base_url: &'a str,
+    pub updated: OffsetDateTime,
+}
+
+impl Opds<'_> {
+    /// The root navigation feed
+    pub fn navigation(&self) -> String {
+        let entries: String = SHELVES
+            .into_iter()
+            .map(|(id, title, content, query)| {
+                format!(
+                    "<entry><title>{}</title><id>{}</id><updated>{}</updated>\
+                     <content type=\"text\">{}</content>{}</entry>\n",
+                    escape(title),
+                    escape(&format!("{}{OPDS_ROOT}#{id}", self.base_url)),
+                    format_datestamp(self.updated),
+                    escape(content),
+                    link(
+                        "subsection",
+                        &self.url(&format!("{BOOKS_PATH}?{query}")),
+                        ACQUISITION_FEED_TYPE
+                    )
+                )
+            })
+            .collect();
+
+        self.feed(
+            OPDS_ROOT,
+            CATALOG_TITLE,
+            NAVIGATION_FEED_TYPE,
+            &[],
+            &entries,
+        )
+    }
+
+    /// A page of the book list. `query` is how the page was asked for, in
+    /// the order the parameters came, and `next_cursor` the cursor for the
+    /// page after it, if any.
+    pub fn books(
+        &self,
+        query: &[(String, String)],
+        next_cursor: Option<&str>,
+        books: &[(Book, OffsetDateTime)],
+    ) -> String {
+        let first: Vec<(String, String)> = query
+            .iter()
+            .filter(|(key, _)| key != "cursor")
+            .cloned()
+            .collect();
+        let mut links = vec![
+            link("up", &self.url(OPDS_ROOT), NAVIGATION_FEED_TYPE),
+            link(
+                "first",
+                &self.url(&with_query(BOOKS_PATH, &first)),
+                ACQUISITION_FEED_TYPE,
+            ),
+        ];
+        if let Some(cursor) = next_cursor {
+            let mut next = first;
+            next.push(("cursor".to_string(), cursor.to_string()));
+            links.push(link(
+                "next",
+                &self.url(&with_query(BOOKS_PATH, &next)),
+                ACQUISITION_FEED_TYPE,
+            ));
+        }
+
+        self.feed(
+            &with_query(BOOKS_PATH, query),
+            CATALOG_TITLE,
+            ACQUISITION_FEED_TYPE,
+            &links,
+            &self.entries(books),
+        )
+    }
+
+    /// A page of the books found searching for `q`, best match first,
+    /// linking on to the next page if there is one
+    pub fn search_results(
+        &self,
+        q: &str,
+        page: &SearchPage,
+        books: &[(Book, OffsetDateTime)],
+    ) -> String {
+        let search_path = |start_index: i64| {
+            with_query(
+                SEARCH_PATH,
+                &[
+                    ("q".to_string(), q.to_string()),
+                    ("startIndex".to_string(), start_index.to_string()),
+                    ("limit".to_string(), page.items_per_page.to_string()),
+                ],
+            )
+        };
+        let mut head = vec![
+            link("up", &self.url(OPDS_ROOT), NAVIGATION_FEED_TYPE),
+            format!(
+                "<opensearch:Query role=\"request\" searchTerms=\"{}\" startIndex=\"{}\"/>\
+                 <opensearch:totalResults>{}</opensearch:totalResults>\
+                 <opensearch:startIndex>{}</opensearch:startIndex>\
+                 <opensearch:itemsPerPage>{}</opensearch:itemsPerPage>",
+                escape(q),
+                page.start_index,
+                page.total_results,
+                page.start_index,
+                page.items_per_page
+            ),
+        ];
+        let next_index = page.start_index + page.items_per_page;
+        if next_index <= page.total_results {
+            head.push(link(
+                "next",
+                &self.url(&search_path(next_index)),
+                ACQUISITION_FEED_TYPE,
+            ));
+        }
+
+        self.feed(
+            &search_path(page.start_index),
+            &format!("Search for {q}"),
+            ACQUISITION_FEED_TYPE,
+            &head,
+            &self.entries(books),
+        )
+    }
+
+    /// The OpenSearch description e-readers read to learn how to search
+    pub fn open_search_description(&self) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <OpenSearchDescription xmlns=\"{OPEN_SEARCH_NAMESPACE}\">\
+             <ShortName>{}</ShortName><Description>Search the catalog by title and author\
+             </Description><InputEncoding>UTF-8</InputEncoding>\
+             <OutputEncoding>UTF-8</OutputEncoding>\
+             <Url type=\"{}\" template=\"{}\"/></OpenSearchDescription>\n",
+            escape(CATALOG_TITLE),
+            escape(ACQUISITION_FEED_TYPE),
+            escape(&format!(
+                "{}?q={{searchTerms}}&startIndex={{startIndex?}}&limit={DEFAULT_SEARCH_LIMIT}",
+                self.url(SEARCH_PATH)
+            ))
+        )
+    }
+
+    /// A feed at `path`, linking back to itself, the root and search, with
+    /// `head` added to what comes before its entries
+    fn feed(&self, path: &str, title: &str, kind: &str, head: &[String], entries: &str) -> String {
+        let url = self.url(path);
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <feed xmlns=\"{ATOM_NAMESPACE}\" xmlns:opds=\"{OPDS_NAMESPACE}\" \
+             xmlns:dc=\"{DC_TERMS_NAMESPACE}\" xmlns:opensearch=\"{OPEN_SEARCH_NAMESPACE}\">\n\
+             <id>{}</id><title>{}</title><updated>{}</updated>\
+             <author><name>{}</name></author>\n{}{}{}{}\n{entries}</feed>\n",
+            escape(&url),
+            escape(title),
+            format_datestamp(self.updated),
+            escape(CATALOG_TITLE),
+            link("self", &url, kind),
+            link("start", &self.url(OPDS_ROOT), NAVIGATION_FEED_TYPE),
+            link("search", &self.url(OPEN_SEARCH_PATH), OPEN_SEARCH_TYPE),
+            head.concat()
+        )
+    }
+
+    fn entries(&self, books: &[(Book, OffsetDateTime)]) -> String {
+        books
+            .iter()
+            .map(|(book, updated)| self.entry(book, *updated) + "\n")
+            .collect()
+    }
+
+    /// A book as an acquisition feed entry, stamped with when it last
+    /// changed, with links to the other ways the catalog can describe it
+    fn entry(&self, book: &Book, updated: OffsetDateTime) -> String {
+        let record = self.url(&format!("/api/books/{}", book.id));
+        let mut entry = format!(
+            "<entry><title>{}</title><id>urn:uuid:{}</id><updated>{}</updated>",
+            escape(text(&book.title).unwrap_or("Untitled")),
+            book.id,
+            format_datestamp(updated)
+        );
+        if let Some(author) = text(&book.author) {
+            entry.push_str(&format!("<author><name>{}</name></author>", escape(author)));
+        }
+        if let Some(isbn) = text(&book.isbn_13) {
+            entry.push_str(&format!(
+                "<dc:identifier>urn:isbn:{}</dc:identifier>",
+                escape(isbn)
+            ));
+        }
+        if let Some(date) = iso_date(book) {
+            entry.push_str(&format!("<dc:issued>{date}</dc:issued>"));
+        }
+        entry.push_str(&link(
+            "http://opds-spec.org/acquisition/borrow",
+            &record,
+            "application/json",
+        ));
+        for kind in ["application/ld+json", "application/marc+xml"] {
+            entry.push_str(&link("alternate", &record, kind));
+        }
+        entry.push_str("</entry>");
+
+        entry
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+}
+
+fn link(rel: &str, href: &str, kind: &str) -> String {
+    format!(
+        "<link rel=\"{}\" href=\"{}\" type=\"{}\"/>",
+        escape(rel),
+        escape(href),
+        escape(kind)
+    )
+}
+
+/// `path` with `params` as its query string
+fn with_query(path: &str, params: &[(String, String)]) -> String {
+    let query: Vec<String> = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+        .collect();
+
+    if query.is_empty() {
+        path.to_string()
+    } else {
+        format!("{path}?{}", query.join("&"))
+    }
+}
+
+/// Escapes everything but the characters RFC 3986 leaves unreserved
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
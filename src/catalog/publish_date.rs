@@ -0,0 +1,247 @@
+use time::{Date, Month};
+
+/// How much of a publication date is known, ordered least precise first
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DatePrecision {
+    Century,
+    Decade,
+    Year,
+    Month,
+    Day,
+}
+
+impl DatePrecision {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Century => "century",
+            Self::Decade => "decade",
+            Self::Year => "year",
+            Self::Month => "month",
+            Self::Day => "day",
+        }
+    }
+
+    pub fn parse(precision: &str) -> Option<Self> {
+        match precision {
+            "century" => Some(Self::Century),
+            "decade" => Some(Self::Decade),
+            "year" => Some(Self::Year),
+            "month" => Some(Self::Month),
+            "day" => Some(Self::Day),
+            _ => None,
+        }
+    }
+}
+
+/// A publication date read out of what a cataloger typed. `on` is the first
+/// day of the period the date covers, so `"March 2001"` is 2001-03-01 with
+/// month precision.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PublishDate {
+    pub on: Date,
+    pub precision: DatePrecision,
+    pub approximate: bool,
+}
+
+impl PublishDate {
+    /// The last day of the period the date covers
+    pub fn last_day(&self) -> Date {
+        let year = self.on.year();
+        let (end_year, end_month) = match self.precision {
+            DatePrecision::Day => return self.on,
+            DatePrecision::Month => (year, self.on.month()),
+            DatePrecision::Year => (year, Month::December),
+            DatePrecision::Decade => (year + 9, Month::December),
+            DatePrecision::Century => (year + 99, Month::December),
+        };
+
+        Date::from_calendar_date(
+            end_year,
+            end_month,
+            time::util::days_in_year_month(end_year, end_month),
+        )
+        .unwrap_or(self.on)
+    }
+}
+
+/// Reads the forms catalogers write publication dates in:
+///
+/// - a year, perhaps with a copyright or phonogram mark: `1998`, `c1998`,
+///   `©1998`, `p1998`
+/// - a month and year, with or without a day: `March 2001`, `Mar. 4, 2001`,
+///   `4 March 2001`
+/// - ISO dates: `2001-03-04`, `2001-03`
+/// - an unknown decade or century: `196-`, `19--`, or `196u`, `19uu`
+///
+/// Square brackets, which only say the cataloger supplied the date, are
+/// ignored. A `?` or a leading `ca.` marks the date approximate. Returns
+/// `None` for anything else, leaving only the text as entered.
+pub fn parse(input: &str) -> Option<PublishDate> {
+    let mut text = input.trim().to_lowercase().replace(['[', ']'], "");
+    let mut approximate = false;
+
+    if text.contains('?') {
+        approximate = true;
+        text = text.replace('?', "");
+    }
+    for circa in ["circa", "ca.", "ca"] {
+        if let Some(rest) = text.trim_start().strip_prefix(circa) {
+            approximate = true;
+            text = rest.to_string();
+            break;
+        }
+    }
+
+    let text = text.trim().trim_end_matches('.').trim();
+    let text = ["copyright", "©", "℗", "c", "p"]
+        .iter()
+        .find_map(|mark| {
+            text.strip_prefix(mark)
+                .filter(|rest| rest.trim_start().starts_with(|c: char| c.is_ascii_digit()))
+        })
+        .unwrap_or(text)
+        .trim();
+
+    let (on, precision) = parse_iso(text)
+        .or_else(|| parse_partial_year(text))
+        .or_else(|| parse_written(text))?;
+
+    Some(PublishDate {
+        on,
+        precision,
+        approximate,
+    })
+}
+
+/// `2001`, `2001-03` and `2001-03-04`
+fn parse_iso(text: &str) -> Option<(Date, DatePrecision)> {
+    let mut parts = text.split('-');
+    let year = parse_year(parts.next()?)?;
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (None, _, _) => Some((first_of(year, Month::January)?, DatePrecision::Year)),
+        (Some(month), None, _) => {
+            let month = parse_number_month(month)?;
+            Some((first_of(year, month)?, DatePrecision::Month))
+        }
+        (Some(month), Some(day), None) => {
+            let month = parse_number_month(month)?;
+            let day = day.parse().ok()?;
+            Some((
+                Date::from_calendar_date(year, month, day).ok()?,
+                DatePrecision::Day,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// `196-` and `19--`, or the MARC `196u` and `19uu`
+fn parse_partial_year(text: &str) -> Option<(Date, DatePrecision)> {
+    if text.len() != 4 || !text.is_ascii() {
+        return None;
+    }
+    let unknown = |c: char| c == '-' || c == 'u';
+    let (known, rest) = text.split_at(text.find(unknown)?);
+    if !rest.chars().all(unknown) || !known.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let (year, precision) = match known.len() {
+        3 => (known.parse::<i32>().ok()? * 10, DatePrecision::Decade),
+        2 => (known.parse::<i32>().ok()? * 100, DatePrecision::Century),
+        _ => return None,
+    };
+
+    Some((first_of(year, Month::January)?, precision))
+}
+
+/// `March 2001`, `Mar. 4, 2001` and `4 March 2001`
+fn parse_written(text: &str) -> Option<(Date, DatePrecision)> {
+    let words: Vec<&str> = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    let mut year = None;
+    let mut month = None;
+    let mut day = None;
+    for word in words {
+        if let Some(parsed) = parse_year(word) {
+            year = Some(parsed);
+        } else if let Some(parsed) = parse_month_name(word) {
+            month = Some(parsed);
+        } else if let Some(parsed) = parse_day(word) {
+            day = Some(parsed);
+        } else {
+            return None;
+        }
+    }
+
+    match (year?, month?, day) {
+        (year, month, None) => Some((first_of(year, month)?, DatePrecision::Month)),
+        (year, month, Some(day)) => Some((
+            Date::from_calendar_date(year, month, day).ok()?,
+            DatePrecision::Day,
+        )),
+    }
+}
+
+fn parse_year(text: &str) -> Option<i32> {
+    if text.len() == 4 && text.chars().all(|c| c.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// `4`, or `4th`
+fn parse_day(text: &str) -> Option<u8> {
+    let number = ["st", "nd", "rd", "th"]
+        .iter()
+        .find_map(|suffix| text.strip_suffix(suffix))
+        .unwrap_or(text);
+    if number.len() > 2 {
+        return None;
+    }
+    number.parse().ok()
+}
+
+fn parse_number_month(text: &str) -> Option<Month> {
+    if text.len() > 2 {
+        return None;
+    }
+    Month::try_from(text.parse::<u8>().ok()?).ok()
+}
+
+fn parse_month_name(text: &str) -> Option<Month> {
+    let name = text.trim_end_matches('.');
+    if name.len() < 3 {
+        return None;
+    }
+
+    [
+        ("january", Month::January),
+        ("february", Month::February),
+        ("march", Month::March),
+        ("april", Month::April),
+        ("may", Month::May),
+        ("june", Month::June),
+        ("july", Month::July),
+        ("august", Month::August),
+        ("september", Month::September),
+        ("october", Month::October),
+        ("november", Month::November),
+        ("december", Month::December),
+    ]
+    .iter()
+    .find(|(full, _)| full.starts_with(name))
+    .map(|(_, month)| *month)
+}
+
+fn first_of(year: i32, month: Month) -> Option<Date> {
+    Date::from_calendar_date(year, month, 1).ok()
+}
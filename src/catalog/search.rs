@@ -0,0 +1,40 @@
+use quick_xml::escape::escape;
+
+/// How many results a search returns when the client doesn't say
+pub const DEFAULT_SEARCH_LIMIT: i64 = 20;
+
+/// The most results a single search may ask for
+pub const MAX_SEARCH_LIMIT: i64 = 200;
+
+/// Turns what a patron typed into a `tsquery` that matches every word as a
+/// prefix, so `"left han"` finds "The Left Hand of Darkness".
+///
+/// Anything that isn't a letter or digit separates words, which also keeps
+/// `tsquery` operators out of the query. Returns `None` when nothing
+/// searchable is left.
+pub fn prefix_query(q: &str) -> Option<String> {
+    let terms: Vec<String> = q
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("{}:*", term.to_lowercase()))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" & "))
+    }
+}
+
+/// The `ts_headline` options search highlights are made with. Matches are
+/// marked with control characters, which never turn up in catalog text, so
+/// `highlight_html` can escape the text before marking them up.
+pub const HIGHLIGHT_OPTIONS: &str = "StartSel=\u{1}, StopSel=\u{2}, HighlightAll=true";
+
+/// A highlight made with `HIGHLIGHT_OPTIONS` as HTML: the text escaped, and
+/// each match wrapped in `<mark>`
+pub fn highlight_html(headline: &str) -> String {
+    escape(headline)
+        .replace('\u{1}', "<mark>")
+        .replace('\u{2}', "</mark>")
+}
@@ -0,0 +1,746 @@
+use quick_xml::escape::escape;
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+use crate::catalog::cql::{self, Boolean, CqlError, CqlQuery, SearchClause};
+use crate::catalog::isbn;
+use crate::catalog::linked_data::{dublin_core_elements, DUBLIN_CORE_NAMESPACE};
+use crate::catalog::marc::MarcRecord;
+use crate::db::Db;
+use crate::routes::BookFromQuery;
+
+/// How many records a search returns when the client doesn't say
+pub const DEFAULT_MAXIMUM_RECORDS: i64 = 10;
+
+/// The most records a single response holds, however many are asked for
+pub const MAX_MAXIMUM_RECORDS: i64 = 100;
+
+/// What the explain record calls the database
+pub const DATABASE_TITLE: &str = "Library Catalog";
+
+const EXPLAIN_NAMESPACE: &str = "http://explain.z3950.org/dtd/2.0/";
+
+const SRW_DC_NAMESPACE: &str = "info:srw/schema/1/dc-schema";
+
+/// Parameters a search takes that aren't a request for something the server
+/// doesn't do. `stylesheet` and `resultSetTTL` are hints that may be ignored.
+const SEARCH_PARAMETERS: [&str; 11] = [
+    "operation",
+    "version",
+    "query",
+    "queryType",
+    "startRecord",
+    "maximumRecords",
+    "recordSchema",
+    "recordPacking",
+    "recordXMLEscaping",
+    "stylesheet",
+    "resultSetTTL",
+];
+
+/// The SRU versions spoken. 1.1 and 1.2 share their response schema.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SruVersion {
+    V1_1,
+    V1_2,
+    V2_0,
+}
+
+impl SruVersion {
+    pub fn parse(version: &str) -> Option<Self> {
+        match version {
+            "1.1" => Some(Self::V1_1),
+            "1.2" => Some(Self::V1_2),
+            "2.0" => Some(Self::V2_0),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::V1_1 => "1.1",
+            Self::V1_2 => "1.2",
+            Self::V2_0 => "2.0",
+        }
+    }
+
+    /// The prefix and namespace of response elements
+    fn namespace(self) -> (&'static str, &'static str) {
+        match self {
+            Self::V1_1 | Self::V1_2 => ("srw", "http://www.loc.gov/zing/srw/"),
+            Self::V2_0 => ("sru", "http://docs.oasis-open.org/ns/search-ws/sruResponse"),
+        }
+    }
+
+    /// The record element and parameter saying whether records are escaped
+    /// as strings
+    fn escaping_parameter(self) -> &'static str {
+        match self {
+            Self::V1_1 | Self::V1_2 => "recordPacking",
+            Self::V2_0 => "recordXMLEscaping",
+        }
+    }
+
+    fn diagnostic_namespace(self) -> &'static str {
+        match self {
+            Self::V1_1 | Self::V1_2 => "http://www.loc.gov/zing/srw/diagnostic/",
+            Self::V2_0 => "http://docs.oasis-open.org/ns/search-ws/diagnostic",
+        }
+    }
+}
+
+/// The schemas records can be retrieved in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordSchema {
+    Dc,
+    Marcxml,
+}
+
+impl RecordSchema {
+    const ALL: [Self; 2] = [Self::Dc, Self::Marcxml];
+
+    /// Reads a schema given by its short name or identifier
+    pub fn parse(schema: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|known| schema == known.name() || schema == known.identifier())
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dc => "dc",
+            Self::Marcxml => "marcxml",
+        }
+    }
+
+    pub fn identifier(self) -> &'static str {
+        match self {
+            Self::Dc => "info:srw/schema/1/dc-v1.1",
+            Self::Marcxml => "info:srw/schema/1/marcxml-v1.1",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Dc => "Dublin Core",
+            Self::Marcxml => "MARC 21 in MARCXML",
+        }
+    }
+
+    fn record(self, book: &BookFromQuery) -> String {
+        match self {
+            Self::Dc => format!(
+                "<srw_dc:dc xmlns:srw_dc=\"{SRW_DC_NAMESPACE}\" \
+                 xmlns:dc=\"{DUBLIN_CORE_NAMESPACE}\">{}</srw_dc:dc>",
+                dublin_core_elements(&book.to_book())
+            ),
+            Self::Marcxml => MarcRecord::from_book(book)
+                .to_namespaced_marcxml()
+                .trim_end()
+                .to_string(),
+        }
+    }
+}
+
+/// An SRU diagnostic, numbered as in the standard diagnostics list and
+/// reported in the response rather than by HTTP status. What it displays is
+/// the diagnostic's `details`.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum SruDiagnostic {
+    #[error("{0}")]
+    UnsupportedOperation(String),
+
+    #[error("{0}")]
+    UnsupportedVersion(String),
+
+    #[error("{0}")]
+    UnsupportedParameterValue(&'static str),
+
+    #[error("{0}")]
+    MandatoryParameter(&'static str),
+
+    #[error("{0}")]
+    UnsupportedParameter(String),
+
+    #[error("{0}")]
+    QuerySyntax(String),
+
+    #[error("{0}")]
+    UnsupportedIndex(String),
+
+    #[error("{0}")]
+    UnsupportedRelation(String),
+
+    #[error("{0}")]
+    UnsupportedRelationModifier(String),
+
+    #[error("")]
+    EmptyTerm,
+
+    #[error("{0}")]
+    UnsupportedBoolean(&'static str),
+
+    #[error("{0}")]
+    UnsupportedBooleanModifier(String),
+
+    #[error("{0}")]
+    UnsupportedQueryFeature(&'static str),
+
+    #[error("{0}")]
+    FirstRecordOutOfRange(i64),
+
+    #[error("{0}")]
+    UnknownSchema(String),
+
+    #[error("")]
+    SortNotSupported,
+}
+
+impl SruDiagnostic {
+    pub fn number(&self) -> u16 {
+        match self {
+            Self::UnsupportedOperation(_) => 4,
+            Self::UnsupportedVersion(_) => 5,
+            Self::UnsupportedParameterValue(_) => 6,
+            Self::MandatoryParameter(_) => 7,
+            Self::UnsupportedParameter(_) => 8,
+            Self::QuerySyntax(_) => 10,
+            Self::UnsupportedIndex(_) => 16,
+            Self::UnsupportedRelation(_) => 19,
+            Self::UnsupportedRelationModifier(_) => 20,
+            Self::EmptyTerm => 27,
+            Self::UnsupportedBoolean(_) => 37,
+            Self::UnsupportedBooleanModifier(_) => 46,
+            Self::UnsupportedQueryFeature(_) => 48,
+            Self::FirstRecordOutOfRange(_) => 61,
+            Self::UnknownSchema(_) => 66,
+            Self::SortNotSupported => 80,
+        }
+    }
+
+    pub fn uri(&self) -> String {
+        format!("info:srw/diagnostic/1/{}", self.number())
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::UnsupportedOperation(_) => "Unsupported operation",
+            Self::UnsupportedVersion(_) => "Unsupported version",
+            Self::UnsupportedParameterValue(_) => "Unsupported parameter value",
+            Self::MandatoryParameter(_) => "Mandatory parameter not supplied",
+            Self::UnsupportedParameter(_) => "Unsupported parameter",
+            Self::QuerySyntax(_) => "Query syntax error",
+            Self::UnsupportedIndex(_) => "Unsupported index",
+            Self::UnsupportedRelation(_) => "Unsupported relation",
+            Self::UnsupportedRelationModifier(_) => "Unsupported relation modifier",
+            Self::EmptyTerm => "Empty term unsupported",
+            Self::UnsupportedBoolean(_) => "Unsupported boolean operator",
+            Self::UnsupportedBooleanModifier(_) => "Unsupported boolean modifier",
+            Self::UnsupportedQueryFeature(_) => "Query feature unsupported",
+            Self::FirstRecordOutOfRange(_) => "First record position out of range",
+            Self::UnknownSchema(_) => "Unknown schema for retrieval",
+            Self::SortNotSupported => "Sort not supported",
+        }
+    }
+}
+
+impl From<CqlError> for SruDiagnostic {
+    fn from(error: CqlError) -> Self {
+        match error {
+            CqlError::Syntax(details) => Self::QuerySyntax(details),
+            CqlError::PrefixAssignment => Self::UnsupportedQueryFeature("prefix assignment"),
+            CqlError::SortBy => Self::SortNotSupported,
+            error @ CqlError::TooComplex => Self::QuerySyntax(error.to_string()),
+        }
+    }
+}
+
+/// A CQL query as the catalog can run it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BookFilter {
+    All,
+    Nothing,
+    And(Box<BookFilter>, Box<BookFilter>),
+    Or(Box<BookFilter>, Box<BookFilter>),
+    AndNot(Box<BookFilter>, Box<BookFilter>),
+    /// Any of `columns` matches the `ilike` pattern, or none does when
+    /// `negated`
+    Like {
+        columns: &'static [&'static str],
+        pattern: String,
+        negated: bool,
+    },
+    /// The ISBN-13 is this one
+    Isbn(String),
+}
+
+impl BookFilter {
+    /// Checks a CQL query against the indexes, relations and booleans the
+    /// catalog supports:
+    ///
+    /// - `dc.title`, `dc.creator` and `cql.serverChoice`, which searches
+    ///   both, match with `=` or `adj` the term as a phrase anywhere in the
+    ///   field, with `any` or `all` any or all of its words, with `==` or
+    ///   `exact` the whole field, and with `<>` anything but the whole field
+    /// - `bath.isbn` matches an ISBN however it was typed, by `=`, `==`,
+    ///   `exact` or `adj`
+    /// - `cql.allRecords` matches every book
+    ///
+    /// Indexes may be given without their context set. Booleans are `and`,
+    /// `or` and `not`, without modifiers.
+    pub fn from_cql(query: &CqlQuery) -> Result<Self, SruDiagnostic> {
+        match query {
+            CqlQuery::Boolean {
+                boolean,
+                modifiers,
+                left,
+                right,
+            } => {
+                if let Some(modifier) = modifiers.first() {
+                    return Err(SruDiagnostic::UnsupportedBooleanModifier(
+                        modifier.name.clone(),
+                    ));
+                }
+                let (left, right) = (
+                    Box::new(Self::from_cql(left)?),
+                    Box::new(Self::from_cql(right)?),
+                );
+                match boolean {
+                    Boolean::And => Ok(Self::And(left, right)),
+                    Boolean::Or => Ok(Self::Or(left, right)),
+                    Boolean::Not => Ok(Self::AndNot(left, right)),
+                    Boolean::Prox => Err(SruDiagnostic::UnsupportedBoolean("prox")),
+                }
+            }
+            CqlQuery::Clause(clause) => Self::from_clause(clause),
+        }
+    }
+
+    fn from_clause(clause: &SearchClause) -> Result<Self, SruDiagnostic> {
+        if let Some(modifier) = clause.relation_modifiers.first() {
+            return Err(SruDiagnostic::UnsupportedRelationModifier(
+                modifier.name.clone(),
+            ));
+        }
+
+        let index = clause.index.as_str();
+        let columns: &'static [&'static str] = match index {
+            "cql.allrecords" => return Ok(Self::All),
+            cql::SERVER_CHOICE => &["title", "author"],
+            "dc.title" | "title" => &["title"],
+            "dc.creator" | "creator" => &["author"],
+            "bath.isbn" | "isbn" => {
+                return match clause.relation.as_str() {
+                    "=" | "==" | "exact" | "adj" => Ok(isbn::normalize(&unescape(&clause.term))
+                        .map(Self::Isbn)
+                        .unwrap_or(Self::Nothing)),
+                    relation => Err(SruDiagnostic::UnsupportedRelation(relation.to_string())),
+                };
+            }
+            _ => return Err(SruDiagnostic::UnsupportedIndex(clause.index.clone())),
+        };
+        if clause.term.trim().is_empty() {
+            return Err(SruDiagnostic::EmptyTerm);
+        }
+
+        let like = |pattern: String, negated: bool| Self::Like {
+            columns,
+            pattern,
+            negated,
+        };
+        let words = || {
+            words(&clause.term)
+                .into_iter()
+                .map(|word| like(like_pattern(&word, false), false))
+        };
+        match clause.relation.as_str() {
+            "=" | "adj" => Ok(like(like_pattern(&clause.term, false), false)),
+            "==" | "exact" => Ok(like(like_pattern(&clause.term, true), false)),
+            "<>" => Ok(like(like_pattern(&clause.term, true), true)),
+            "any" => Ok(words()
+                .reduce(|left, right| Self::Or(Box::new(left), Box::new(right)))
+                .unwrap_or(Self::Nothing)),
+            "all" => Ok(words()
+                .reduce(|left, right| Self::And(Box::new(left), Box::new(right)))
+                .unwrap_or(Self::Nothing)),
+            relation => Err(SruDiagnostic::UnsupportedRelation(relation.to_string())),
+        }
+    }
+}
+
+/// Reads a CQL term into an `ilike` pattern. `*` masks any run of
+/// characters and `?` any one, a `^` at either end anchors the term there,
+/// and a backslash makes the character after it literal. An `exact` pattern
+/// is anchored at both ends.
+fn like_pattern(term: &str, exact: bool) -> String {
+    let mut chars: Vec<(bool, char)> = Vec::new();
+    let mut escaped = false;
+    for c in term.trim().chars() {
+        if escaped {
+            chars.push((true, c));
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else {
+            chars.push((false, c));
+        }
+    }
+
+    let anchored_start = exact || chars.first() == Some(&(false, '^'));
+    let anchored_end = exact || (chars.len() > 1 && chars.last() == Some(&(false, '^')));
+    if chars.first() == Some(&(false, '^')) {
+        chars.remove(0);
+    }
+    if chars.last() == Some(&(false, '^')) {
+        chars.pop();
+    }
+
+    let mut pattern = String::new();
+    if !anchored_start {
+        pattern.push('%');
+    }
+    for (literal, c) in chars {
+        match c {
+            '*' if !literal => pattern.push('%'),
+            '?' if !literal => pattern.push('_'),
+            '%' | '_' | '\\' => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            c => pattern.push(c),
+        }
+    }
+    if !anchored_end {
+        pattern.push('%');
+    }
+    pattern
+}
+
+/// The words of a term, split on whitespace that isn't escaped, with their
+/// escapes kept
+fn words(term: &str) -> Vec<String> {
+    let mut words = vec![String::new()];
+    let mut escaped = false;
+    for c in term.chars() {
+        if c.is_whitespace() && !escaped {
+            words.push(String::new());
+        } else if let Some(word) = words.last_mut() {
+            word.push(c);
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+/// A term with its backslash escapes read, for indexes that don't mask
+fn unescape(term: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = term.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// A searchRetrieve request with its parameters checked
+struct SearchRetrieve {
+    filter: BookFilter,
+    start_record: i64,
+    maximum_records: i64,
+    schema: RecordSchema,
+    escape_records: bool,
+}
+
+impl SearchRetrieve {
+    fn parse(version: SruVersion, params: &HashMap<String, String>) -> Result<Self, SruDiagnostic> {
+        if params.contains_key("sortKeys") {
+            return Err(SruDiagnostic::SortNotSupported);
+        }
+        if let Some(name) = params
+            .keys()
+            .find(|name| !name.starts_with("x-") && !SEARCH_PARAMETERS.contains(&name.as_str()))
+        {
+            return Err(SruDiagnostic::UnsupportedParameter(name.clone()));
+        }
+
+        let param = |name| params.get(name).map(String::as_str);
+        if param("queryType").is_some_and(|query_type| query_type != "cql") {
+            return Err(SruDiagnostic::UnsupportedParameterValue("queryType"));
+        }
+        let query = param("query").ok_or(SruDiagnostic::MandatoryParameter("query"))?;
+        let filter = BookFilter::from_cql(&cql::parse(query)?)?;
+
+        let number = |name: &'static str, default: i64, least: i64| {
+            param(name).map_or(Ok(default), |value| {
+                value
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|number| *number >= least)
+                    .ok_or(SruDiagnostic::UnsupportedParameterValue(name))
+            })
+        };
+        let start_record = number("startRecord", 1, 1)?;
+        let maximum_records =
+            number("maximumRecords", DEFAULT_MAXIMUM_RECORDS, 0)?.min(MAX_MAXIMUM_RECORDS);
+
+        let schema = match param("recordSchema") {
+            Some(schema) => RecordSchema::parse(schema)
+                .ok_or_else(|| SruDiagnostic::UnknownSchema(schema.to_string()))?,
+            None => RecordSchema::Dc,
+        };
+
+        // SRU 2.0 renamed 1.x's recordPacking to recordXMLEscaping, and
+        // gave recordPacking a new meaning
+        if version == SruVersion::V2_0
+            && !matches!(param("recordPacking"), None | Some("packed" | "unpacked"))
+        {
+            return Err(SruDiagnostic::UnsupportedParameterValue("recordPacking"));
+        }
+        let escape_records = match param(version.escaping_parameter()) {
+            None | Some("xml") => false,
+            Some("string") => true,
+            Some(_) => {
+                return Err(SruDiagnostic::UnsupportedParameterValue(
+                    version.escaping_parameter(),
+                ))
+            }
+        };
+
+        Ok(Self {
+            filter,
+            start_record,
+            maximum_records,
+            schema,
+            escape_records,
+        })
+    }
+}
+
+/// An SRU server over the catalog, speaking versions 1.1, 1.2 and 2.0, with
+/// CQL queries and records in Dublin Core or MARCXML.
+///
+/// A request without a `version` is answered as 2.0. Without an `operation`
+/// it is a search if it has a `query` and an explain request if not, as 2.0
+/// has it. Results are in title order, and each request runs the search
+/// afresh, so there are no result sets to hold on to.
+pub struct Sru<'a> {
+    pub host: &'a str,
+    pub port: u16,
+    pub database: &'a str,
+}
+
+impl Sru<'_> {
+    /// Answers a request with a complete SRU response document
+    pub async fn respond(
+        &self,
+        params: &HashMap<String, String>,
+        connection_pool: &PgPool,
+    ) -> Result<String, sqlx::Error> {
+        let version = match params.get("version") {
+            Some(version) => match SruVersion::parse(version) {
+                Some(version) => version,
+                None => {
+                    let diagnostic = SruDiagnostic::UnsupportedVersion(version.clone());
+                    return Ok(search_response(
+                        SruVersion::V2_0,
+                        0,
+                        "",
+                        None,
+                        Some(&diagnostic),
+                    ));
+                }
+            },
+            None => SruVersion::V2_0,
+        };
+
+        let operation = params.get("operation").map_or_else(
+            || match params.contains_key("query") {
+                true => "searchRetrieve",
+                false => "explain",
+            },
+            String::as_str,
+        );
+        match operation {
+            "explain" => Ok(self.explain(version)),
+            "searchRetrieve" => self.search(version, params, connection_pool).await,
+            _ => {
+                let diagnostic = SruDiagnostic::UnsupportedOperation(operation.to_string());
+                Ok(search_response(version, 0, "", None, Some(&diagnostic)))
+            }
+        }
+    }
+
+    async fn search(
+        &self,
+        version: SruVersion,
+        params: &HashMap<String, String>,
+        connection_pool: &PgPool,
+    ) -> Result<String, sqlx::Error> {
+        let search = match SearchRetrieve::parse(version, params) {
+            Ok(search) => search,
+            Err(diagnostic) => return Ok(search_response(version, 0, "", None, Some(&diagnostic))),
+        };
+
+        let count = Db::get_book_filter_count(&search.filter, connection_pool).await?;
+        if search.start_record > count && count > 0 {
+            let diagnostic = SruDiagnostic::FirstRecordOutOfRange(search.start_record);
+            return Ok(search_response(version, count, "", None, Some(&diagnostic)));
+        }
+        let books = match search.maximum_records {
+            0 => Vec::new(),
+            limit => {
+                Db::get_book_filter_list(
+                    &search.filter,
+                    search.start_record - 1,
+                    limit,
+                    connection_pool,
+                )
+                .await?
+            }
+        };
+
+        let (prefix, _) = version.namespace();
+        let escaping = version.escaping_parameter();
+        let records: String = books
+            .iter()
+            .zip(search.start_record..)
+            .map(|(book, position)| {
+                let data = search.schema.record(book);
+                let (escaping_value, data) = match search.escape_records {
+                    true => ("string", escape(&data).into_owned()),
+                    false => ("xml", data),
+                };
+                format!(
+                    "<{prefix}:record><{prefix}:recordSchema>{}</{prefix}:recordSchema>\
+                     <{prefix}:{escaping}>{escaping_value}</{prefix}:{escaping}>\
+                     <{prefix}:recordData>{data}</{prefix}:recordData>\
+                     <{prefix}:recordPosition>{position}</{prefix}:recordPosition>\
+                     </{prefix}:record>",
+                    search.schema.identifier()
+                )
+            })
+            .collect();
+        let next = search.start_record + books.len() as i64;
+
+        Ok(search_response(
+            version,
+            count,
+            &records,
+            (next <= count && !books.is_empty()).then_some(next),
+            None,
+        ))
+    }
+
+    fn explain(&self, version: SruVersion) -> String {
+        let (prefix, namespace) = version.namespace();
+        let indexes: String = [
+            ("Title", "dc", "title"),
+            ("Creator", "dc", "creator"),
+            ("ISBN", "bath", "isbn"),
+            ("Title or creator", "cql", "serverChoice"),
+            ("Every record", "cql", "allRecords"),
+        ]
+        .into_iter()
+        .map(|(title, set, name)| {
+            format!(
+                "<index><title>{title}</title><map><name set=\"{set}\">{name}</name></map></index>"
+            )
+        })
+        .collect();
+        let schemas: String = RecordSchema::ALL
+            .into_iter()
+            .map(|schema| {
+                format!(
+                    "<schema identifier=\"{}\" name=\"{}\"><title>{}</title></schema>",
+                    schema.identifier(),
+                    schema.name(),
+                    schema.title()
+                )
+            })
+            .collect();
+        let explain = format!(
+            "<explain xmlns=\"{EXPLAIN_NAMESPACE}\">\
+             <serverInfo protocol=\"SRU\" version=\"{}\"><host>{}</host><port>{}</port>\
+             <database>{}</database></serverInfo>\
+             <databaseInfo><title>{}</title></databaseInfo>\
+             <indexInfo>\
+             <set name=\"dc\" identifier=\"info:srw/cql-context-set/1/dc-v1.1\"/>\
+             <set name=\"bath\" identifier=\"http://zing.z3950.org/cql/bath/2.0/\"/>\
+             <set name=\"cql\" identifier=\"info:srw/cql-context-set/1/cql-v1.2\"/>\
+             {indexes}</indexInfo>\
+             <schemaInfo>{schemas}</schemaInfo>\
+             <configInfo>\
+             <default type=\"numberOfRecords\">{DEFAULT_MAXIMUM_RECORDS}</default>\
+             <setting type=\"maximumRecords\">{MAX_MAXIMUM_RECORDS}</setting>\
+             <default type=\"retrieveSchema\">{}</default>\
+             </configInfo></explain>",
+            version.as_str(),
+            escape(self.host),
+            self.port,
+            escape(self.database),
+            escape(DATABASE_TITLE),
+            RecordSchema::Dc.name()
+        );
+        let escaping = version.escaping_parameter();
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <{prefix}:explainResponse xmlns:{prefix}=\"{namespace}\">\
+             <{prefix}:version>{}</{prefix}:version>\
+             <{prefix}:record><{prefix}:recordSchema>{EXPLAIN_NAMESPACE}</{prefix}:recordSchema>\
+             <{prefix}:{escaping}>xml</{prefix}:{escaping}>\
+             <{prefix}:recordData>{explain}</{prefix}:recordData></{prefix}:record>\
+             </{prefix}:explainResponse>\n",
+            version.as_str()
+        )
+    }
+}
+
+/// A searchRetrieve response document, the `records` already rendered
+fn search_response(
+    version: SruVersion,
+    count: i64,
+    records: &str,
+    next_record_position: Option<i64>,
+    diagnostic: Option<&SruDiagnostic>,
+) -> String {
+    let (prefix, namespace) = version.namespace();
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <{prefix}:searchRetrieveResponse xmlns:{prefix}=\"{namespace}\">\
+         <{prefix}:version>{}</{prefix}:version>\
+         <{prefix}:numberOfRecords>{count}</{prefix}:numberOfRecords>",
+        version.as_str()
+    );
+    if !records.is_empty() {
+        xml.push_str(&format!("<{prefix}:records>{records}</{prefix}:records>"));
+    }
+    if let Some(next) = next_record_position {
+        xml.push_str(&format!(
+            "<{prefix}:nextRecordPosition>{next}</{prefix}:nextRecordPosition>"
+        ));
+    }
+
+    if let Some(diagnostic) = diagnostic {
+        let details = diagnostic.to_string();
+        let details = match details.is_empty() {
+            true => String::new(),
+            false => format!("<diag:details>{}</diag:details>", escape(&details)),
+        };
+        xml.push_str(&format!(
+            "<{prefix}:diagnostics><diag:diagnostic xmlns:diag=\"{}\">\
+             <diag:uri>{}</diag:uri>{details}<diag:message>{}</diag:message>\
+             </diag:diagnostic></{prefix}:diagnostics>",
+            version.diagnostic_namespace(),
+            diagnostic.uri(),
+            diagnostic.message()
+        ));
+    }
+
+    xml.push_str(&format!("</{prefix}:searchRetrieveResponse>\n"));
+    xml
+}
@@ -0,0 +1,46 @@
+use sqlx::types::Uuid;
+use sqlx::PgConnection;
+use time::{Date, Duration};
+
+use crate::db::Db;
+use crate::routes::{ClosureFromQuery, OpeningHours};
+
+/// How far ahead to look for an open day before giving up on a calendar that
+/// never opens
+const MAX_ROLL_FORWARD_DAYS: i64 = 366;
+
+/// True if a location with these hours and closures opens on `date`. A
+/// location with no hours at all is taken to open every day.
+pub fn is_open(date: Date, hours: &[OpeningHours], closures: &[ClosureFromQuery]) -> bool {
+    let weekday = i16::from(date.weekday().number_from_monday());
+    let opens_today = hours.is_empty() || hours.iter().any(|opening| opening.weekday == weekday);
+    let closed_today = closures
+        .iter()
+        .any(|closure| closure.starts_on <= date && date <= closure.ends_on);
+
+    opens_today && !closed_today
+}
+
+/// The first day on or after `date` the location opens
+pub fn next_open_day(date: Date, hours: &[OpeningHours], closures: &[ClosureFromQuery]) -> Date {
+    (0..=MAX_ROLL_FORWARD_DAYS)
+        .map(|days| date + Duration::days(days))
+        .find(|day| is_open(*day, hours, closures))
+        .unwrap_or(date)
+}
+
+/// Rolls a due date forward to the next day the copy's location is open
+pub(crate) async fn roll_forward(
+    location_id: Option<Uuid>,
+    date: Date,
+    connection: &mut PgConnection,
+) -> Result<Date, sqlx::Error> {
+    let Some(location_id) = location_id else {
+        return Ok(date);
+    };
+
+    let hours = Db::get_effective_opening_hours(&location_id, &mut *connection).await?;
+    let closures = Db::get_effective_closures(&location_id, date, connection).await?;
+
+    Ok(next_open_day(date, &hours, &closures))
+}
@@ -0,0 +1,225 @@
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+use time::Date;
+
+use crate::circulation::{policy, today};
+use crate::db::Db;
+use crate::routes::{
+    BookCopyFromQuery, ChargeQuery, CreditQuery, Error, LedgerEntryFromQuery, LoanFromQuery,
+    LoanPolicyMatchFromQuery, PatronFromQuery, ResultExt,
+};
+
+/// Kinds of charge staff may raise by hand. Overdue fines are only ever
+/// raised by accrual.
+const CHARGE_KINDS: [&str; 3] = ["lost", "damaged", "manual"];
+
+/// The patron account ledger. Overdue loans accrue fines under their loan
+/// policy, staff raise charges for lost or damaged items, and payments and
+/// waivers bring the balance back down.
+///
+/// Entries are never changed once written. Accrual tops a loan's fine up to
+/// what it should be as of today rather than charging a day at a time, so
+/// running it twice in a day charges nothing the second time.
+pub struct Fines;
+
+impl Fines {
+    pub async fn charge(
+        charge_query: ChargeQuery,
+        connection_pool: &PgPool,
+    ) -> Result<LedgerEntryFromQuery, Error> {
+        if !CHARGE_KINDS.contains(&charge_query.kind.as_str()) {
+            return Err(Error::unprocessable_entity([(
+                "kind",
+                "must be one of lost, damaged or manual",
+            )]));
+        }
+        if charge_query.amount_cents <= 0 {
+            return Err(Error::unprocessable_entity([(
+                "amount_cents",
+                "must be greater than zero",
+            )]));
+        }
+
+        let mut transaction = connection_pool.begin().await?;
+
+        Db::lock_patron(&charge_query.patron_id, &mut transaction)
+            .await?
+            .ok_or_else(|| Error::unprocessable_entity([("patron_id", "does not exist")]))?;
+
+        let entry = Db::create_ledger_entry(
+            &charge_query.patron_id,
+            charge_query.loan_id,
+            None,
+            &charge_query.kind,
+            charge_query.amount_cents,
+            charge_query.note,
+            charge_query.created_by,
+            &mut transaction,
+        )
+        .await
+        .on_constraint("ledger_entry_loan_id_fkey", |_| {
+            Error::unprocessable_entity([("loan_id", "does not exist")])
+        })?;
+
+        transaction.commit().await?;
+
+        Ok(entry)
+    }
+
+    /// Records a `payment` or `waiver`. Neither may take the balance below
+    /// zero.
+    pub async fn credit(
+        kind: &str,
+        credit_query: CreditQuery,
+        connection_pool: &PgPool,
+    ) -> Result<LedgerEntryFromQuery, Error> {
+        if credit_query.amount_cents <= 0 {
+            return Err(Error::unprocessable_entity([(
+                "amount_cents",
+                "must be greater than zero",
+            )]));
+        }
+
+        let mut transaction = connection_pool.begin().await?;
+
+        Db::lock_patron(&credit_query.patron_id, &mut transaction)
+            .await?
+            .ok_or_else(|| Error::unprocessable_entity([("patron_id", "does not exist")]))?;
+
+        let balance = Db::get_patron_balance(&credit_query.patron_id, &mut transaction).await?;
+        if credit_query.amount_cents > balance {
+            return Err(Error::conflict("amount is more than the patron owes"));
+        }
+
+        let entry = Db::create_ledger_entry(
+            &credit_query.patron_id,
+            None,
+            credit_query.reference_id,
+            kind,
+            -credit_query.amount_cents,
+            credit_query.note,
+            credit_query.created_by,
+            &mut transaction,
+        )
+        .await
+        .on_constraint("ledger_entry_reference_entry_id_fkey", |_| {
+            Error::unprocessable_entity([("reference_id", "does not exist")])
+        })?;
+
+        transaction.commit().await?;
+
+        Ok(entry)
+    }
+
+    /// Brings the fine on every overdue loan still out up to date
+    pub async fn accrue(connection_pool: &PgPool) -> Result<Vec<LedgerEntryFromQuery>, Error> {
+        let today = today();
+        let mut entries = Vec::new();
+
+        for overdue in Db::get_overdue_loan_list(today, connection_pool).await? {
+            let mut transaction = connection_pool.begin().await?;
+
+            let copy = Db::lock_book_copy(&overdue.book_copy_id, &mut transaction)
+                .await?
+                .ok_or(Error::NotFound)?;
+            let loan = Db::lock_loan(&overdue.loan_id, &mut transaction)
+                .await?
+                .ok_or(Error::NotFound)?;
+            if loan.returned_at.is_some() {
+                continue;
+            }
+            let patron = Db::lock_patron(&loan.patron_id, &mut transaction)
+                .await?
+                .ok_or(Error::NotFound)?;
+
+            if let Some(entry) =
+                Self::accrue_loan(&loan, &patron, &copy, today, &mut transaction).await?
+            {
+                entries.push(entry);
+            }
+
+            transaction.commit().await?;
+        }
+
+        Ok(entries)
+    }
+
+    /// Charges whatever a loan's overdue fine as of `as_of` comes to beyond
+    /// what has already been charged for it
+    pub(crate) async fn accrue_loan(
+        loan: &LoanFromQuery,
+        patron: &PatronFromQuery,
+        copy: &BookCopyFromQuery,
+        as_of: Date,
+        connection: &mut PgConnection,
+    ) -> Result<Option<LedgerEntryFromQuery>, sqlx::Error> {
+        let matches = Db::get_loan_policy_matches(
+            &patron.patron_category,
+            &copy.item_type,
+            copy.location_id,
+            &mut *connection,
+        )
+        .await?;
+        let Some(policy) = policy::resolve(matches) else {
+            return Ok(None);
+        };
+
+        let fine = overdue_fine_cents(
+            loan.due_on,
+            as_of,
+            policy.daily_fine_cents,
+            policy.max_fine_cents,
+        );
+        let charged = Db::get_overdue_charged(&loan.loan_id, &mut *connection).await?;
+        if fine <= charged {
+            return Ok(None);
+        }
+
+        Db::create_ledger_entry(
+            &loan.patron_id,
+            Some(loan.loan_id),
+            None,
+            "overdue",
+            fine - charged,
+            None,
+            None,
+            connection,
+        )
+        .await
+        .map(Some)
+    }
+
+    /// Refuses patrons who owe more than the policy's fine threshold
+    pub(crate) async fn ensure_under_threshold(
+        patron_id: &Uuid,
+        policy: &LoanPolicyMatchFromQuery,
+        connection: &mut PgConnection,
+    ) -> Result<(), Error> {
+        let Some(threshold) = policy.fine_threshold_cents else {
+            return Ok(());
+        };
+
+        if Db::get_patron_balance(patron_id, connection).await? > i64::from(threshold) {
+            return Err(Error::conflict("patron owes more than the fine threshold"));
+        }
+
+        Ok(())
+    }
+}
+
+/// The overdue fine on a loan due on `due_on` as of `as_of`: the daily rate
+/// for each day late, capped at `max_fine_cents` when the policy sets one
+pub fn overdue_fine_cents(
+    due_on: Date,
+    as_of: Date,
+    daily_fine_cents: i32,
+    max_fine_cents: Option<i32>,
+) -> i64 {
+    let days_late = (as_of - due_on).whole_days().max(0);
+    let fine = days_late * i64::from(daily_fine_cents);
+
+    match max_fine_cents {
+        Some(max_fine_cents) => fine.min(i64::from(max_fine_cents)),
+        None => fine,
+    }
+}
@@ -0,0 +1,191 @@
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+use time::Date;
+
+use crate::circulation::{due_date, ensure_patron_may_borrow, today};
+use crate::db::Db;
+use crate::routes::{Error, HoldFromQuery, HoldQuery, ResultExt};
+
+/// How many days a trapped copy waits on the hold shelf for its patron
+pub const HOLD_SHELF_DAYS: i32 = 7;
+
+/// The hold queue. Patrons line up for a title first come, first served and
+/// each copy returned goes to the first hold able to take it.
+///
+/// Anything changing a queue locks it whole before touching its holds, so
+/// changes to one title's queue are applied one at a time.
+pub struct Holds;
+
+impl Holds {
+    pub async fn place(
+        hold_query: HoldQuery,
+        connection_pool: &PgPool,
+    ) -> Result<HoldFromQuery, Error> {
+        let mut transaction = connection_pool.begin().await?;
+
+        let patron = Db::lock_patron(&hold_query.patron_id, &mut transaction)
+            .await?
+            .ok_or_else(|| Error::unprocessable_entity([("patron_id", "does not exist")]))?;
+        ensure_patron_may_borrow(&patron, today())?;
+
+        let queue = Db::lock_hold_queue(&hold_query.master_book_id, &mut transaction).await?;
+        let queue_position = queue
+            .iter()
+            .map(|hold| hold.queue_position)
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        let hold = Db::create_hold(hold_query, queue_position, &mut transaction)
+            .await
+            .on_constraint("hold_patron_id_master_book_id_active_key", |_| {
+                Error::conflict("patron already has a hold on this title")
+            })
+            .on_constraint("hold_master_book_id_fkey", |_| {
+                Error::unprocessable_entity([("master_book_id", "does not exist")])
+            })
+            .on_constraint("hold_pickup_location_id_fkey", |_| {
+                Error::unprocessable_entity([("pickup_location_id", "does not exist")])
+            })
+            .on_constraint("hold_suspension_check", |_| {
+                Error::unprocessable_entity([("suspended_from", "is required to suspend a hold")])
+            })
+            .on_constraint("hold_suspension_order_check", |_| {
+                Error::unprocessable_entity([(
+                    "suspended_until",
+                    "must not be before suspended_from",
+                )])
+            })?;
+
+        transaction.commit().await?;
+
+        Ok(hold)
+    }
+
+    /// Cancels a hold. A copy already trapped for it goes on to the next hold
+    /// in the queue.
+    pub async fn cancel(id: &Uuid, connection_pool: &PgPool) -> Result<HoldFromQuery, Error> {
+        let mut transaction = connection_pool.begin().await?;
+
+        let master_book_id = Db::get_hold_master_book_id(id, &mut transaction)
+            .await?
+            .ok_or(Error::NotFound)?;
+        Db::lock_hold_queue(&master_book_id, &mut transaction).await?;
+        let hold = Db::lock_hold(id, &mut transaction)
+            .await?
+            .ok_or(Error::NotFound)?;
+        if hold.status != "waiting" && hold.status != "trapped" {
+            return Err(Error::conflict("hold is no longer active"));
+        }
+
+        let cancelled = Db::set_hold_status(id, "cancelled", &mut transaction).await?;
+
+        if let (Some(book_copy_id), "trapped") = (hold.book_copy_id, hold.status.as_str()) {
+            Self::trap(
+                &hold.master_book_id,
+                &book_copy_id,
+                today(),
+                &mut transaction,
+            )
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(cancelled)
+    }
+
+    /// Moves a hold to a new place in its title's queue, 1 being the front,
+    /// and renumbers the rest of the queue around it
+    pub async fn reorder(
+        id: &Uuid,
+        queue_position: usize,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<HoldFromQuery>, Error> {
+        let mut transaction = connection_pool.begin().await?;
+
+        let master_book_id = Db::get_hold_master_book_id(id, &mut transaction)
+            .await?
+            .ok_or(Error::NotFound)?;
+        let queue = Db::lock_hold_queue(&master_book_id, &mut transaction).await?;
+        if !queue.iter().any(|queued| queued.hold_id == *id) {
+            return Err(Error::conflict("hold is no longer active"));
+        }
+
+        let order = move_in_queue(
+            queue.iter().map(|queued| queued.hold_id).collect(),
+            id,
+            queue_position,
+        );
+        for (index, hold_id) in order.iter().enumerate() {
+            Db::set_hold_queue_position(hold_id, index as i32 + 1, &mut transaction).await?;
+        }
+
+        let reordered = Db::lock_hold_queue(&master_book_id, &mut transaction).await?;
+
+        transaction.commit().await?;
+
+        Ok(reordered)
+    }
+
+    /// Expires every trapped hold whose patron didn't collect it by its
+    /// `pickup_by` date, passing each copy on to the next hold in its queue.
+    /// Returns the holds it expired.
+    pub async fn expire_uncollected(connection_pool: &PgPool) -> Result<Vec<HoldFromQuery>, Error> {
+        let today = today();
+        let mut expired = Vec::new();
+
+        for uncollected in Db::get_uncollected_hold_list(today, connection_pool).await? {
+            let mut transaction = connection_pool.begin().await?;
+
+            Db::lock_hold_queue(&uncollected.master_book_id, &mut transaction).await?;
+            let hold = Db::lock_hold(&uncollected.hold_id, &mut transaction)
+                .await?
+                .ok_or(Error::NotFound)?;
+            let (Some(book_copy_id), Some(pickup_by)) = (hold.book_copy_id, hold.pickup_by) else {
+                continue;
+            };
+            if hold.status != "trapped" || pickup_by >= today {
+                continue;
+            }
+
+            expired.push(Db::set_hold_status(&hold.hold_id, "expired", &mut transaction).await?);
+            Self::trap(&hold.master_book_id, &book_copy_id, today, &mut transaction).await?;
+
+            transaction.commit().await?;
+        }
+
+        Ok(expired)
+    }
+
+    /// Traps a returned copy for the next hold on its title, if there is one.
+    /// The queue is locked first, so a place, reorder or cancel running at
+    /// the same time is waited for rather than skipped over.
+    pub(crate) async fn trap(
+        master_book_id: &Uuid,
+        book_copy_id: &Uuid,
+        today: Date,
+        connection: &mut PgConnection,
+    ) -> Result<Option<HoldFromQuery>, sqlx::Error> {
+        Db::lock_hold_queue(master_book_id, &mut *connection).await?;
+        Db::trap_next_hold(
+            master_book_id,
+            book_copy_id,
+            today,
+            due_date(today, HOLD_SHELF_DAYS),
+            connection,
+        )
+        .await
+    }
+}
+
+/// Moves `id` to the 1-based `position` in `queue`, clamping to the ends
+pub fn move_in_queue(mut queue: Vec<Uuid>, id: &Uuid, position: usize) -> Vec<Uuid> {
+    if let Some(current) = queue.iter().position(|queued| queued == id) {
+        let moving = queue.remove(current);
+        let index = position.saturating_sub(1).min(queue.len());
+        queue.insert(index, moving);
+    }
+
+    queue
+}
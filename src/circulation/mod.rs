@@ -0,0 +1,225 @@
+pub mod calendar;
+pub mod fines;
+pub mod hold;
+pub mod policy;
+
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+use time::{Date, Duration, OffsetDateTime};
+
+use crate::circulation::fines::Fines;
+use crate::circulation::hold::Holds;
+use crate::db::Db;
+use crate::routes::{
+    BookCopyFromQuery, Error, HoldFromQuery, LoanFromQuery, LoanPolicyMatchFromQuery,
+    PatronFromQuery, ResultExt,
+};
+
+/// The circulation engine. Checks copies out to patrons, checks them back in
+/// and renews them.
+///
+/// Every operation runs in its own transaction and locks the rows it makes
+/// decisions on, so two desks scanning the same copy can't both lend it out.
+/// The locks are always taken in the same order, the copy, then its loan,
+/// then the patron, then the title's hold queue, so operations on the same
+/// loan wait for each other rather than deadlock.
+/// Anything that refuses a loan comes back as `Error::Conflict`.
+///
+/// Loan periods and limits come from the loan policy resolved for the
+/// patron's category and the copy's item type and location. Checking a copy
+/// in traps it for the next hold on its title, after which only that hold's
+/// patron may check it out.
+///
+/// Patrons owing more than their policy's fine threshold may not borrow or
+/// renew, and a late return is charged its overdue fine as it is checked in.
+///
+/// A due date that lands on a day the copy's location is closed rolls forward
+/// to the next day it opens.
+pub struct Circulation;
+
+impl Circulation {
+    pub async fn checkout(
+        book_copy_id: &Uuid,
+        patron_id: &Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<LoanFromQuery, Error> {
+        let today = today();
+        let mut transaction = connection_pool.begin().await?;
+
+        let copy = Db::lock_book_copy(book_copy_id, &mut transaction)
+            .await?
+            .ok_or_else(|| Error::unprocessable_entity([("book_copy_id", "does not exist")]))?;
+        if copy.withdrawn_at.is_some() {
+            return Err(Error::conflict("copy has been withdrawn"));
+        }
+
+        if Db::get_open_loan_for_book_copy(book_copy_id, &mut transaction)
+            .await?
+            .is_some()
+        {
+            return Err(Error::conflict("copy is already checked out"));
+        }
+
+        let patron = Db::lock_patron(patron_id, &mut transaction)
+            .await?
+            .ok_or_else(|| Error::unprocessable_entity([("patron_id", "does not exist")]))?;
+        ensure_patron_may_borrow(&patron, today)?;
+
+        if let Some(hold) =
+            Db::get_trapped_hold_for_book_copy(book_copy_id, &mut transaction).await?
+        {
+            if hold.patron_id != *patron_id {
+                return Err(Error::conflict("copy is on hold for another patron"));
+            }
+            Db::set_hold_status(&hold.hold_id, "fulfilled", &mut transaction).await?;
+        }
+
+        let policy = Self::loan_policy(&patron, &copy, &mut transaction).await?;
+        Fines::ensure_under_threshold(patron_id, &policy, &mut transaction).await?;
+        let open_loans = Db::count_open_loans(patron_id, &mut transaction).await?;
+        if open_loans >= i64::from(policy.max_items) {
+            return Err(Error::conflict("patron has reached their loan limit"));
+        }
+
+        let due_on = calendar::roll_forward(
+            copy.location_id,
+            due_date(today, policy.loan_period_days),
+            &mut transaction,
+        )
+        .await?;
+        let loan = Db::create_loan(book_copy_id, patron_id, due_on, &mut transaction)
+            .await
+            .on_constraint("loan_book_copy_id_open_key", |_| {
+                Error::conflict("copy is already checked out")
+            })?;
+
+        transaction.commit().await?;
+
+        Ok(loan)
+    }
+
+    /// Checks a copy in, returning the loan and the hold it was trapped for
+    pub async fn checkin(
+        book_copy_id: &Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<(LoanFromQuery, Option<HoldFromQuery>), Error> {
+        let today = today();
+        let mut transaction = connection_pool.begin().await?;
+
+        let copy = Db::lock_book_copy(book_copy_id, &mut transaction)
+            .await?
+            .ok_or_else(|| Error::unprocessable_entity([("book_copy_id", "does not exist")]))?;
+
+        let open_loan = Db::get_open_loan_for_book_copy(book_copy_id, &mut transaction)
+            .await?
+            .ok_or_else(|| Error::conflict("copy is not checked out"))?;
+
+        let patron = Db::lock_patron(&open_loan.patron_id, &mut transaction)
+            .await?
+            .ok_or(Error::NotFound)?;
+        Fines::accrue_loan(&open_loan, &patron, &copy, today, &mut transaction).await?;
+
+        let loan = Db::return_loan(&open_loan.loan_id, &mut transaction).await?;
+
+        let hold = match copy.withdrawn_at {
+            Some(_) => None,
+            None => {
+                Holds::trap(&copy.master_book_id, book_copy_id, today, &mut transaction).await?
+            }
+        };
+
+        transaction.commit().await?;
+
+        Ok((loan, hold))
+    }
+
+    pub async fn renew(loan_id: &Uuid, connection_pool: &PgPool) -> Result<LoanFromQuery, Error> {
+        let today = today();
+        let mut transaction = connection_pool.begin().await?;
+
+        let book_copy_id = Db::get_loan_book_copy_id(loan_id, &mut transaction)
+            .await?
+            .ok_or(Error::NotFound)?;
+        let copy = Db::lock_book_copy(&book_copy_id, &mut transaction)
+            .await?
+            .ok_or(Error::NotFound)?;
+
+        let loan = Db::lock_loan(loan_id, &mut transaction)
+            .await?
+            .ok_or(Error::NotFound)?;
+        if loan.returned_at.is_some() {
+            return Err(Error::conflict("loan has already been returned"));
+        }
+
+        let patron = Db::lock_patron(&loan.patron_id, &mut transaction)
+            .await?
+            .ok_or(Error::NotFound)?;
+        ensure_patron_may_borrow(&patron, today)?;
+
+        let policy = Self::loan_policy(&patron, &copy, &mut transaction).await?;
+        Fines::ensure_under_threshold(&loan.patron_id, &policy, &mut transaction).await?;
+        if loan.renewals >= policy.max_renewals {
+            return Err(Error::conflict("loan has reached its renewal limit"));
+        }
+        if Db::count_eligible_holds(&copy.master_book_id, today, &mut transaction).await? > 0 {
+            return Err(Error::conflict("title has holds waiting"));
+        }
+
+        let due_on = calendar::roll_forward(
+            copy.location_id,
+            renewal_due_date(loan.due_on, today, policy.loan_period_days),
+            &mut transaction,
+        )
+        .await?;
+        let renewed = Db::renew_loan(loan_id, due_on, &mut transaction).await?;
+
+        transaction.commit().await?;
+
+        Ok(renewed)
+    }
+
+    /// Resolves the loan policy for a patron borrowing a copy
+    async fn loan_policy(
+        patron: &PatronFromQuery,
+        copy: &BookCopyFromQuery,
+        connection: &mut PgConnection,
+    ) -> Result<LoanPolicyMatchFromQuery, Error> {
+        let matches = Db::get_loan_policy_matches(
+            &patron.patron_category,
+            &copy.item_type,
+            copy.location_id,
+            connection,
+        )
+        .await?;
+
+        policy::resolve(matches).ok_or_else(|| Error::conflict("no loan policy applies"))
+    }
+}
+
+/// The date a loan starting on `from` comes due
+pub fn due_date(from: Date, loan_period_days: i32) -> Date {
+    from + Duration::days(loan_period_days.into())
+}
+
+/// The date a renewed loan comes due: a full period past its current due
+/// date, or past today if it is already overdue. Renewing early never
+/// shortens a loan.
+pub fn renewal_due_date(due_on: Date, today: Date, loan_period_days: i32) -> Date {
+    due_date(due_on.max(today), loan_period_days)
+}
+
+/// Refuses blocked patrons and patrons whose card has expired
+pub fn ensure_patron_may_borrow(patron: &PatronFromQuery, today: Date) -> Result<(), Error> {
+    if patron.blocked {
+        return Err(Error::conflict("patron is blocked"));
+    }
+
+    match patron.expires_on {
+        Some(expires_on) if expires_on < today => Err(Error::conflict("patron card has expired")),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn today() -> Date {
+    OffsetDateTime::now_utc().date()
+}
@@ -0,0 +1,27 @@
+use std::cmp::Reverse;
+
+use crate::routes::LoanPolicyMatchFromQuery;
+
+/// Orders the policies matching a loan from most to least specific, so the
+/// first one is the rule that applies.
+///
+/// A policy naming a location beats one that doesn't, and the closer that
+/// location is to the copy's own shelf the better. After that a policy naming
+/// the patron category beats one that doesn't, and finally the same for the
+/// item type.
+pub fn rank(mut matches: Vec<LoanPolicyMatchFromQuery>) -> Vec<LoanPolicyMatchFromQuery> {
+    matches.sort_by_key(|candidate| {
+        Reverse((
+            candidate.location_distance.map(Reverse),
+            candidate.patron_category.is_some(),
+            candidate.item_type.is_some(),
+        ))
+    });
+
+    matches
+}
+
+/// The rule that applies out of the policies matching a loan
+pub fn resolve(matches: Vec<LoanPolicyMatchFromQuery>) -> Option<LoanPolicyMatchFromQuery> {
+    rank(matches).into_iter().next()
+}
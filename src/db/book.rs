@@ -0,0 +1,465 @@
+use futures::stream::BoxStream;
+use sqlx::postgres::PgQueryResult;
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgExecutor, PgPool, Postgres, QueryBuilder};
+
+use crate::catalog::backfill::BackfillFromQuery;
+use crate::catalog::publish_date::PublishDate;
+use crate::catalog::search::HIGHLIGHT_OPTIONS;
+use crate::db::Db;
+use crate::routes::pagination::{page_size, Page};
+use crate::routes::{
+    BookFromQuery, BookQuery, BookSearchFromQuery, BookUpdateQuery, ListBooksQuery, SortOrder,
+};
+
+/// Queries against the `master_book` table
+impl Db {
+    /// Lists a page of books matching the filters in `query`. The filters are
+    /// bound as parameters and the sort column comes from a fixed list, so
+    /// nothing the client sends is spliced into the SQL.
+    ///
+    /// Nulls sort last whichever way the sort runs, and ties are broken by
+    /// id, so the cursor picks up strictly after the last book returned.
+    pub async fn get_book_list(
+        query: &ListBooksQuery,
+        connection_pool: &PgPool,
+    ) -> Result<Page<BookFromQuery>, sqlx::Error> {
+        let column = query.sort.column();
+        let column_type = query.sort.column_type();
+        let page_size = page_size(query.page_size);
+
+        let mut builder = QueryBuilder::<Postgres>::new(
+            r#"select
+                master_book_id,
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate
+            from "master_book" where true"#,
+        );
+
+        if let Some(author) = &query.author {
+            builder
+                .push(" and author ilike ")
+                .push_bind(contains_pattern(author));
+        }
+        if let Some(title) = &query.title {
+            builder
+                .push(" and title ilike ")
+                .push_bind(contains_pattern(title));
+        }
+        if let Some(isbn) = &query.isbn {
+            builder.push(" and isbn_13 = ").push_bind(isbn);
+        }
+        if let Some(lccn) = &query.lccn {
+            builder
+                .push(" and lccn_normalized = ")
+                .push_bind(lccn);
+        }
+        if let Some(from) = query.publish_date_from {
+            builder.push(" and published_on >= ").push_bind(from);
+        }
+        if let Some(to) = query.publish_date_to {
+            builder.push(" and published_on <= ").push_bind(to);
+        }
+
+        if let Some(cursor) = &query.cursor {
+            match &cursor.value {
+                Some(value) => {
+                    let after = match query.order {
+                        SortOrder::Asc => " > ",
+                        SortOrder::Desc => " < ",
+                    };
+                    builder
+                        .push(format_args!(" and ({column}{after}"))
+                        .push_bind(value)
+                        .push(format_args!("::{column_type} or ({column} = "))
+                        .push_bind(value)
+                        .push(format_args!("::{column_type} and master_book_id > "))
+                        .push_bind(cursor.id)
+                        .push(format_args!(") or {column} is null)"));
+                }
+                None => {
+                    builder
+                        .push(format_args!(" and {column} is null and master_book_id > "))
+                        .push_bind(cursor.id);
+                }
+            }
+        }
+
+        builder.push(format_args!(" order by {column}"));
+        builder.push(match query.order {
+            SortOrder::Asc => " asc nulls last",
+            SortOrder::Desc => " desc nulls last",
+        });
+        builder
+            .push(", master_book_id limit ")
+            .push_bind(page_size + 1);
+
+        let rows = builder
+            .build_query_as::<BookFromQuery>()
+            .fetch_all(connection_pool)
+            .await?;
+
+        Ok(Page::from_rows(rows, page_size))
+    }
+
+    /// Up to `limit` of the books matching `tsquery`, best match first,
+    /// skipping the first `offset`. Titles count for more than authors, and
+    /// where each one matched is marked as `HIGHLIGHT_OPTIONS` describes.
+    pub async fn search_books(
+        tsquery: &str,
+        offset: i64,
+        limit: i64,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<BookSearchFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookSearchFromQuery,
+            r#"select
+                master_book_id,
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate,
+                ts_rank_cd(search_vector, query) as "rank!",
+                ts_headline('english', coalesce(title, ''), query, $4) as "title_highlight!",
+                ts_headline('english', coalesce(author, ''), query, $4) as "author_highlight!"
+            from "master_book", to_tsquery('english', $1) query
+            where search_vector @@ query
+            order by ts_rank_cd(search_vector, query) desc, title, master_book_id
+            offset $2
+            limit $3"#,
+            tsquery,
+            offset,
+            limit,
+            HIGHLIGHT_OPTIONS
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    /// How many books match `tsquery`
+    pub async fn count_search_results(
+        tsquery: &str,
+        connection_pool: &PgPool,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"select count(*) as "count!"
+            from "master_book", to_tsquery('english', $1) query
+            where search_vector @@ query"#,
+            tsquery
+        )
+        .fetch_one(connection_pool)
+        .await
+    }
+
+    pub async fn get_book(
+        id: &Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<BookFromQuery, sqlx::Error> {
+        sqlx::query_as!(
+            BookFromQuery,
+            r#"select 
+                master_book_id, 
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate
+            from "master_book" where master_book_id = $1"#,
+            id
+        )
+        .fetch_one(connection_pool)
+        .await
+    }
+
+    /// Every book in id order, read from the database as the stream is
+    /// polled rather than all at once
+    pub fn get_book_stream(
+        connection_pool: &PgPool,
+    ) -> BoxStream<'_, Result<BookFromQuery, sqlx::Error>> {
+        sqlx::query_as!(
+            BookFromQuery,
+            r#"select
+                master_book_id,
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate
+            from "master_book"
+            order by master_book_id"#
+        )
+        .fetch(connection_pool)
+    }
+
+    /// The earliest catalogued book with an ISBN-13 or, failing that, a
+    /// normalized LCCN
+    pub async fn find_book_by_identifier(
+        isbn_13: Option<&str>,
+        lccn_normalized: Option<&str>,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<Option<BookFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookFromQuery,
+            r#"select
+                master_book_id,
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate
+            from "master_book"
+            where isbn_13 = $1 or lccn_normalized = $2
+            order by isbn_13 = $1 desc nulls last, create_at
+            limit 1"#,
+            isbn_13,
+            lccn_normalized
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// The earliest catalogued book with a normalized LCCN
+    pub async fn get_book_by_lccn(
+        lccn_normalized: &str,
+        connection_pool: &PgPool,
+    ) -> Result<Option<BookFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookFromQuery,
+            r#"select
+                master_book_id,
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate
+            from "master_book" where lccn_normalized = $1
+            order by create_at
+            limit 1"#,
+            lccn_normalized
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    pub async fn create_book(
+        book_query: BookQuery,
+        isbn_13: Option<String>,
+        lccn_normalized: Option<String>,
+        published: Option<PublishDate>,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<Uuid, sqlx::Error> {
+        sqlx::query_scalar!(
+            // language=PostgreSQL
+            r#"insert into "master_book"
+                (author, title, lccn, lccn_normalized, isbn, isbn_13, publish_date,
+                 published_on, published_precision, published_approximate)
+               values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) returning master_book_id"#,
+            book_query.author,
+            book_query.title,
+            book_query.lccn,
+            lccn_normalized,
+            book_query.isbn,
+            isbn_13,
+            book_query.publish_date,
+            published.map(|published| published.on),
+            published.map(|published| published.precision.as_str()),
+            published.map_or(false, |published| published.approximate)
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    /// Updates a book. `isbn_13`, `lccn_normalized` and the structured
+    /// publication date go with `isbn`, `lccn` and `publish_date`, so each
+    /// only changes when a new value for its field is sent.
+    pub async fn update_book(
+        book_update_query: BookUpdateQuery,
+        isbn_13: Option<String>,
+        lccn_normalized: Option<String>,
+        published: Option<PublishDate>,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<BookFromQuery, sqlx::Error> {
+        sqlx::query_as!(
+            BookFromQuery,
+            r#"
+            with updated_book as (
+
+            update "master_book" 
+               set 
+                   author = coalesce($2, author),
+                   title = coalesce($3, title),
+                   lccn = coalesce($4, lccn),
+                   lccn_normalized = case when $4::text is null then lccn_normalized else $8 end,
+                   isbn = coalesce($5, isbn),
+                   isbn_13 = case when $5::text is null then isbn_13 else $7 end,
+                   publish_date = coalesce($6, publish_date),
+                   published_on = case when $6::text is null then published_on else $9 end,
+                   published_precision = case
+                       when $6::text is null then published_precision else $10
+                   end,
+                   published_approximate = case
+                       when $6::text is null then published_approximate else $11
+                   end,
+                   updated_at = now()
+            where master_book_id = $1
+            returning 
+                    master_book_id, author, title, lccn, lccn_normalized, isbn, isbn_13, publish_date,
+                    published_on, published_precision, published_approximate
+            )
+            select 
+               updated_book.master_book_id master_book_id,
+               updated_book.author author,
+               updated_book.title title,
+               updated_book.lccn lccn,
+               updated_book.lccn_normalized lccn_normalized,
+               updated_book.isbn isbn,
+               updated_book.isbn_13 isbn_13,
+               updated_book.publish_date publish_date,
+               updated_book.published_on published_on,
+               updated_book.published_precision published_precision,
+               updated_book.published_approximate published_approximate
+            from updated_book    
+            "#,
+            book_update_query.id,
+            book_update_query.author,
+            book_update_query.title,
+            book_update_query.lccn,
+            book_update_query.isbn,
+            book_update_query.publish_date,
+            isbn_13,
+            lccn_normalized,
+            published.map(|published| published.on),
+            published.map(|published| published.precision.as_str()),
+            published.map_or(false, |published| published.approximate),
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    pub async fn delete_book(
+        id: Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(r#"delete from "master_book" where master_book_id=$1"#, id)
+            .execute(connection_pool)
+            .await
+    }
+
+    /// A batch of books, after `after` in id order, with something typed
+    /// but not yet read. Only what is still to be read is returned: the ISBN
+    /// without an ISBN-13, the LCCN without a normalized one, and the
+    /// publication date without a structured one.
+    pub async fn get_backfill_list(
+        after: Option<Uuid>,
+        limit: i64,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<BackfillFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BackfillFromQuery,
+            r#"select
+                master_book_id,
+                case when isbn_13 is null then isbn end as isbn,
+                case when lccn_normalized is null then lccn end as lccn,
+                case when published_on is null then publish_date end as publish_date
+            from "master_book"
+            where (
+                (isbn_13 is null and coalesce(trim(isbn), '') <> '')
+                or (lccn_normalized is null and coalesce(trim(lccn), '') <> '')
+                or (published_on is null and coalesce(trim(publish_date), '') <> '')
+              )
+              and ($1::uuid is null or master_book_id > $1)
+            order by master_book_id
+            limit $2"#,
+            after,
+            limit
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    /// Fills in what was read of a book, leaving alone anything written
+    /// since, returning how many books were changed
+    pub async fn backfill_book(
+        id: Uuid,
+        isbn_13: Option<&str>,
+        lccn_normalized: Option<&str>,
+        published: Option<&PublishDate>,
+        connection: &mut PgConnection,
+    ) -> Result<u64, sqlx::Error> {
+        // What is read shows in harvested records, so the change is
+        // datestamped
+        Ok(sqlx::query!(
+            r#"update "master_book"
+               set isbn_13 = coalesce(isbn_13, $2),
+                   lccn_normalized = coalesce(lccn_normalized, $3),
+                   published_on = coalesce(published_on, $4),
+                   published_precision = case
+                       when published_on is null and $4::date is not null then $5
+                       else published_precision
+                   end,
+                   published_approximate = case
+                       when published_on is null and $4::date is not null then $6
+                       else published_approximate
+                   end,
+                   updated_at = now()
+            where master_book_id = $1
+              and (
+                (isbn_13 is null and $2::text is not null)
+                or (lccn_normalized is null and $3::text is not null)
+                or (published_on is null and $4::date is not null)
+              )"#,
+            id,
+            isbn_13,
+            lccn_normalized,
+            published.map(|published| published.on),
+            published.map(|published| published.precision.as_str()),
+            published.map(|published| published.approximate)
+        )
+        .execute(connection)
+        .await?
+        .rows_affected())
+    }
+}
+
+/// An `ilike` pattern matching `value` anywhere, with its own wildcards escaped
+fn contains_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
@@ -0,0 +1,163 @@
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgExecutor};
+use time::Date;
+
+use crate::db::Db;
+use crate::routes::{ClosureFromQuery, ClosureQuery, OpeningHours};
+
+/// Queries against the `location_opening_hours` and `location_closure`
+/// tables.
+///
+/// A location takes its opening hours from the nearest location up the
+/// hierarchy that has any, so a branch's hours cover its floors and shelves.
+/// Closures add up instead: a branch holiday closes everything below it.
+impl Db {
+    pub async fn get_opening_hours(
+        location_id: &Uuid,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<Vec<OpeningHours>, sqlx::Error> {
+        sqlx::query_as!(
+            OpeningHours,
+            r#"select weekday, opens_at, closes_at
+            from "location_opening_hours"
+            where location_id = $1
+            order by weekday"#,
+            location_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn get_closures(
+        location_id: &Uuid,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<Vec<ClosureFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            ClosureFromQuery,
+            r#"select location_closure_id, starts_on, ends_on, reason
+            from "location_closure"
+            where location_id = $1
+            order by starts_on"#,
+            location_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// The opening hours that apply at a location, inherited from the nearest
+    /// location up the hierarchy that sets any
+    pub async fn get_effective_opening_hours(
+        location_id: &Uuid,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<Vec<OpeningHours>, sqlx::Error> {
+        sqlx::query_as!(
+            OpeningHours,
+            r#"
+            with recursive ancestors as (
+                select location_id, parent_location_id, 0 as distance
+                from "location" where location_id = $1
+                union all
+                select l.location_id, l.parent_location_id, a.distance + 1
+                from "location" l
+                join ancestors a on l.location_id = a.parent_location_id
+            ),
+            nearest as (
+                select a.location_id
+                from ancestors a
+                where exists (
+                    select 1 from "location_opening_hours" h where h.location_id = a.location_id
+                )
+                order by a.distance
+                limit 1
+            )
+            select h.weekday, h.opens_at, h.closes_at
+            from "location_opening_hours" h
+            join nearest n on n.location_id = h.location_id
+            order by h.weekday
+            "#,
+            location_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Closures at a location or anywhere above it that haven't ended before
+    /// `from`
+    pub async fn get_effective_closures(
+        location_id: &Uuid,
+        from: Date,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<Vec<ClosureFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            ClosureFromQuery,
+            r#"
+            with recursive ancestors as (
+                select location_id, parent_location_id
+                from "location" where location_id = $1
+                union all
+                select l.location_id, l.parent_location_id
+                from "location" l
+                join ancestors a on l.location_id = a.parent_location_id
+            )
+            select c.location_closure_id, c.starts_on, c.ends_on, c.reason
+            from "location_closure" c
+            join ancestors a on a.location_id = c.location_id
+            where c.ends_on >= $2
+            order by c.starts_on
+            "#,
+            location_id,
+            from
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Replaces a location's opening hours and closures with the ones given
+    pub async fn set_location_calendar(
+        location_id: &Uuid,
+        hours: &[OpeningHours],
+        closures: &[ClosureQuery],
+        connection: &mut PgConnection,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"delete from "location_opening_hours" where location_id = $1"#,
+            location_id
+        )
+        .execute(&mut *connection)
+        .await?;
+        sqlx::query!(
+            r#"delete from "location_closure" where location_id = $1"#,
+            location_id
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        for opening in hours {
+            sqlx::query!(
+                r#"insert into "location_opening_hours" (location_id, weekday, opens_at, closes_at)
+                   values ($1, $2, $3, $4)"#,
+                location_id,
+                opening.weekday,
+                opening.opens_at,
+                opening.closes_at
+            )
+            .execute(&mut *connection)
+            .await?;
+        }
+
+        for closure in closures {
+            sqlx::query!(
+                r#"insert into "location_closure" (location_id, starts_on, ends_on, reason)
+                   values ($1, $2, $3, $4)"#,
+                location_id,
+                closure.starts_on,
+                closure.ends_on,
+                closure.reason
+            )
+            .execute(&mut *connection)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
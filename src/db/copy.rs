@@ -0,0 +1,176 @@
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::db::Db;
+use crate::routes::{BookCopyFromQuery, BookCopyQuery, BookCopyUpdateQuery, BookHoldingFromQuery};
+
+/// Queries against the `book_copy` table
+impl Db {
+    pub async fn get_book_copy_list(
+        master_book_id: &Uuid,
+        include_withdrawn: bool,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<BookCopyFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookCopyFromQuery,
+            r#"select
+                book_copy_id,
+                master_book_id,
+                location_id,
+                item_type,
+                barcode,
+                condition,
+                acquisition_date,
+                price_cents,
+                withdrawn_at
+            from "book_copy"
+            where master_book_id = $1
+              and ($2 or withdrawn_at is null)
+            order by create_at"#,
+            master_book_id,
+            include_withdrawn
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    pub async fn get_book_copy(
+        id: &Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<Option<BookCopyFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookCopyFromQuery,
+            r#"select
+                book_copy_id,
+                master_book_id,
+                location_id,
+                item_type,
+                barcode,
+                condition,
+                acquisition_date,
+                price_cents,
+                withdrawn_at
+            from "book_copy" where book_copy_id = $1"#,
+            id
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    pub async fn create_book_copy(
+        book_copy_query: BookCopyQuery,
+        connection_pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        sqlx::query_scalar!(
+            // language=PostgreSQL
+            r#"insert into "book_copy" (master_book_id, location_id, item_type, barcode, condition, acquisition_date, price_cents)
+               values ($1, $2, $3, $4, $5, $6, $7) returning book_copy_id"#,
+            book_copy_query.master_book_id,
+            book_copy_query.location_id,
+            book_copy_query.item_type,
+            book_copy_query.barcode,
+            book_copy_query.condition,
+            book_copy_query.acquisition_date,
+            book_copy_query.price_cents
+        )
+        .fetch_one(connection_pool)
+        .await
+    }
+
+    pub async fn update_book_copy(
+        book_copy_update_query: BookCopyUpdateQuery,
+        connection_pool: &PgPool,
+    ) -> Result<Option<BookCopyFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookCopyFromQuery,
+            r#"
+            update "book_copy"
+               set
+                   location_id = coalesce($2, location_id),
+                   item_type = coalesce($3, item_type),
+                   barcode = coalesce($4, barcode),
+                   condition = coalesce($5, condition),
+                   acquisition_date = coalesce($6, acquisition_date),
+                   price_cents = coalesce($7, price_cents),
+                   updated_at = now()
+            where book_copy_id = $1
+            returning
+                book_copy_id,
+                master_book_id,
+                location_id,
+                item_type,
+                barcode,
+                condition,
+                acquisition_date,
+                price_cents,
+                withdrawn_at
+            "#,
+            book_copy_update_query.id,
+            book_copy_update_query.location_id,
+            book_copy_update_query.item_type,
+            book_copy_update_query.barcode,
+            book_copy_update_query.condition,
+            book_copy_update_query.acquisition_date,
+            book_copy_update_query.price_cents,
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    /// Withdrawn copies are kept for their history rather than deleted
+    pub async fn withdraw_book_copy(
+        id: Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<Option<BookCopyFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookCopyFromQuery,
+            r#"
+            update "book_copy"
+               set
+                   withdrawn_at = coalesce(withdrawn_at, now()),
+                   updated_at = now()
+            where book_copy_id = $1
+            returning
+                book_copy_id,
+                master_book_id,
+                location_id,
+                item_type,
+                barcode,
+                condition,
+                acquisition_date,
+                price_cents,
+                withdrawn_at
+            "#,
+            id
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    /// The copies on the shelves for a master record along with where they live
+    pub async fn get_book_holdings(
+        master_book_id: &Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<BookHoldingFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookHoldingFromQuery,
+            r#"select
+                c.book_copy_id,
+                c.barcode,
+                l.location_id as "location_id?",
+                l.parent_location_id,
+                l.name as "location_name?",
+                l.floor,
+                l.shelf_start,
+                l.shelf_end
+            from "book_copy" c
+            left join "location" l on l.location_id = c.location_id
+            where c.master_book_id = $1
+              and c.withdrawn_at is null
+            order by c.create_at"#,
+            master_book_id
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+}
@@ -0,0 +1,444 @@
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use time::Date;
+
+use crate::db::Db;
+use crate::routes::pagination::Page;
+use crate::routes::{HoldCursor, HoldFromQuery, HoldQuery, HoldUpdateQuery};
+
+/// Queries against the `hold` table.
+///
+/// Only `waiting` and `trapped` holds are active; the queue for a title is
+/// its active holds ordered by `queue_position`. A title's queue is locked
+/// before any of its holds.
+impl Db {
+    pub async fn get_hold_list_for_book(
+        master_book_id: &Uuid,
+        page_size: i64,
+        cursor: Option<&HoldCursor>,
+        connection_pool: &PgPool,
+    ) -> Result<Page<HoldFromQuery>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            HoldFromQuery,
+            r#"select
+                hold_id,
+                master_book_id,
+                patron_id,
+                pickup_location_id,
+                queue_position,
+                status,
+                placed_at,
+                expires_on,
+                suspended_from,
+                suspended_until,
+                book_copy_id,
+                trapped_at,
+                pickup_by
+            from "hold"
+            where master_book_id = $1 and status in ('waiting', 'trapped')
+              and ($3::int is null or (queue_position, hold_id) > ($3, $4))
+            order by queue_position, hold_id
+            limit $2"#,
+            master_book_id,
+            page_size + 1,
+            cursor.map(|cursor| cursor.queue_position),
+            cursor.map(|cursor| cursor.id)
+        )
+        .fetch_all(connection_pool)
+        .await?;
+
+        Ok(Page::from_rows(rows, page_size))
+    }
+
+    pub async fn get_hold_list_for_patron(
+        patron_id: &Uuid,
+        page_size: i64,
+        cursor: Option<&HoldCursor>,
+        connection_pool: &PgPool,
+    ) -> Result<Page<HoldFromQuery>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            HoldFromQuery,
+            r#"select
+                hold_id,
+                master_book_id,
+                patron_id,
+                pickup_location_id,
+                queue_position,
+                status,
+                placed_at,
+                expires_on,
+                suspended_from,
+                suspended_until,
+                book_copy_id,
+                trapped_at,
+                pickup_by
+            from "hold"
+            where patron_id = $1 and status in ('waiting', 'trapped')
+              and ($3::timestamptz is null or (placed_at, hold_id) > ($3, $4))
+            order by placed_at, hold_id
+            limit $2"#,
+            patron_id,
+            page_size + 1,
+            cursor.map(|cursor| cursor.placed_at),
+            cursor.map(|cursor| cursor.id)
+        )
+        .fetch_all(connection_pool)
+        .await?;
+
+        Ok(Page::from_rows(rows, page_size))
+    }
+
+    /// The title a hold is for, read without locking anything so the
+    /// title's queue can be locked ahead of the hold
+    pub async fn get_hold_master_book_id(
+        id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        sqlx::query_scalar!(r#"select master_book_id from "hold" where hold_id = $1"#, id)
+            .fetch_optional(connection)
+            .await
+    }
+
+    pub async fn lock_hold(
+        id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<Option<HoldFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            HoldFromQuery,
+            r#"select
+                hold_id,
+                master_book_id,
+                patron_id,
+                pickup_location_id,
+                queue_position,
+                status,
+                placed_at,
+                expires_on,
+                suspended_from,
+                suspended_until,
+                book_copy_id,
+                trapped_at,
+                pickup_by
+            from "hold" where hold_id = $1
+            for update"#,
+            id
+        )
+        .fetch_optional(connection)
+        .await
+    }
+
+    /// Locks a title's queue so places and reorders are applied one at a time
+    pub async fn lock_hold_queue(
+        master_book_id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<HoldFromQuery>, sqlx::Error> {
+        sqlx::query!(
+            r#"select master_book_id from "master_book" where master_book_id = $1 for update"#,
+            master_book_id
+        )
+        .fetch_optional(&mut *connection)
+        .await?;
+
+        sqlx::query_as!(
+            HoldFromQuery,
+            r#"select
+                hold_id,
+                master_book_id,
+                patron_id,
+                pickup_location_id,
+                queue_position,
+                status,
+                placed_at,
+                expires_on,
+                suspended_from,
+                suspended_until,
+                book_copy_id,
+                trapped_at,
+                pickup_by
+            from "hold"
+            where master_book_id = $1 and status in ('waiting', 'trapped')
+            order by queue_position
+            for update"#,
+            master_book_id
+        )
+        .fetch_all(connection)
+        .await
+    }
+
+    pub async fn create_hold(
+        hold_query: HoldQuery,
+        queue_position: i32,
+        connection: &mut PgConnection,
+    ) -> Result<HoldFromQuery, sqlx::Error> {
+        sqlx::query_as!(
+            HoldFromQuery,
+            r#"insert into "hold"
+                (master_book_id, patron_id, pickup_location_id, queue_position,
+                 expires_on, suspended_from, suspended_until)
+               values ($1, $2, $3, $4, $5, $6, $7)
+               returning
+                hold_id,
+                master_book_id,
+                patron_id,
+                pickup_location_id,
+                queue_position,
+                status,
+                placed_at,
+                expires_on,
+                suspended_from,
+                suspended_until,
+                book_copy_id,
+                trapped_at,
+                pickup_by"#,
+            hold_query.master_book_id,
+            hold_query.patron_id,
+            hold_query.pickup_location_id,
+            queue_position,
+            hold_query.expires_on,
+            hold_query.suspended_from,
+            hold_query.suspended_until
+        )
+        .fetch_one(connection)
+        .await
+    }
+
+    /// Updates the pickup location, expiry and suspension of a hold. Sending
+    /// `suspended_from` without `suspended_until` suspends it indefinitely;
+    /// `resume` lifts any suspension.
+    pub async fn update_hold(
+        hold_update_query: HoldUpdateQuery,
+        connection_pool: &PgPool,
+    ) -> Result<Option<HoldFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            HoldFromQuery,
+            r#"
+            update "hold"
+               set
+                   pickup_location_id = coalesce($2, pickup_location_id),
+                   expires_on = coalesce($3, expires_on),
+                   suspended_from = case
+                       when $6 then null
+                       when $4::date is not null then $4
+                       else suspended_from
+                   end,
+                   suspended_until = case
+                       when $6 then null
+                       when $4::date is not null then $5
+                       else coalesce($5, suspended_until)
+                   end,
+                   updated_at = now()
+            where hold_id = $1 and status in ('waiting', 'trapped')
+            returning
+                hold_id,
+                master_book_id,
+                patron_id,
+                pickup_location_id,
+                queue_position,
+                status,
+                placed_at,
+                expires_on,
+                suspended_from,
+                suspended_until,
+                book_copy_id,
+                trapped_at,
+                pickup_by
+            "#,
+            hold_update_query.id,
+            hold_update_query.pickup_location_id,
+            hold_update_query.expires_on,
+            hold_update_query.suspended_from,
+            hold_update_query.suspended_until,
+            hold_update_query.resume,
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    pub async fn set_hold_queue_position(
+        id: &Uuid,
+        queue_position: i32,
+        connection: &mut PgConnection,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"update "hold" set queue_position = $2, updated_at = now() where hold_id = $1"#,
+            id,
+            queue_position
+        )
+        .execute(connection)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_hold_status(
+        id: &Uuid,
+        status: &str,
+        connection: &mut PgConnection,
+    ) -> Result<HoldFromQuery, sqlx::Error> {
+        sqlx::query_as!(
+            HoldFromQuery,
+            r#"update "hold"
+               set
+                   status = $2,
+                   updated_at = now()
+            where hold_id = $1
+            returning
+                hold_id,
+                master_book_id,
+                patron_id,
+                pickup_location_id,
+                queue_position,
+                status,
+                placed_at,
+                expires_on,
+                suspended_from,
+                suspended_until,
+                book_copy_id,
+                trapped_at,
+                pickup_by"#,
+            id,
+            status
+        )
+        .fetch_one(connection)
+        .await
+    }
+
+    pub async fn get_trapped_hold_for_book_copy(
+        book_copy_id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<Option<HoldFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            HoldFromQuery,
+            r#"select
+                hold_id,
+                master_book_id,
+                patron_id,
+                pickup_location_id,
+                queue_position,
+                status,
+                placed_at,
+                expires_on,
+                suspended_from,
+                suspended_until,
+                book_copy_id,
+                trapped_at,
+                pickup_by
+            from "hold"
+            where book_copy_id = $1 and status = 'trapped'
+            for update"#,
+            book_copy_id
+        )
+        .fetch_optional(connection)
+        .await
+    }
+
+    /// Trapped holds whose patron should have collected them before `today`
+    pub async fn get_uncollected_hold_list(
+        today: Date,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<HoldFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            HoldFromQuery,
+            r#"select
+                hold_id,
+                master_book_id,
+                patron_id,
+                pickup_location_id,
+                queue_position,
+                status,
+                placed_at,
+                expires_on,
+                suspended_from,
+                suspended_until,
+                book_copy_id,
+                trapped_at,
+                pickup_by
+            from "hold"
+            where status = 'trapped' and pickup_by < $1
+            order by pickup_by"#,
+            today
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    /// Traps a copy for the first hold in the queue that hasn't expired and
+    /// isn't suspended on `today`. The title's queue must already be locked.
+    pub async fn trap_next_hold(
+        master_book_id: &Uuid,
+        book_copy_id: &Uuid,
+        today: Date,
+        pickup_by: Date,
+        connection: &mut PgConnection,
+    ) -> Result<Option<HoldFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            HoldFromQuery,
+            r#"
+            update "hold"
+               set
+                   status = 'trapped',
+                   book_copy_id = $2,
+                   trapped_at = now(),
+                   pickup_by = $4,
+                   updated_at = now()
+            where hold_id = (
+                select hold_id from "hold"
+                where master_book_id = $1
+                  and status = 'waiting'
+                  and (expires_on is null or expires_on >= $3)
+                  and not (
+                      suspended_from is not null
+                      and suspended_from <= $3
+                      and (suspended_until is null or suspended_until >= $3)
+                  )
+                order by queue_position
+                limit 1
+                for update
+            )
+            returning
+                hold_id,
+                master_book_id,
+                patron_id,
+                pickup_location_id,
+                queue_position,
+                status,
+                placed_at,
+                expires_on,
+                suspended_from,
+                suspended_until,
+                book_copy_id,
+                trapped_at,
+                pickup_by
+            "#,
+            master_book_id,
+            book_copy_id,
+            today,
+            pickup_by
+        )
+        .fetch_optional(connection)
+        .await
+    }
+
+    /// Number of holds on a title that a returned copy could go to today
+    pub async fn count_eligible_holds(
+        master_book_id: &Uuid,
+        today: Date,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"select count(*) as "count!" from "hold"
+            where master_book_id = $1
+              and status = 'waiting'
+              and (expires_on is null or expires_on >= $2)
+              and not (
+                  suspended_from is not null
+                  and suspended_from <= $2
+                  and (suspended_until is null or suspended_until >= $2)
+              )"#,
+            master_book_id,
+            today
+        )
+        .fetch_one(executor)
+        .await
+    }
+}
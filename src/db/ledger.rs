@@ -0,0 +1,126 @@
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use time::Date;
+
+use crate::db::Db;
+use crate::routes::{LedgerEntryFromQuery, LoanFromQuery};
+
+/// Queries against the `ledger_entry` table.
+///
+/// Entries are only ever inserted; the table refuses updates and deletes.
+/// Charges are stored positive and credits negative, so a balance is a sum.
+impl Db {
+    pub async fn get_ledger_entry_list(
+        patron_id: &Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<LedgerEntryFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LedgerEntryFromQuery,
+            r#"select
+                ledger_entry_id,
+                patron_id,
+                loan_id,
+                reference_entry_id,
+                kind,
+                amount_cents,
+                note,
+                created_by,
+                created_at
+            from "ledger_entry"
+            where patron_id = $1
+            order by created_at, ledger_entry_id"#,
+            patron_id
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    pub async fn get_patron_balance(
+        patron_id: &Uuid,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"select coalesce(sum(amount_cents), 0)::bigint as "balance!"
+            from "ledger_entry" where patron_id = $1"#,
+            patron_id
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    /// How much of a loan's overdue fine has been charged so far
+    pub async fn get_overdue_charged(
+        loan_id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"select coalesce(sum(amount_cents), 0)::bigint as "charged!"
+            from "ledger_entry" where loan_id = $1 and kind = 'overdue'"#,
+            loan_id
+        )
+        .fetch_one(connection)
+        .await
+    }
+
+    /// Loans still out that came due before `today`
+    pub async fn get_overdue_loan_list(
+        today: Date,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<LoanFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LoanFromQuery,
+            r#"select
+                loan_id,
+                book_copy_id,
+                patron_id,
+                checked_out_at,
+                due_on,
+                returned_at,
+                renewals
+            from "loan"
+            where returned_at is null and due_on < $1
+            order by due_on"#,
+            today
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_ledger_entry(
+        patron_id: &Uuid,
+        loan_id: Option<Uuid>,
+        reference_entry_id: Option<Uuid>,
+        kind: &str,
+        amount_cents: i64,
+        note: Option<String>,
+        created_by: Option<String>,
+        connection: &mut PgConnection,
+    ) -> Result<LedgerEntryFromQuery, sqlx::Error> {
+        sqlx::query_as!(
+            LedgerEntryFromQuery,
+            r#"insert into "ledger_entry"
+                (patron_id, loan_id, reference_entry_id, kind, amount_cents, note, created_by)
+               values ($1, $2, $3, $4, $5, $6, $7)
+               returning
+                ledger_entry_id,
+                patron_id,
+                loan_id,
+                reference_entry_id,
+                kind,
+                amount_cents,
+                note,
+                created_by,
+                created_at"#,
+            patron_id,
+            loan_id,
+            reference_entry_id,
+            kind,
+            amount_cents,
+            note,
+            created_by
+        )
+        .fetch_one(connection)
+        .await
+    }
+}
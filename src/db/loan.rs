@@ -0,0 +1,236 @@
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+use time::Date;
+
+use crate::db::Db;
+use crate::routes::pagination::Page;
+use crate::routes::{BookCopyFromQuery, LoanCursor, LoanFromQuery, PatronFromQuery};
+
+/// Queries against the `loan` table, along with the row locks circulation
+/// takes on copies and patrons while it works out whether a loan is allowed.
+/// Those locks are always taken copy first, then loan, then patron.
+///
+/// Anything taking a `PgConnection` is expected to run inside a transaction.
+impl Db {
+    pub async fn lock_book_copy(
+        id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<Option<BookCopyFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookCopyFromQuery,
+            r#"select
+                book_copy_id,
+                master_book_id,
+                location_id,
+                item_type,
+                barcode,
+                condition,
+                acquisition_date,
+                price_cents,
+                withdrawn_at
+            from "book_copy" where book_copy_id = $1
+            for update"#,
+            id
+        )
+        .fetch_optional(connection)
+        .await
+    }
+
+    pub async fn lock_patron(
+        id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<Option<PatronFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            PatronFromQuery,
+            r#"select
+                patron_id,
+                card_number,
+                first_name,
+                last_name,
+                email,
+                phone,
+                address,
+                patron_category,
+                expires_on,
+                blocked,
+                blocked_reason
+            from "patron" where patron_id = $1
+            for update"#,
+            id
+        )
+        .fetch_optional(connection)
+        .await
+    }
+
+    pub async fn get_open_loan_for_book_copy(
+        book_copy_id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<Option<LoanFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LoanFromQuery,
+            r#"select
+                loan_id,
+                book_copy_id,
+                patron_id,
+                checked_out_at,
+                due_on,
+                returned_at,
+                renewals
+            from "loan"
+            where book_copy_id = $1 and returned_at is null
+            for update"#,
+            book_copy_id
+        )
+        .fetch_optional(connection)
+        .await
+    }
+
+    /// The copy a loan is for, read without locking anything so the copy can
+    /// be locked ahead of the loan
+    pub async fn get_loan_book_copy_id(
+        id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        sqlx::query_scalar!(r#"select book_copy_id from "loan" where loan_id = $1"#, id)
+            .fetch_optional(connection)
+            .await
+    }
+
+    pub async fn lock_loan(
+        id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<Option<LoanFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LoanFromQuery,
+            r#"select
+                loan_id,
+                book_copy_id,
+                patron_id,
+                checked_out_at,
+                due_on,
+                returned_at,
+                renewals
+            from "loan" where loan_id = $1
+            for update"#,
+            id
+        )
+        .fetch_optional(connection)
+        .await
+    }
+
+    /// A patron's loans, newest first, ties broken by id so the cursor picks
+    /// up strictly after the last loan
+    pub async fn get_patron_loan_list(
+        patron_id: &Uuid,
+        include_returned: bool,
+        page_size: i64,
+        cursor: Option<&LoanCursor>,
+        connection_pool: &PgPool,
+    ) -> Result<Page<LoanFromQuery>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            LoanFromQuery,
+            r#"select
+                loan_id,
+                book_copy_id,
+                patron_id,
+                checked_out_at,
+                due_on,
+                returned_at,
+                renewals
+            from "loan"
+            where patron_id = $1
+              and ($2 or returned_at is null)
+              and ($4::timestamptz is null or (checked_out_at, loan_id) < ($4, $5))
+            order by checked_out_at desc, loan_id desc
+            limit $3"#,
+            patron_id,
+            include_returned,
+            page_size + 1,
+            cursor.map(|cursor| cursor.checked_out_at),
+            cursor.map(|cursor| cursor.id)
+        )
+        .fetch_all(connection_pool)
+        .await?;
+
+        Ok(Page::from_rows(rows, page_size))
+    }
+
+    pub async fn create_loan(
+        book_copy_id: &Uuid,
+        patron_id: &Uuid,
+        due_on: Date,
+        connection: &mut PgConnection,
+    ) -> Result<LoanFromQuery, sqlx::Error> {
+        sqlx::query_as!(
+            LoanFromQuery,
+            r#"insert into "loan" (book_copy_id, patron_id, due_on)
+               values ($1, $2, $3)
+               returning
+                loan_id,
+                book_copy_id,
+                patron_id,
+                checked_out_at,
+                due_on,
+                returned_at,
+                renewals"#,
+            book_copy_id,
+            patron_id,
+            due_on
+        )
+        .fetch_one(connection)
+        .await
+    }
+
+    pub async fn return_loan(
+        id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<LoanFromQuery, sqlx::Error> {
+        sqlx::query_as!(
+            LoanFromQuery,
+            r#"update "loan"
+               set
+                   returned_at = now(),
+                   updated_at = now()
+            where loan_id = $1
+            returning
+                loan_id,
+                book_copy_id,
+                patron_id,
+                checked_out_at,
+                due_on,
+                returned_at,
+                renewals"#,
+            id
+        )
+        .fetch_one(connection)
+        .await
+    }
+
+    pub async fn renew_loan(
+        id: &Uuid,
+        due_on: Date,
+        connection: &mut PgConnection,
+    ) -> Result<LoanFromQuery, sqlx::Error> {
+        sqlx::query_as!(
+            LoanFromQuery,
+            r#"update "loan"
+               set
+                   due_on = $2,
+                   renewals = renewals + 1,
+                   updated_at = now()
+            where loan_id = $1
+            returning
+                loan_id,
+                book_copy_id,
+                patron_id,
+                checked_out_at,
+                due_on,
+                returned_at,
+                renewals"#,
+            id,
+            due_on
+        )
+        .fetch_one(connection)
+        .await
+    }
+}
@@ -0,0 +1,192 @@
+use sqlx::postgres::PgQueryResult;
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+
+use crate::db::Db;
+use crate::routes::{
+    LoanPolicyFromQuery, LoanPolicyMatchFromQuery, LoanPolicyQuery, LoanPolicyUpdateQuery,
+};
+
+/// Queries against the `loan_policy` table
+impl Db {
+    pub async fn get_loan_policy_list(
+        connection_pool: &PgPool,
+    ) -> Result<Vec<LoanPolicyFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LoanPolicyFromQuery,
+            r#"select
+                loan_policy_id,
+                patron_category,
+                item_type,
+                location_id,
+                loan_period_days,
+                max_renewals,
+                max_items,
+                daily_fine_cents,
+                max_fine_cents,
+                fine_threshold_cents
+            from "loan_policy"
+            order by patron_category nulls first, item_type nulls first, create_at"#
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    pub async fn get_loan_policy(
+        id: &Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<Option<LoanPolicyFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LoanPolicyFromQuery,
+            r#"select
+                loan_policy_id,
+                patron_category,
+                item_type,
+                location_id,
+                loan_period_days,
+                max_renewals,
+                max_items,
+                daily_fine_cents,
+                max_fine_cents,
+                fine_threshold_cents
+            from "loan_policy" where loan_policy_id = $1"#,
+            id
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    /// Every policy that could apply to a loan with these keys.
+    ///
+    /// A policy set on a location also covers everything below it, so each
+    /// match carries how many levels up the hierarchy its location sits from
+    /// the copy's own location.
+    pub async fn get_loan_policy_matches(
+        patron_category: &str,
+        item_type: &str,
+        location_id: Option<Uuid>,
+        executor: impl PgExecutor<'_>,
+    ) -> Result<Vec<LoanPolicyMatchFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LoanPolicyMatchFromQuery,
+            r#"
+            with recursive ancestors as (
+                select location_id, parent_location_id, 0 as distance
+                from "location" where location_id = $3
+                union all
+                select l.location_id, l.parent_location_id, a.distance + 1
+                from "location" l
+                join ancestors a on l.location_id = a.parent_location_id
+            )
+            select
+                p.loan_policy_id as "loan_policy_id!",
+                p.patron_category,
+                p.item_type,
+                p.location_id,
+                p.loan_period_days as "loan_period_days!",
+                p.max_renewals as "max_renewals!",
+                p.max_items as "max_items!",
+                p.daily_fine_cents as "daily_fine_cents!",
+                p.max_fine_cents,
+                p.fine_threshold_cents,
+                a.distance as "location_distance?"
+            from "loan_policy" p
+            left join ancestors a on a.location_id = p.location_id
+            where (p.patron_category is null or p.patron_category = $1)
+              and (p.item_type is null or p.item_type = $2)
+              and (p.location_id is null or a.location_id is not null)
+            "#,
+            patron_category,
+            item_type,
+            location_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn count_open_loans(
+        patron_id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"select count(*) as "count!" from "loan" where patron_id = $1 and returned_at is null"#,
+            patron_id
+        )
+        .fetch_one(connection)
+        .await
+    }
+
+    pub async fn create_loan_policy(
+        loan_policy_query: LoanPolicyQuery,
+        connection_pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        sqlx::query_scalar!(
+            // language=PostgreSQL
+            r#"insert into "loan_policy"
+                (patron_category, item_type, location_id, loan_period_days, max_renewals, max_items,
+                 daily_fine_cents, max_fine_cents, fine_threshold_cents)
+               values ($1, $2, $3, $4, $5, $6, $7, $8, $9) returning loan_policy_id"#,
+            loan_policy_query.patron_category,
+            loan_policy_query.item_type,
+            loan_policy_query.location_id,
+            loan_policy_query.loan_period_days,
+            loan_policy_query.max_renewals,
+            loan_policy_query.max_items,
+            loan_policy_query.daily_fine_cents,
+            loan_policy_query.max_fine_cents,
+            loan_policy_query.fine_threshold_cents
+        )
+        .fetch_one(connection_pool)
+        .await
+    }
+
+    pub async fn update_loan_policy(
+        loan_policy_update_query: LoanPolicyUpdateQuery,
+        connection_pool: &PgPool,
+    ) -> Result<Option<LoanPolicyFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LoanPolicyFromQuery,
+            r#"
+            update "loan_policy"
+               set
+                   loan_period_days = coalesce($2, loan_period_days),
+                   max_renewals = coalesce($3, max_renewals),
+                   max_items = coalesce($4, max_items),
+                   daily_fine_cents = coalesce($5, daily_fine_cents),
+                   max_fine_cents = coalesce($6, max_fine_cents),
+                   fine_threshold_cents = coalesce($7, fine_threshold_cents),
+                   updated_at = now()
+            where loan_policy_id = $1
+            returning
+                loan_policy_id,
+                patron_category,
+                item_type,
+                location_id,
+                loan_period_days,
+                max_renewals,
+                max_items,
+                daily_fine_cents,
+                max_fine_cents,
+                fine_threshold_cents
+            "#,
+            loan_policy_update_query.id,
+            loan_policy_update_query.loan_period_days,
+            loan_policy_update_query.max_renewals,
+            loan_policy_update_query.max_items,
+            loan_policy_update_query.daily_fine_cents,
+            loan_policy_update_query.max_fine_cents,
+            loan_policy_update_query.fine_threshold_cents,
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    pub async fn delete_loan_policy(
+        id: Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(r#"delete from "loan_policy" where loan_policy_id = $1"#, id)
+            .execute(connection_pool)
+            .await
+    }
+}
@@ -0,0 +1,175 @@
+use sqlx::postgres::PgQueryResult;
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+
+use crate::db::Db;
+use crate::routes::{LocationFromQuery, LocationQuery, LocationUpdateQuery};
+
+/// Queries against the `location` table
+impl Db {
+    pub async fn get_location_list(
+        parent_location_id: Option<Uuid>,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<LocationFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LocationFromQuery,
+            r#"select
+                location_id,
+                parent_location_id,
+                name,
+                floor,
+                shelf_start,
+                shelf_end
+            from "location"
+            where parent_location_id is not distinct from $1
+            order by name"#,
+            parent_location_id
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    pub async fn get_location(
+        id: &Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<Option<LocationFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LocationFromQuery,
+            r#"select
+                location_id,
+                parent_location_id,
+                name,
+                floor,
+                shelf_start,
+                shelf_end
+            from "location" where location_id = $1"#,
+            id
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    /// Locks a location about to move under `parent_id`, along with
+    /// `parent_id` and everything above it, so two moves that could close a
+    /// loop between them wait on each other. The rows are locked in id order
+    /// so those moves can't deadlock.
+    pub async fn lock_location_move(
+        location_id: &Uuid,
+        parent_id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            with recursive ancestors as (
+                select location_id, parent_location_id from "location" where location_id = $2
+                union
+                select parent.location_id, parent.parent_location_id
+                from "location" parent
+                join ancestors on parent.location_id = ancestors.parent_location_id
+            )
+            select location_id from "location"
+            where location_id = $1 or location_id in (select location_id from ancestors)
+            order by location_id
+            for update
+            "#,
+            location_id,
+            parent_id
+        )
+        .fetch_all(connection)
+        .await?;
+
+        Ok(())
+    }
+
+    /// True if `candidate_id` is `location_id` itself or sits anywhere below it
+    pub async fn location_is_within(
+        candidate_id: &Uuid,
+        location_id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<bool, sqlx::Error> {
+        let within = sqlx::query_scalar!(
+            r#"
+            with recursive subtree as (
+                select location_id from "location" where location_id = $2
+                union
+                select child.location_id
+                from "location" child
+                join subtree on child.parent_location_id = subtree.location_id
+            )
+            select exists (select 1 from subtree where location_id = $1) as "within!"
+            "#,
+            candidate_id,
+            location_id
+        )
+        .fetch_one(connection)
+        .await?;
+
+        Ok(within)
+    }
+
+    pub async fn create_location(
+        location_query: LocationQuery,
+        connection_pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        sqlx::query_scalar!(
+            // language=PostgreSQL
+            r#"insert into "location" (parent_location_id, name, floor, shelf_start, shelf_end)
+               values ($1, $2, $3, $4, $5) returning location_id"#,
+            location_query.parent_location_id,
+            location_query.name,
+            location_query.floor,
+            location_query.shelf_start,
+            location_query.shelf_end
+        )
+        .fetch_one(connection_pool)
+        .await
+    }
+
+    pub async fn update_location(
+        location_update_query: LocationUpdateQuery,
+        connection: &mut PgConnection,
+    ) -> Result<Option<LocationFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            LocationFromQuery,
+            r#"
+            update "location"
+               set
+                   parent_location_id = case
+                       when $7 then null
+                       else coalesce($2, parent_location_id)
+                   end,
+                   name = coalesce($3, name),
+                   floor = coalesce($4, floor),
+                   shelf_start = coalesce($5, shelf_start),
+                   shelf_end = coalesce($6, shelf_end),
+                   updated_at = now()
+            where location_id = $1
+            returning
+                location_id,
+                parent_location_id,
+                name,
+                floor,
+                shelf_start,
+                shelf_end
+            "#,
+            location_update_query.id,
+            location_update_query.parent_location_id,
+            location_update_query.name,
+            location_update_query.floor,
+            location_update_query.shelf_start,
+            location_update_query.shelf_end,
+            location_update_query.clear_parent,
+        )
+        .fetch_optional(connection)
+        .await
+    }
+
+    pub async fn delete_location(
+        id: Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(r#"delete from "location" where location_id = $1"#, id)
+            .execute(connection_pool)
+            .await
+    }
+}
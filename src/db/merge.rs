@@ -0,0 +1,492 @@
+use sqlx::types::Uuid;
+use sqlx::{PgConnection, PgPool};
+
+use crate::db::Db;
+use crate::routes::pagination::Page;
+use crate::routes::{
+    BookFromQuery, BookMergeCursor, BookMergeFromQuery, DuplicateCursor, DuplicateFromQuery,
+};
+
+/// Queries for finding duplicate `master_book` records and merging them,
+/// along with the `master_book_merge` history
+impl Db {
+    /// A page of pairs of records that look like the same work: the same
+    /// ISBN-13 or normalized LCCN, or titles and authors at least `threshold`
+    /// similar by trigram. Identifier matches score 1, and a pair matching
+    /// several ways is listed once, for the strongest. Given a
+    /// `master_book_id`, only pairs including that record are listed.
+    ///
+    /// Pairs are listed ISBN matches first, then LCCN matches, then the rest
+    /// likeliest first, ties broken by the pair's ids. Each way of matching
+    /// is its own join, so each can use its index: the btrees on `isbn_13`
+    /// and `lccn_normalized`, and the trigram index on `title` through `%`.
+    /// Across the catalog each join picks up after the cursor and stops at a
+    /// page, rather than every pair being found again for every page.
+    pub async fn get_duplicate_list(
+        master_book_id: Option<Uuid>,
+        threshold: f32,
+        page_size: i64,
+        cursor: Option<&DuplicateCursor>,
+        connection_pool: &PgPool,
+    ) -> Result<Page<DuplicateFromQuery>, sqlx::Error> {
+        let mut transaction = connection_pool.begin().await?;
+
+        // `%` matches on this threshold, for this transaction only
+        sqlx::query!(
+            "select set_config('pg_trgm.similarity_threshold', $1::real::text, true)",
+            threshold
+        )
+        .fetch_one(&mut transaction)
+        .await?;
+
+        let rows = match master_book_id {
+            Some(master_book_id) => {
+                Self::get_duplicates_of(
+                    master_book_id,
+                    threshold,
+                    page_size,
+                    cursor,
+                    &mut transaction,
+                )
+                .await?
+            }
+            None => {
+                Self::get_all_duplicates(threshold, page_size, cursor, &mut transaction).await?
+            }
+        };
+
+        transaction.commit().await?;
+
+        Ok(Page::from_rows(rows, page_size))
+    }
+
+    /// Pairs across the whole catalog, `page_size + 1` at most. Each way of
+    /// matching is paged in turn, from the cursor's, and a later one is only
+    /// queried once the earlier ones run out. Each leaves out the pairs an
+    /// earlier one already lists.
+    async fn get_all_duplicates(
+        threshold: f32,
+        page_size: i64,
+        cursor: Option<&DuplicateCursor>,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<DuplicateFromQuery>, sqlx::Error> {
+        let reasons = ["isbn", "lccn", "title_author"];
+        let first = cursor.map_or(0, |cursor| {
+            reasons
+                .iter()
+                .position(|reason| *reason == cursor.reason)
+                .unwrap_or(reasons.len())
+        });
+
+        let mut rows = Vec::new();
+        for reason in &reasons[first..] {
+            let limit = page_size + 1 - rows.len() as i64;
+            if limit == 0 {
+                break;
+            }
+            let after = cursor.filter(|cursor| cursor.reason == *reason);
+            let pairs = match *reason {
+                "isbn" => Self::get_isbn_duplicates(limit, after, &mut *connection).await?,
+                "lccn" => Self::get_lccn_duplicates(limit, after, &mut *connection).await?,
+                _ => {
+                    Self::get_title_author_duplicates(threshold, limit, after, &mut *connection)
+                        .await?
+                }
+            };
+            rows.extend(pairs);
+        }
+
+        Ok(rows)
+    }
+
+    async fn get_isbn_duplicates(
+        limit: i64,
+        after: Option<&DuplicateCursor>,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<DuplicateFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            DuplicateFromQuery,
+            r#"
+            select
+                a.master_book_id,
+                b.master_book_id as duplicate_master_book_id,
+                'isbn' as "reason!",
+                1::real as "score!"
+            from "master_book" a
+            join "master_book" b
+                on b.isbn_13 = a.isbn_13 and a.master_book_id < b.master_book_id
+            where $2::uuid is null or (a.master_book_id, b.master_book_id) > ($2, $3)
+            order by a.master_book_id, b.master_book_id
+            limit $1
+            "#,
+            limit,
+            after.map(|cursor| cursor.master_book_id),
+            after.map(|cursor| cursor.duplicate_master_book_id)
+        )
+        .fetch_all(connection)
+        .await
+    }
+
+    async fn get_lccn_duplicates(
+        limit: i64,
+        after: Option<&DuplicateCursor>,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<DuplicateFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            DuplicateFromQuery,
+            r#"
+            select
+                a.master_book_id,
+                b.master_book_id as duplicate_master_book_id,
+                'lccn' as "reason!",
+                1::real as "score!"
+            from "master_book" a
+            join "master_book" b
+                on b.lccn_normalized = a.lccn_normalized
+                and a.master_book_id < b.master_book_id
+            where (a.isbn_13 = b.isbn_13) is not true
+              and ($2::uuid is null or (a.master_book_id, b.master_book_id) > ($2, $3))
+            order by a.master_book_id, b.master_book_id
+            limit $1
+            "#,
+            limit,
+            after.map(|cursor| cursor.master_book_id),
+            after.map(|cursor| cursor.duplicate_master_book_id)
+        )
+        .fetch_all(connection)
+        .await
+    }
+
+    async fn get_title_author_duplicates(
+        threshold: f32,
+        limit: i64,
+        after: Option<&DuplicateCursor>,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<DuplicateFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            DuplicateFromQuery,
+            r#"
+            select
+                master_book_id as "master_book_id!",
+                duplicate_master_book_id as "duplicate_master_book_id!",
+                'title_author' as "reason!",
+                score as "score!"
+            from (
+                select
+                    a.master_book_id,
+                    b.master_book_id as duplicate_master_book_id,
+                    ((
+                        similarity(a.title, b.title)
+                        + similarity(coalesce(a.author, ''), coalesce(b.author, ''))
+                    ) / 2)::real as score
+                from "master_book" a
+                join "master_book" b
+                    on b.title % a.title and a.master_book_id < b.master_book_id
+                where similarity(coalesce(a.author, ''), coalesce(b.author, '')) >= $1
+                  and (a.isbn_13 = b.isbn_13) is not true
+                  and (a.lccn_normalized = b.lccn_normalized) is not true
+            ) pairs
+            where $3::real is null
+               or score < $3
+               or (score = $3 and (master_book_id, duplicate_master_book_id) > ($4, $5))
+            order by score desc, master_book_id, duplicate_master_book_id
+            limit $2
+            "#,
+            threshold,
+            limit,
+            after.map(|cursor| cursor.score),
+            after.map(|cursor| cursor.master_book_id),
+            after.map(|cursor| cursor.duplicate_master_book_id)
+        )
+        .fetch_all(connection)
+        .await
+    }
+
+    /// Pairs including one record, `page_size + 1` at most. Each join starts
+    /// from that record alone, so there are only ever a few pairs to page.
+    async fn get_duplicates_of(
+        master_book_id: Uuid,
+        threshold: f32,
+        page_size: i64,
+        cursor: Option<&DuplicateCursor>,
+        connection: &mut PgConnection,
+    ) -> Result<Vec<DuplicateFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            DuplicateFromQuery,
+            r#"
+            select
+                master_book_id as "master_book_id!",
+                duplicate_master_book_id as "duplicate_master_book_id!",
+                reason as "reason!",
+                score as "score!"
+            from (
+                select
+                    least(m.master_book_id, o.master_book_id) as master_book_id,
+                    greatest(m.master_book_id, o.master_book_id) as duplicate_master_book_id,
+                    'isbn' as reason,
+                    1::real as score,
+                    1 as rank
+                from "master_book" m
+                join "master_book" o
+                    on o.isbn_13 = m.isbn_13 and o.master_book_id <> m.master_book_id
+                where m.master_book_id = $3
+                union all
+                select
+                    least(m.master_book_id, o.master_book_id),
+                    greatest(m.master_book_id, o.master_book_id),
+                    'lccn',
+                    1::real,
+                    2
+                from "master_book" m
+                join "master_book" o
+                    on o.lccn_normalized = m.lccn_normalized
+                    and o.master_book_id <> m.master_book_id
+                where m.master_book_id = $3
+                  and (m.isbn_13 = o.isbn_13) is not true
+                union all
+                select
+                    least(m.master_book_id, o.master_book_id),
+                    greatest(m.master_book_id, o.master_book_id),
+                    'title_author',
+                    ((
+                        similarity(m.title, o.title)
+                        + similarity(coalesce(m.author, ''), coalesce(o.author, ''))
+                    ) / 2)::real,
+                    3
+                from "master_book" m
+                join "master_book" o
+                    on o.title % m.title and o.master_book_id <> m.master_book_id
+                where m.master_book_id = $3
+                  and similarity(coalesce(m.author, ''), coalesce(o.author, '')) >= $1
+                  and (m.isbn_13 = o.isbn_13) is not true
+                  and (m.lccn_normalized = o.lccn_normalized) is not true
+            ) pairs
+            where $4::text is null
+               or rank > case $4 when 'isbn' then 1 when 'lccn' then 2 else 3 end
+               or (
+                    reason = $4
+                    and (
+                        score < $5
+                        or (score = $5 and (master_book_id, duplicate_master_book_id) > ($6, $7))
+                    )
+               )
+            order by rank, score desc, master_book_id, duplicate_master_book_id
+            limit $2
+            "#,
+            threshold,
+            page_size + 1,
+            master_book_id,
+            cursor.map(|cursor| cursor.reason.to_owned()),
+            cursor.map(|cursor| cursor.score),
+            cursor.map(|cursor| cursor.master_book_id),
+            cursor.map(|cursor| cursor.duplicate_master_book_id)
+        )
+        .fetch_all(connection)
+        .await
+    }
+
+    pub async fn get_books_by_ids(
+        ids: &[Uuid],
+        connection_pool: &PgPool,
+    ) -> Result<Vec<BookFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookFromQuery,
+            r#"select
+                master_book_id,
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate
+            from "master_book" where master_book_id = any($1)"#,
+            ids
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    pub async fn lock_book(
+        id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<Option<BookFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            BookFromQuery,
+            r#"select
+                master_book_id,
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate
+            from "master_book" where master_book_id = $1
+            for update"#,
+            id
+        )
+        .fetch_optional(connection)
+        .await
+    }
+
+    /// Moves every copy of one record onto another, returning how many moved
+    pub async fn move_book_copies(
+        from_master_book_id: &Uuid,
+        to_master_book_id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"update "book_copy"
+               set master_book_id = $2, updated_at = now()
+            where master_book_id = $1"#,
+            from_master_book_id,
+            to_master_book_id
+        )
+        .execute(connection)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Moves a hold onto another record's queue at `queue_position`
+    pub async fn move_hold(
+        id: &Uuid,
+        to_master_book_id: &Uuid,
+        queue_position: i32,
+        connection: &mut PgConnection,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"update "hold"
+               set master_book_id = $2, queue_position = $3, updated_at = now()
+            where hold_id = $1"#,
+            id,
+            to_master_book_id,
+            queue_position
+        )
+        .execute(connection)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Moves the holds on one record that are no longer active onto another,
+    /// so the patrons' histories survive the merge
+    pub async fn move_inactive_holds(
+        from_master_book_id: &Uuid,
+        to_master_book_id: &Uuid,
+        connection: &mut PgConnection,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"update "hold"
+               set master_book_id = $2, updated_at = now()
+            where master_book_id = $1 and status not in ('waiting', 'trapped')"#,
+            from_master_book_id,
+            to_master_book_id
+        )
+        .execute(connection)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records a merge, snapshotting the merged record, then removes it. The
+    /// merged record's own history moves to the record kept.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn record_book_merge(
+        kept_master_book_id: &Uuid,
+        merged_master_book_id: &Uuid,
+        copies_moved: i32,
+        holds_moved: i32,
+        holds_cancelled: i32,
+        merged_by: Option<String>,
+        connection: &mut PgConnection,
+    ) -> Result<BookMergeFromQuery, sqlx::Error> {
+        sqlx::query!(
+            r#"update "master_book_merge" set kept_master_book_id = $2
+            where kept_master_book_id = $1"#,
+            merged_master_book_id,
+            kept_master_book_id
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        let merge = sqlx::query_as!(
+            BookMergeFromQuery,
+            r#"insert into "master_book_merge"
+                (kept_master_book_id, merged_master_book_id, merged_record,
+                 copies_moved, holds_moved, holds_cancelled, merged_by)
+               select $1, m.master_book_id, to_jsonb(m) - 'search_vector', $3, $4, $5, $6
+               from "master_book" m where m.master_book_id = $2
+               returning
+                master_book_merge_id,
+                kept_master_book_id,
+                merged_master_book_id,
+                merged_record,
+                copies_moved,
+                holds_moved,
+                holds_cancelled,
+                merged_by,
+                merged_at"#,
+            kept_master_book_id,
+            merged_master_book_id,
+            copies_moved,
+            holds_moved,
+            holds_cancelled,
+            merged_by
+        )
+        .fetch_one(&mut *connection)
+        .await?;
+
+        sqlx::query!(
+            r#"delete from "master_book" where master_book_id = $1"#,
+            merged_master_book_id
+        )
+        .execute(connection)
+        .await?;
+
+        Ok(merge)
+    }
+
+    /// A page of the merges folded into a record, oldest first, ties broken
+    /// by id so the cursor picks up strictly after the last merge
+    pub async fn get_book_merge_list(
+        master_book_id: &Uuid,
+        page_size: i64,
+        cursor: Option<&BookMergeCursor>,
+        connection_pool: &PgPool,
+    ) -> Result<Page<BookMergeFromQuery>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            BookMergeFromQuery,
+            r#"select
+                master_book_merge_id,
+                kept_master_book_id,
+                merged_master_book_id,
+                merged_record,
+                copies_moved,
+                holds_moved,
+                holds_cancelled,
+                merged_by,
+                merged_at
+            from "master_book_merge"
+            where kept_master_book_id = $1
+              and ($3::timestamptz is null or (merged_at, master_book_merge_id) > ($3, $4))
+            order by merged_at, master_book_merge_id
+            limit $2"#,
+            master_book_id,
+            page_size + 1,
+            cursor.map(|cursor| cursor.merged_at),
+            cursor.map(|cursor| cursor.id)
+        )
+        .fetch_all(connection_pool)
+        .await?;
+
+        Ok(Page::from_rows(rows, page_size))
+    }
+}
@@ -0,0 +1,17 @@
+mod book;
+mod calendar;
+mod copy;
+mod hold;
+mod ledger;
+mod loan;
+mod loan_policy;
+mod location;
+mod merge;
+mod oai;
+mod patron;
+mod sru;
+
+/// A catch all Database Structure to encapsulate our Queries
+/// Queries are split by table into the modules of this folder, each adding
+/// its own `impl Db` block
+pub struct Db;
@@ -0,0 +1,190 @@
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::db::Db;
+use crate::routes::OaiBookFromQuery;
+
+/// Queries for harvesting `master_book` over OAI-PMH. A book's datestamp is
+/// when it was last updated, or created if it never has been. A book merged
+/// into another is a deleted record, datestamped when it was merged.
+impl Db {
+    /// Up to `limit` records with datestamps from `from` up to but not
+    /// including `before`, in datestamp order, continuing after `after`.
+    /// Books and merged books are each read in that order and limit before
+    /// they are put together.
+    pub async fn get_oai_book_list(
+        from: Option<OffsetDateTime>,
+        before: Option<OffsetDateTime>,
+        after: Option<(OffsetDateTime, Uuid)>,
+        limit: i64,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<OaiBookFromQuery>, sqlx::Error> {
+        let (after_datestamp, after_id) = after.unzip();
+
+        sqlx::query_as!(
+            OaiBookFromQuery,
+            r#"
+            select
+                master_book_id as "master_book_id!",
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate as "published_approximate!",
+                datestamp as "datestamp!",
+                deleted as "deleted!"
+            from (
+                (
+                    select
+                        master_book_id,
+                        author,
+                        title,
+                        lccn,
+                        lccn_normalized,
+                        isbn,
+                        isbn_13,
+                        publish_date,
+                        published_on,
+                        published_precision,
+                        published_approximate,
+                        coalesce(updated_at, create_at) as datestamp,
+                        false as deleted
+                    from "master_book"
+                    where ($1::timestamptz is null or coalesce(updated_at, create_at) >= $1)
+                      and ($2::timestamptz is null or coalesce(updated_at, create_at) < $2)
+                      and (
+                          $3::timestamptz is null
+                          or (coalesce(updated_at, create_at), master_book_id) > ($3, $4::uuid)
+                      )
+                    order by coalesce(updated_at, create_at), master_book_id
+                    limit $5
+                )
+                union all
+                (
+                    select
+                        merged_master_book_id,
+                        null,
+                        null,
+                        null,
+                        null,
+                        null,
+                        null,
+                        null,
+                        null,
+                        null,
+                        false,
+                        merged_at,
+                        true
+                    from "master_book_merge"
+                    where ($1::timestamptz is null or merged_at >= $1)
+                      and ($2::timestamptz is null or merged_at < $2)
+                      and (
+                          $3::timestamptz is null
+                          or (merged_at, merged_master_book_id) > ($3, $4::uuid)
+                      )
+                    order by merged_at, merged_master_book_id
+                    limit $5
+                )
+            ) records
+            order by datestamp, master_book_id
+            limit $5
+            "#,
+            from,
+            before,
+            after_datestamp,
+            after_id,
+            limit
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    /// A single book with its datestamp, or the deleted record left by
+    /// merging it away
+    pub async fn get_oai_book(
+        id: Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<Option<OaiBookFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            OaiBookFromQuery,
+            r#"
+            select
+                master_book_id as "master_book_id!",
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate as "published_approximate!",
+                coalesce(updated_at, create_at) as "datestamp!",
+                false as "deleted!"
+            from "master_book"
+            where master_book_id = $1
+            union all
+            select
+                merged_master_book_id,
+                null,
+                null,
+                null,
+                null,
+                null,
+                null,
+                null,
+                null,
+                null,
+                false,
+                merged_at,
+                true
+            from "master_book_merge"
+            where merged_master_book_id = $1
+            "#,
+            id
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    /// The datestamps of the books with these ids
+    pub async fn get_book_datestamps(
+        ids: &[Uuid],
+        connection_pool: &PgPool,
+    ) -> Result<Vec<(Uuid, OffsetDateTime)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"select master_book_id, coalesce(updated_at, create_at) as "datestamp!"
+            from "master_book" where master_book_id = any($1)"#,
+            ids
+        )
+        .fetch_all(connection_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.master_book_id, row.datestamp))
+            .collect())
+    }
+
+    /// The earliest datestamp in the catalog, if it has any books or merged
+    /// books
+    pub async fn get_oai_earliest_datestamp(
+        connection_pool: &PgPool,
+    ) -> Result<Option<OffsetDateTime>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"select least(
+                (select min(coalesce(updated_at, create_at)) from "master_book"),
+                (select min(merged_at) from "master_book_merge")
+            )"#
+        )
+        .fetch_one(connection_pool)
+        .await
+    }
+}
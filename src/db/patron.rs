@@ -0,0 +1,172 @@
+use sqlx::postgres::PgQueryResult;
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::db::Db;
+use crate::routes::pagination::Page;
+use crate::routes::{
+    PatronCategory, PatronCursor, PatronFromQuery, PatronQuery, PatronUpdateQuery,
+};
+
+/// Queries against the `patron` and `patron_category` tables
+impl Db {
+    pub async fn get_patron_categories(
+        connection_pool: &PgPool,
+    ) -> Result<Vec<PatronCategory>, sqlx::Error> {
+        sqlx::query_as!(
+            PatronCategory,
+            r#"select patron_category as category, description
+            from "patron_category" order by patron_category"#
+        )
+        .fetch_all(connection_pool)
+        .await
+    }
+
+    /// Patrons by name, ties broken by id so the cursor picks up strictly
+    /// after the last patron
+    pub async fn get_patron_list(
+        patron_category: Option<String>,
+        page_size: i64,
+        cursor: Option<&PatronCursor>,
+        connection_pool: &PgPool,
+    ) -> Result<Page<PatronFromQuery>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            PatronFromQuery,
+            r#"select
+                patron_id,
+                card_number,
+                first_name,
+                last_name,
+                email,
+                phone,
+                address,
+                patron_category,
+                expires_on,
+                blocked,
+                blocked_reason
+            from "patron"
+            where ($1::text is null or patron_category = $1)
+              and ($3::text is null or (last_name, first_name, patron_id) > ($3, $4, $5))
+            order by last_name, first_name, patron_id
+            limit $2"#,
+            patron_category,
+            page_size + 1,
+            cursor.map(|cursor| cursor.last_name.as_str()),
+            cursor.map(|cursor| cursor.first_name.as_str()),
+            cursor.map(|cursor| cursor.id)
+        )
+        .fetch_all(connection_pool)
+        .await?;
+
+        Ok(Page::from_rows(rows, page_size))
+    }
+
+    pub async fn get_patron(
+        id: &Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<Option<PatronFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            PatronFromQuery,
+            r#"select
+                patron_id,
+                card_number,
+                first_name,
+                last_name,
+                email,
+                phone,
+                address,
+                patron_category,
+                expires_on,
+                blocked,
+                blocked_reason
+            from "patron" where patron_id = $1"#,
+            id
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    pub async fn create_patron(
+        patron_query: PatronQuery,
+        connection_pool: &PgPool,
+    ) -> Result<Uuid, sqlx::Error> {
+        sqlx::query_scalar!(
+            // language=PostgreSQL
+            r#"insert into "patron"
+                (card_number, first_name, last_name, email, phone, address, patron_category, expires_on)
+               values ($1, $2, $3, $4, $5, $6, $7, $8) returning patron_id"#,
+            patron_query.card_number,
+            patron_query.first_name,
+            patron_query.last_name,
+            patron_query.email,
+            patron_query.phone,
+            patron_query.address,
+            patron_query.category,
+            patron_query.expires_on
+        )
+        .fetch_one(connection_pool)
+        .await
+    }
+
+    pub async fn update_patron(
+        patron_update_query: PatronUpdateQuery,
+        connection_pool: &PgPool,
+    ) -> Result<Option<PatronFromQuery>, sqlx::Error> {
+        sqlx::query_as!(
+            PatronFromQuery,
+            r#"
+            update "patron"
+               set
+                   card_number = coalesce($2, card_number),
+                   first_name = coalesce($3, first_name),
+                   last_name = coalesce($4, last_name),
+                   email = coalesce($5, email),
+                   phone = coalesce($6, phone),
+                   address = coalesce($7, address),
+                   patron_category = coalesce($8, patron_category),
+                   expires_on = coalesce($9, expires_on),
+                   blocked = coalesce($10, blocked),
+                   blocked_reason = case
+                       when coalesce($10, blocked) then coalesce($11, blocked_reason)
+                       else null
+                   end,
+                   updated_at = now()
+            where patron_id = $1
+            returning
+                patron_id,
+                card_number,
+                first_name,
+                last_name,
+                email,
+                phone,
+                address,
+                patron_category,
+                expires_on,
+                blocked,
+                blocked_reason
+            "#,
+            patron_update_query.id,
+            patron_update_query.card_number,
+            patron_update_query.first_name,
+            patron_update_query.last_name,
+            patron_update_query.email,
+            patron_update_query.phone,
+            patron_update_query.address,
+            patron_update_query.category,
+            patron_update_query.expires_on,
+            patron_update_query.blocked,
+            patron_update_query.blocked_reason,
+        )
+        .fetch_optional(connection_pool)
+        .await
+    }
+
+    pub async fn delete_patron(
+        id: Uuid,
+        connection_pool: &PgPool,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(r#"delete from "patron" where patron_id = $1"#, id)
+            .execute(connection_pool)
+            .await
+    }
+}
@@ -0,0 +1,112 @@
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+use crate::catalog::sru::BookFilter;
+use crate::db::Db;
+use crate::routes::BookFromQuery;
+
+/// Queries for books matching a CQL query, as checked into a `BookFilter`
+impl Db {
+    /// Up to `limit` matching books in title order, skipping the first
+    /// `offset`
+    pub async fn get_book_filter_list(
+        filter: &BookFilter,
+        offset: i64,
+        limit: i64,
+        connection_pool: &PgPool,
+    ) -> Result<Vec<BookFromQuery>, sqlx::Error> {
+        let mut builder = QueryBuilder::<Postgres>::new(
+            r#"select
+                master_book_id,
+                author,
+                title,
+                lccn,
+                lccn_normalized,
+                isbn,
+                isbn_13,
+                publish_date,
+                published_on,
+                published_precision,
+                published_approximate
+            from "master_book" where "#,
+        );
+        push_filter(&mut builder, filter);
+        builder
+            .push(" order by title asc nulls last, master_book_id limit ")
+            .push_bind(limit)
+            .push(" offset ")
+            .push_bind(offset);
+
+        builder
+            .build_query_as::<BookFromQuery>()
+            .fetch_all(connection_pool)
+            .await
+    }
+
+    /// How many books match
+    pub async fn get_book_filter_count(
+        filter: &BookFilter,
+        connection_pool: &PgPool,
+    ) -> Result<i64, sqlx::Error> {
+        let mut builder =
+            QueryBuilder::<Postgres>::new(r#"select count(*) from "master_book" where "#);
+        push_filter(&mut builder, filter);
+
+        let (count,) = builder
+            .build_query_as::<(i64,)>()
+            .fetch_one(connection_pool)
+            .await?;
+
+        Ok(count)
+    }
+}
+
+/// Writes a filter as a condition. Columns are compared through `coalesce`
+/// so a missing value fails to match rather than making the whole condition
+/// null, which `not` would carry through.
+fn push_filter(builder: &mut QueryBuilder<'_, Postgres>, filter: &BookFilter) {
+    match filter {
+        BookFilter::All => {
+            builder.push("true");
+        }
+        BookFilter::Nothing => {
+            builder.push("false");
+        }
+        BookFilter::And(left, right) => push_joined(builder, left, " and ", right),
+        BookFilter::Or(left, right) => push_joined(builder, left, " or ", right),
+        BookFilter::AndNot(left, right) => push_joined(builder, left, " and not ", right),
+        BookFilter::Like {
+            columns,
+            pattern,
+            negated,
+        } => {
+            builder.push(if *negated { "not (" } else { "(" });
+            for (position, column) in columns.iter().enumerate() {
+                if position > 0 {
+                    builder.push(" or ");
+                }
+                builder
+                    .push(format_args!("coalesce({column}, '') ilike "))
+                    .push_bind(pattern.clone());
+            }
+            builder.push(")");
+        }
+        BookFilter::Isbn(isbn_13) => {
+            builder
+                .push("coalesce(isbn_13, '') = ")
+                .push_bind(isbn_13.clone());
+        }
+    }
+}
+
+fn push_joined(
+    builder: &mut QueryBuilder<'_, Postgres>,
+    left: &BookFilter,
+    joiner: &str,
+    right: &BookFilter,
+) {
+    builder.push("(");
+    push_filter(builder, left);
+    builder.push(joiner);
+    push_filter(builder, right);
+    builder.push(")");
+}
@@ -1,2 +1,4 @@
+pub mod catalog;
+pub mod circulation;
 pub mod db;
 pub mod routes;
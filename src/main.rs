@@ -1,39 +1,124 @@
 use axum::Server;
 use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
 use tokio::signal;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use library_api_rir::catalog::backfill::Backfills;
+use library_api_rir::catalog::import::Imports;
 use library_api_rir::routes::app;
 
-/// Spins up our app, sets debug level, gets the database started and binds
-/// our server.
+const USAGE: &str = "usage: library-api-rir [import-marc <file>... | backfill]";
+
+/// With no arguments, serves the API. `import-marc` instead imports the
+/// books in each MARC file named, printing a report for each. `backfill`
+/// fills in what is read out of each book for books written before it was.
 #[tokio::main]
 async fn main() {
-    println!("Server Spinning Up");
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    for (n,v) in std::env::vars() {
-        println!("{}: {}", n,v);
+    match args.first().map(String::as_str) {
+        None => serve().await,
+        Some("import-marc") if args.len() > 1 => import_marc(&args[1..]).await,
+        Some("backfill") if args.len() == 1 => backfill().await,
+        Some(_) => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Imports each MARC file named, exiting with failure if any record could
+/// not be imported
+async fn import_marc(paths: &[String]) {
+    let db = connect(&database_url()).await;
+    let mut failed = false;
+
+    for path in paths {
+        let file = std::fs::read(path).unwrap_or_else(|error| {
+            eprintln!("{path}: {error}");
+            std::process::exit(1);
+        });
+        match Imports::marc(&file, &db).await {
+            Ok(report) => {
+                failed |= report.failed > 0;
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&report).expect("reports always serialize")
+                );
+            }
+            Err(error) => {
+                eprintln!("{path}: {error}");
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
     }
+}
+
+/// Fills in what is read out of each book for books written before it was,
+/// exiting with failure if the database gives out part way
+async fn backfill() {
+    init_tracing("library_api_rir=info");
+    let db = connect(&database_url()).await;
 
+    match Backfills::run(&db).await {
+        Ok(backfilled) => tracing::info!(
+            "backfilled {} books, {} left as typed as they can't be read",
+            backfilled.filled,
+            backfilled.unreadable
+        ),
+        Err(error) => {
+            tracing::error!("backfill stopped: {error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Logs through `tracing`, filtered by `RUST_LOG` or else by `default`
+fn init_tracing(default: &str) {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "library_app_rir=debug,tower_http=debug".into()),
+                .unwrap_or_else(|_| default.into()),
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
+}
 
-    let database_url = std::env::var("DATABASE_URL")
+fn database_url() -> String {
+    std::env::var("DATABASE_URL")
         .unwrap()
-        .to_string();
-
-    println!("db_url: {:?}", &database_url);
+        .to_string()
+}
 
-    let db = PgPoolOptions::new()
+async fn connect(database_url: &str) -> PgPool {
+    PgPoolOptions::new()
         .max_connections(5)
-        .connect(&database_url)
+        .connect(database_url)
         .await
-        .expect("couldnt connect to database url");
+        .expect("couldnt connect to database url")
+}
+
+/// Spins up our app, sets debug level, gets the database started and binds
+/// our server.
+async fn serve() {
+    println!("Server Spinning Up");
+
+    for (n,v) in std::env::vars() {
+        println!("{}: {}", n,v);
+    }
+
+    init_tracing("library_app_rir=debug,tower_http=debug");
+
+    let database_url = database_url();
+
+    println!("db_url: {:?}", &database_url);
+
+    let db = connect(&database_url).await;
 
     let addr = std::net::SocketAddr::from(([127, 0, 0, 1], 8081));
     Server::bind(&addr)
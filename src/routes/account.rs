@@ -0,0 +1,188 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+use time::OffsetDateTime;
+
+use crate::circulation::fines::Fines;
+use crate::db::Db;
+use crate::routes::{ApiContext, Error};
+
+/// Used to namespace our JSON query
+/// { "account": <T> }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct AccountBody<T> {
+    pub account: T,
+}
+
+/// Used to namespace our JSON query
+/// { "entry": <T> }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct LedgerEntryBody<T> {
+    pub entry: T,
+}
+
+/// A patron's account: every ledger entry in the order it was made, and the
+/// running balance they leave. A positive balance is money owed.
+#[derive(serde::Serialize)]
+pub struct Account {
+    pub patron_id: Uuid,
+    pub balance_cents: i64,
+    pub entries: Vec<LedgerEntry>,
+}
+
+/// The base return structure for a ledger entry to the client. Charges are
+/// positive and payments and waivers negative.
+#[derive(serde::Serialize)]
+pub struct LedgerEntry {
+    pub id: Uuid,
+    pub patron_id: Uuid,
+    pub loan_id: Option<Uuid>,
+    pub reference_id: Option<Uuid>,
+    pub kind: String,
+    pub amount_cents: i64,
+    pub note: Option<String>,
+    pub created_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// Account Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct AccountQuery {
+    pub patron_id: Uuid,
+}
+
+/// Charge Query coming from Client, for a `lost`, `damaged` or `manual` fee
+#[derive(serde::Deserialize)]
+pub struct ChargeQuery {
+    pub patron_id: Uuid,
+    pub loan_id: Option<Uuid>,
+    pub kind: String,
+    pub amount_cents: i64,
+    pub note: Option<String>,
+    pub created_by: Option<String>,
+}
+
+/// Payment or Waiver Query coming from Client. The amount is sent positive
+/// and may name the charge it settles.
+#[derive(serde::Deserialize)]
+pub struct CreditQuery {
+    pub patron_id: Uuid,
+    pub amount_cents: i64,
+    pub reference_id: Option<Uuid>,
+    pub note: Option<String>,
+    pub created_by: Option<String>,
+}
+
+/// Array of ledger entries to the client
+#[derive(serde::Serialize, Default)]
+pub struct LedgerEntriesQuery {
+    pub entries: Vec<LedgerEntry>,
+}
+
+/// Database Object to be cast into a LedgerEntry
+#[derive(Clone)]
+pub struct LedgerEntryFromQuery {
+    pub ledger_entry_id: Uuid,
+    pub patron_id: Uuid,
+    pub loan_id: Option<Uuid>,
+    pub reference_entry_id: Option<Uuid>,
+    pub kind: String,
+    pub amount_cents: i64,
+    pub note: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+impl LedgerEntryFromQuery {
+    fn to_ledger_entry(&self) -> LedgerEntry {
+        let this = self.to_owned();
+        LedgerEntry {
+            id: this.ledger_entry_id,
+            patron_id: this.patron_id,
+            loan_id: this.loan_id,
+            reference_id: this.reference_entry_id,
+            kind: this.kind,
+            amount_cents: this.amount_cents,
+            note: this.note,
+            created_by: this.created_by,
+            created_at: this.created_at,
+        }
+    }
+}
+
+/// Gets a patron's ledger and balance
+pub async fn get_account(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<AccountBody<AccountQuery>>,
+) -> Result<(StatusCode, Json<Account>), Error> {
+    let connection_pool = &api_context.db;
+    let patron_id = request.account.patron_id;
+
+    Db::get_patron(&patron_id, connection_pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let list = Db::get_ledger_entry_list(&patron_id, connection_pool).await?;
+    let balance_cents = list.iter().map(|entry| entry.amount_cents).sum();
+    let entries = list.iter().map(|entry| entry.to_ledger_entry()).collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(Account {
+            patron_id,
+            balance_cents,
+            entries,
+        }),
+    ))
+}
+
+/// Charges a patron a fee
+pub async fn create_charge(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LedgerEntryBody<ChargeQuery>>,
+) -> Result<(StatusCode, Json<LedgerEntry>), Error> {
+    let connection_pool = &api_context.db;
+
+    let entry = Fines::charge(request.entry, connection_pool).await?;
+
+    Ok((StatusCode::OK, Json(entry.to_ledger_entry())))
+}
+
+/// Records a payment against a patron's balance
+pub async fn create_payment(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LedgerEntryBody<CreditQuery>>,
+) -> Result<(StatusCode, Json<LedgerEntry>), Error> {
+    let connection_pool = &api_context.db;
+
+    let entry = Fines::credit("payment", request.entry, connection_pool).await?;
+
+    Ok((StatusCode::OK, Json(entry.to_ledger_entry())))
+}
+
+/// Waives part or all of a patron's balance
+pub async fn create_waiver(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LedgerEntryBody<CreditQuery>>,
+) -> Result<(StatusCode, Json<LedgerEntry>), Error> {
+    let connection_pool = &api_context.db;
+
+    let entry = Fines::credit("waiver", request.entry, connection_pool).await?;
+
+    Ok((StatusCode::OK, Json(entry.to_ledger_entry())))
+}
+
+/// Brings the overdue fines on every loan still out up to date, returning
+/// the entries it made. Safe to run as often as you like.
+pub async fn accrue_fines(
+    State(api_context): State<ApiContext>,
+) -> Result<(StatusCode, Json<LedgerEntriesQuery>), Error> {
+    let connection_pool = &api_context.db;
+
+    let list = Fines::accrue(connection_pool).await?;
+    let entries = list.iter().map(|entry| entry.to_ledger_entry()).collect();
+
+    Ok((StatusCode::OK, Json(LedgerEntriesQuery { entries })))
+}
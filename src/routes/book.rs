@@ -1,10 +1,27 @@
-use axum::extract::State;
-use axum::http::StatusCode;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
 use sqlx::types::Uuid;
+use std::collections::HashMap;
+use time::Date;
 
+use crate::catalog::enrichment::ENRICH_ON_CREATE_TIMEOUT;
+use crate::catalog::publish_date::{self, DatePrecision};
+use crate::catalog::{isbn, lccn};
+use crate::catalog::search::{
+    highlight_html, prefix_query, DEFAULT_SEARCH_LIMIT, MAX_SEARCH_LIMIT,
+};
 use crate::db::Db;
-use crate::routes::{ApiError, ApiContext};
+use crate::routes::citation::{book_citation, book_citations};
+use crate::routes::enrichment::enrich;
+use crate::routes::export::{accepted_media_types, book_record};
+use crate::routes::linked_data::book_linked_data;
+use crate::routes::pagination::decode_cursor;
+use crate::routes::{
+    ApiError, ApiContext, BookHolding, CitationFormat, CitationQuery, Error, ExportFormat,
+    LinkedDataFormat, ResultExt,
+};
 
 /// Used to namespace our JSON query
 /// { "book": <T> }
@@ -13,7 +30,9 @@ pub struct BookBody<T = BookQuery> {
     pub book: T,
 }
 
-/// The base return structure for our book object to the client
+/// The base return structure for our book object to the client. `isbn` and
+/// `lccn` are shown as they were entered, alongside the bare ISBN-13 and the
+/// normalized LCCN. `published` is what could be read out of `publish_date`.
 #[derive(serde::Serialize, Default)]
 pub struct Book {
     pub id: Uuid,
@@ -22,8 +41,22 @@ pub struct Book {
     pub author: Option<String>,
     pub title: Option<String>,
     pub lccn: Option<String>,
+    pub lccn_normalized: Option<String>,
     pub isbn: Option<String>,
+    pub isbn_13: Option<String>,
     pub publish_date: Option<String>,
+    pub published: Option<Published>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copies: Option<Vec<BookHolding>>,
+}
+
+/// A structured publication date: the first day of the period it covers and
+/// how precisely it is known
+#[derive(serde::Serialize)]
+pub struct Published {
+    pub on: Date,
+    pub precision: DatePrecision,
+    pub approximate: bool,
 }
 
 /// Query coming from Client
@@ -36,10 +69,21 @@ pub struct BookQuery {
     pub publish_date: String,
 }
 
-/// Array Query coming from Client
+/// Array Query coming from Client, one page at a time. `next_cursor` fetches
+/// the following page and is `null` on the last one.
 #[derive(serde::Serialize, Default)]
 pub struct BooksQuery {
     pub books: Vec<Book>,
+    pub next_cursor: Option<String>,
+}
+
+/// Create Book Query coming from Client as query parameters. `enrich=true`
+/// fills in what the book is missing from the metadata source once it is
+/// created, if the source answers within `ENRICH_ON_CREATE_TIMEOUT`.
+#[derive(serde::Deserialize, Default)]
+pub struct CreateBookQuery {
+    #[serde(default)]
+    pub enrich: bool,
 }
 
 /// Update Query coming from Client
@@ -65,20 +109,217 @@ pub struct DeleteBook {
    pub id: Uuid,
 }
 
+/// A field the book list can be sorted on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookSortField {
+    #[default]
+    Title,
+    Author,
+    Isbn,
+    Lccn,
+    PublishDate,
+}
+
+impl BookSortField {
+    fn parse(field: &str) -> Option<Self> {
+        match field {
+            "title" => Some(Self::Title),
+            "author" => Some(Self::Author),
+            "isbn" => Some(Self::Isbn),
+            "lccn" => Some(Self::Lccn),
+            "publish_date" => Some(Self::PublishDate),
+            _ => None,
+        }
+    }
+
+    /// The column to order by. Only ever one of these fixed names reaches
+    /// the SQL.
+    pub fn column(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Author => "author",
+            Self::Isbn => "isbn",
+            Self::Lccn => "lccn",
+            Self::PublishDate => "published_on",
+        }
+    }
+
+    /// The type cursor values are cast to when compared with the column
+    pub fn column_type(self) -> &'static str {
+        match self {
+            Self::PublishDate => "date",
+            _ => "text",
+        }
+    }
+
+    /// The value a book has in this field
+    pub fn value_of(self, book: &BookFromQuery) -> Option<String> {
+        match self {
+            Self::Title => book.title.clone(),
+            Self::Author => book.author.clone(),
+            Self::Isbn => book.isbn.clone(),
+            Self::Lccn => book.lccn.clone(),
+            Self::PublishDate => book.published_on.map(|on| on.to_string()),
+        }
+    }
+}
+
+/// Which way a sort runs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// List Books Query coming from Client as query parameters.
+///
+/// `author` and `title` match anywhere in the field ignoring case, `isbn`
+/// and `lccn` match however the number was typed, and `publish_date_from` and
+/// `publish_date_to` bound the publication date inclusively, taking any form
+/// a cataloger might write it in. `sort` names a field and `order` is `asc`
+/// or `desc`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ListBooksQuery {
+    pub author: Option<String>,
+    pub title: Option<String>,
+    pub isbn: Option<String>,
+    pub lccn: Option<String>,
+    pub publish_date_from: Option<Date>,
+    pub publish_date_to: Option<Date>,
+    pub sort: BookSortField,
+    pub order: SortOrder,
+    pub page_size: Option<i64>,
+    pub cursor: Option<BookCursor>,
+}
+
+/// Where a page of the book list ended: the last book's id and its value in
+/// the sort field, along with the sort itself so a cursor can't be replayed
+/// against a different order
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BookCursor {
+    pub sort: BookSortField,
+    pub order: SortOrder,
+    pub value: Option<String>,
+    pub id: Uuid,
+}
+
+impl ListBooksQuery {
+    /// Reads the query parameters, refusing any it doesn't know
+    pub fn from_params(params: HashMap<String, String>) -> Result<Self, Error> {
+        let mut query = Self::default();
+        let mut errors = Vec::new();
+
+        for (key, value) in params {
+            match key.as_str() {
+                "author" => query.author = Some(value),
+                "title" => query.title = Some(value),
+                "isbn" => match isbn::normalize(&value) {
+                    Ok(isbn_13) => query.isbn = Some(isbn_13),
+                    Err(error) => errors.push((key, error.to_string())),
+                },
+                "lccn" => match lccn::normalize(&value) {
+                    Ok(lccn) => query.lccn = Some(lccn),
+                    Err(error) => errors.push((key, error.to_string())),
+                },
+                "publish_date_from" => match publish_date::parse(&value) {
+                    Some(from) => query.publish_date_from = Some(from.on),
+                    None => errors.push((key, "is not a date we can read".to_string())),
+                },
+                "publish_date_to" => match publish_date::parse(&value) {
+                    Some(to) => query.publish_date_to = Some(to.last_day()),
+                    None => errors.push((key, "is not a date we can read".to_string())),
+                },
+                "sort" => match BookSortField::parse(&value) {
+                    Some(sort) => query.sort = sort,
+                    None => errors.push((
+                        key,
+                        "must be one of title, author, isbn, lccn or publish_date".to_string(),
+                    )),
+                },
+                "order" => match value.as_str() {
+                    "asc" => query.order = SortOrder::Asc,
+                    "desc" => query.order = SortOrder::Desc,
+                    _ => errors.push((key, "must be asc or desc".to_string())),
+                },
+                "page_size" => match value.parse() {
+                    Ok(page_size) => query.page_size = Some(page_size),
+                    Err(_) => errors.push((key, "must be a number".to_string())),
+                },
+                "cursor" => query.cursor = Some(decode_cursor(&value)?),
+                _ => errors.push((key, "is not a field books can be filtered on".to_string())),
+            }
+        }
+
+        if let Some(cursor) = &query.cursor {
+            if cursor.sort != query.sort || cursor.order != query.order {
+                errors.push((
+                    "cursor".to_string(),
+                    "was made for a different sort".to_string(),
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(query)
+        } else {
+            Err(Error::unprocessable_entity(errors))
+        }
+    }
+}
+
+/// Search Query coming from Client as `?q=`, with `?format=` asking for the
+/// results as citations
+#[derive(serde::Deserialize)]
+pub struct SearchBooksQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+    pub format: Option<CitationFormat>,
+}
+
+/// A search hit: the book, how well it matched, and its title and author as
+/// HTML, escaped, with the matching words wrapped in `<mark>`
+#[derive(serde::Serialize)]
+pub struct BookSearchResult {
+    pub book: Book,
+    pub rank: f32,
+    pub highlights: BookHighlights,
+}
+
+/// Title and author with matches marked up
+#[derive(serde::Serialize)]
+pub struct BookHighlights {
+    pub title: String,
+    pub author: String,
+}
+
+/// Array of search hits to the client, best match first
+#[derive(serde::Serialize, Default)]
+pub struct BookSearchResultsQuery {
+    pub results: Vec<BookSearchResult>,
+}
+
 /// Database Object to be cast into a Book
-#[derive(Clone)]
+#[derive(Clone, sqlx::FromRow)]
 pub struct BookFromQuery {
     pub master_book_id: Uuid,
     pub author: Option<String>,
     pub title: Option<String>,
     pub lccn: Option<String>,
+    pub lccn_normalized: Option<String>,
     pub isbn: Option<String>,
+    pub isbn_13: Option<String>,
     pub publish_date: Option<String>,
+    pub published_on: Option<Date>,
+    pub published_precision: Option<String>,
+    pub published_approximate: bool,
 }
 
 /// Casts the BookFromQuery to Book. Maybe implement Into Trait here
 impl BookFromQuery {
-    fn to_book(&self) -> Book {
+    pub(crate) fn to_book(&self) -> Book {
         let this = self.to_owned();
         Book {
             id: self.master_book_id,
@@ -87,25 +328,202 @@ impl BookFromQuery {
             author: this.author,
             title: this.title,
             lccn: this.lccn,
+            lccn_normalized: this.lccn_normalized,
             isbn: this.isbn,
+            isbn_13: this.isbn_13,
             publish_date: this.publish_date,
+            published: match (this.published_on, this.published_precision) {
+                (Some(on), Some(precision)) => {
+                    DatePrecision::parse(&precision).map(|precision| Published {
+                        on,
+                        precision,
+                        approximate: this.published_approximate,
+                    })
+                }
+                _ => None,
+            },
+            copies: None,
+        }
+    }
+}
+
+/// Database Object to be cast into a BookSearchResult
+#[derive(Clone)]
+pub struct BookSearchFromQuery {
+    pub master_book_id: Uuid,
+    pub author: Option<String>,
+    pub title: Option<String>,
+    pub lccn: Option<String>,
+    pub lccn_normalized: Option<String>,
+    pub isbn: Option<String>,
+    pub isbn_13: Option<String>,
+    pub publish_date: Option<String>,
+    pub published_on: Option<Date>,
+    pub published_precision: Option<String>,
+    pub published_approximate: bool,
+    pub rank: f32,
+    pub title_highlight: String,
+    pub author_highlight: String,
+}
+
+impl BookSearchFromQuery {
+    pub(crate) fn to_book_search_result(&self) -> BookSearchResult {
+        let this = self.to_owned();
+        BookSearchResult {
+            book: BookFromQuery {
+                master_book_id: this.master_book_id,
+                author: this.author,
+                title: this.title,
+                lccn: this.lccn,
+                lccn_normalized: this.lccn_normalized,
+                isbn: this.isbn,
+                isbn_13: this.isbn_13,
+                publish_date: this.publish_date,
+                published_on: this.published_on,
+                published_precision: this.published_precision,
+                published_approximate: this.published_approximate,
+            }
+            .to_book(),
+            rank: this.rank,
+            highlights: BookHighlights {
+                title: highlight_html(&this.title_highlight),
+                author: highlight_html(&this.author_highlight),
+            },
         }
     }
 }
 
-/// Gets a list of books 
+/// Gets a page of books, filtered and sorted by the query parameters
 pub async fn get_list_books(
     State(api_context): State<ApiContext>,
-) -> Result<(StatusCode, Json<BooksQuery>), ApiError> {
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<(StatusCode, Json<BooksQuery>), Error> {
+    let connection_pool = &api_context.db;
+    let query = ListBooksQuery::from_params(params)?;
+
+    let page = Db::get_book_list(&query, connection_pool).await?;
+    let next_cursor = page.next_cursor(|book| BookCursor {
+        sort: query.sort,
+        order: query.order,
+        value: query.sort.value_of(book),
+        id: book.master_book_id,
+    });
+    let books = page.rows.iter().map(|book| book.to_book()).collect();
+
+    Ok((StatusCode::OK, Json(BooksQuery { books, next_cursor })))
+}
+
+/// Searches titles and authors, matching each word typed as a prefix. Asked
+/// for a citation format by `?format=` or `Accept`, the books found come back
+/// cited in it, best match first.
+pub async fn search_books(
+    State(api_context): State<ApiContext>,
+    Query(query): Query<SearchBooksQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let connection_pool = &api_context.db;
+
+    let tsquery = prefix_query(&query.q)
+        .ok_or_else(|| Error::unprocessable_entity([("q", "must contain a word to search for")]))?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+
+    let list = Db::search_books(&tsquery, 0, limit, connection_pool).await?;
+    let results: Vec<BookSearchResult> = list
+        .iter()
+        .map(|book| book.to_book_search_result())
+        .collect();
+    if let Some(format) = CitationFormat::requested(query.format, &headers) {
+        let books: Vec<Book> = results.into_iter().map(|result| result.book).collect();
+        return Ok(book_citations(format, &books));
+    }
+
+    Ok((StatusCode::OK, Json(BookSearchResultsQuery { results })).into_response())
+}
+
+/// Looks a book up by LCCN, however it was typed. It is represented as
+/// `get_book_by_id` represents it, but without its copies.
+pub async fn get_book_by_lccn(
+    State(api_context): State<ApiContext>,
+    Path(lccn): Path<String>,
+    Query(query): Query<CitationQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let connection_pool = &api_context.db;
+
+    let lccn = lccn::normalize(&lccn)
+        .map_err(|error| Error::unprocessable_entity([("lccn", error.to_string())]))?;
+    let book = Db::get_book_by_lccn(&lccn, connection_pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+    if let Some(response) = negotiated_book(&book, query.format, &headers) {
+        return Ok(response);
+    }
+
+    Ok((StatusCode::OK, Json(book.to_book())).into_response())
+}
+
+/// Gets a book by id, as JSON along with where each of its copies lives
+/// unless another representation is asked for. `?format=` or `Accept` can ask
+/// for a citation; `Accept` alone for schema.org JSON-LD
+/// (`application/ld+json`), Dublin Core (`application/dc+xml`) or a MARC
+/// record (`application/marc+xml` or `application/marc`).
+pub async fn get_book_by_id(
+    State(api_context): State<ApiContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<CitationQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
     let connection_pool = &api_context.db;
 
-    let list = Db::get_book_list(connection_pool).await?;
-    let books = list.iter().map(|book| book.to_book()).collect();
+    let book_from_query = match Db::get_book(&id, connection_pool).await {
+        Err(sqlx::Error::RowNotFound) => return Err(Error::NotFound),
+        book_from_query => book_from_query?,
+    };
+    if let Some(response) = negotiated_book(&book_from_query, query.format, &headers) {
+        return Ok(response);
+    }
+
+    let holdings = Db::get_book_holdings(&id, connection_pool).await?;
+    let mut book = book_from_query.to_book();
+    book.copies = Some(holdings.iter().map(|holding| holding.to_book_holding()).collect());
 
-    Ok((StatusCode::OK, Json(BooksQuery { books })))
+    Ok((StatusCode::OK, Json(book)).into_response())
 }
 
-/// Get a specific book 
+/// The book in whatever representation other than plain JSON the request
+/// asks for, if any. `?format=` wins outright; otherwise it is the one the
+/// `Accept` header rates highest, and none if that is plain JSON.
+fn negotiated_book(
+    book: &BookFromQuery,
+    format: Option<CitationFormat>,
+    headers: &HeaderMap,
+) -> Option<Response> {
+    if let Some(format) = format {
+        return Some(book_citation(format, &book.to_book()));
+    }
+
+    for media_type in accepted_media_types(headers) {
+        if media_type == "application/json" || media_type == "*/*" {
+            return None;
+        }
+        if let Some(format) = CitationFormat::from_media_type(media_type) {
+            return Some(book_citation(format, &book.to_book()));
+        }
+        if let Some(format) = LinkedDataFormat::from_media_type(media_type) {
+            return Some(book_linked_data(format, &book.to_book()));
+        }
+        if let Some(format) = ExportFormat::from_media_type(media_type) {
+            return Some(book_record(format, book));
+        }
+    }
+
+    None
+}
+
+/// Get a specific book along with where each of its copies lives
 pub async fn get_book(
     State(api_context): State<ApiContext>,
     request: Json<BookBody<GetBookQuery>>,
@@ -114,17 +532,30 @@ pub async fn get_book(
     let id = &request.book.id;
 
     let book_from_query = Db::get_book(id, connection_pool).await?;
+    let holdings = Db::get_book_holdings(id, connection_pool).await?;
+
+    let mut book = book_from_query.to_book();
+    book.copies = Some(holdings.iter().map(|holding| holding.to_book_holding()).collect());
 
-    Ok((StatusCode::OK, Json(book_from_query.to_book())))
+    Ok((StatusCode::OK, Json(book)))
 }
 
-/// Creates a book
+/// Creates a book. An ISBN or LCCN is checked and its normalized form stored
+/// alongside it. Asked to enrich it, a book with an ISBN is filled in from
+/// the metadata source; if the source can't help, or is slow to, the book is
+/// created as it was sent.
 pub async fn create_book(
     State(api_context): State<ApiContext>,
+    Query(query): Query<CreateBookQuery>,
     request: Json<BookBody<BookQuery>>,
-) -> Result<(StatusCode, String), ApiError> {
+) -> Result<(StatusCode, String), Error> {
     let connection_pool = &api_context.db;
     let book = request.book.to_owned();
+    let isbn_13 = isbn::normalize_field(&book.isbn)
+        .map_err(|error| Error::unprocessable_entity([("isbn", error.to_string())]))?;
+    let lccn_normalized = lccn::normalize_field(&book.lccn)
+        .map_err(|error| Error::unprocessable_entity([("lccn", error.to_string())]))?;
+    let published = publish_date::parse(&book.publish_date);
     let book_query = BookQuery {
         isbn: book.isbn,
         lccn: book.lccn,
@@ -132,7 +563,26 @@ pub async fn create_book(
         author: book.author,
         publish_date: book.publish_date,
     };
-    let book_id = Db::create_book(book_query, connection_pool).await?;
+    let book_id = Db::create_book(
+        book_query,
+        isbn_13,
+        lccn_normalized,
+        published,
+        connection_pool,
+    ).await?;
+
+    if query.enrich {
+        let book = Db::get_book(&book_id, connection_pool).await?;
+        if book.isbn_13.is_some() {
+            let enriching = enrich(&api_context, &book);
+            match tokio::time::timeout(ENRICH_ON_CREATE_TIMEOUT, enriching).await {
+                Ok(Err(Error::Sqlx(error))) => return Err(Error::Sqlx(error)),
+                Ok(Err(error)) => tracing::warn!("book {book_id} was not enriched: {error}"),
+                Ok(Ok(_)) => {}
+                Err(_) => tracing::warn!("book {book_id} was not enriched in time"),
+            }
+        }
+    }
 
     Ok((StatusCode::OK, book_id.to_string()))
 }
@@ -141,9 +591,20 @@ pub async fn create_book(
 pub async fn update_book(
     State(api_context): State<ApiContext>,
     Json(request): Json<BookBody<BookUpdateQuery>>,
-) -> Result<(StatusCode, Json<Book>), ApiError> {
+) -> Result<(StatusCode, Json<Book>), Error> {
     let connection_pool = &api_context.db;
     let book_body = request.book.to_owned();
+    let isbn_13 = match &book_body.isbn {
+        Some(isbn) => isbn::normalize_field(isbn)
+            .map_err(|error| Error::unprocessable_entity([("isbn", error.to_string())]))?,
+        None => None,
+    };
+    let lccn_normalized = match &book_body.lccn {
+        Some(lccn) => lccn::normalize_field(lccn)
+            .map_err(|error| Error::unprocessable_entity([("lccn", error.to_string())]))?,
+        None => None,
+    };
+    let published = book_body.publish_date.as_deref().and_then(publish_date::parse);
     let book_query = BookUpdateQuery {
         id: book_body.id,
         isbn: book_body.isbn,
@@ -153,21 +614,32 @@ pub async fn update_book(
         publish_date: book_body.publish_date,
     };
 
-    let updated_book = Db::update_book(book_query, connection_pool).await?;
+    let updated_book = Db::update_book(
+        book_query,
+        isbn_13,
+        lccn_normalized,
+        published,
+        connection_pool,
+    ).await?;
 
     let book = updated_book.to_book();
 
     Ok((StatusCode::OK, Json(book)))
 }
 
-/// Deletes a particular book
+/// Deletes a particular book along with its copies. A book with a copy that
+/// has ever been lent out is kept, since its loans are the circulation record.
 pub async fn delete_book(
     State(api_context): State<ApiContext>,
     request: Json<BookBody<DeleteBook>>,
-) -> Result<(StatusCode, String), ApiError> {
+) -> Result<(StatusCode, String), Error> {
     let connection_pool = &api_context.db;
 
-    let result = Db::delete_book(request.book.id, connection_pool).await?;
+    let result = Db::delete_book(request.book.id, connection_pool)
+        .await
+        .on_constraint("loan_book_copy_id_fkey", |_| {
+            Error::conflict("book has copies with loans on record")
+        })?;
 
     Ok((StatusCode::OK, result.rows_affected().to_string()))
 }
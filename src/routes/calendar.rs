@@ -0,0 +1,151 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+use time::{Date, Time};
+
+use crate::db::Db;
+use crate::routes::{ApiContext, Error, ResultExt};
+
+time::serde::format_description!(clock_time, Time, "[hour]:[minute]");
+
+/// Used to namespace our JSON query
+/// { "calendar": <T> }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct CalendarBody<T = CalendarQuery> {
+    pub calendar: T,
+}
+
+/// The base return structure for a location's calendar to the client.
+///
+/// `hours` lists the weekdays the location opens, 1 being Monday; a location
+/// with no hours of its own follows its parent's. `closures` are holidays and
+/// other days it stays shut.
+#[derive(serde::Serialize)]
+pub struct Calendar {
+    pub location_id: Uuid,
+    pub hours: Vec<OpeningHours>,
+    pub closures: Vec<Closure>,
+}
+
+/// The hours a location opens on one weekday, written `"09:30"`
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct OpeningHours {
+    pub weekday: i16,
+    #[serde(with = "clock_time")]
+    pub opens_at: Time,
+    #[serde(with = "clock_time")]
+    pub closes_at: Time,
+}
+
+/// A run of days a location is closed, inclusive of both ends
+#[derive(serde::Serialize)]
+pub struct Closure {
+    pub id: Uuid,
+    pub starts_on: Date,
+    pub ends_on: Date,
+    pub reason: Option<String>,
+}
+
+/// Query coming from Client. Replaces the location's whole calendar.
+#[derive(serde::Deserialize, Clone)]
+pub struct CalendarQuery {
+    #[serde(default)]
+    pub hours: Vec<OpeningHours>,
+    #[serde(default)]
+    pub closures: Vec<ClosureQuery>,
+}
+
+/// Closure Query coming from Client
+#[derive(serde::Deserialize, Clone)]
+pub struct ClosureQuery {
+    pub starts_on: Date,
+    pub ends_on: Date,
+    pub reason: Option<String>,
+}
+
+/// Database Object to be cast into a Closure
+#[derive(Clone)]
+pub struct ClosureFromQuery {
+    pub location_closure_id: Uuid,
+    pub starts_on: Date,
+    pub ends_on: Date,
+    pub reason: Option<String>,
+}
+
+impl ClosureFromQuery {
+    fn to_closure(&self) -> Closure {
+        let this = self.to_owned();
+        Closure {
+            id: this.location_closure_id,
+            starts_on: this.starts_on,
+            ends_on: this.ends_on,
+            reason: this.reason,
+        }
+    }
+}
+
+/// Gets a location's own opening hours and closures
+pub async fn get_calendar(
+    State(api_context): State<ApiContext>,
+    Path(location_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<Calendar>), Error> {
+    let connection_pool = &api_context.db;
+
+    Db::get_location(&location_id, connection_pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let hours = Db::get_opening_hours(&location_id, connection_pool).await?;
+    let closures = Db::get_closures(&location_id, connection_pool).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(Calendar {
+            location_id,
+            hours,
+            closures: closures
+                .iter()
+                .map(|closure| closure.to_closure())
+                .collect(),
+        }),
+    ))
+}
+
+/// Replaces a location's opening hours and closures
+pub async fn update_calendar(
+    State(api_context): State<ApiContext>,
+    Path(location_id): Path<Uuid>,
+    Json(request): Json<CalendarBody<CalendarQuery>>,
+) -> Result<(StatusCode, Json<Calendar>), Error> {
+    let connection_pool = &api_context.db;
+    let calendar = request.calendar;
+
+    Db::get_location(&location_id, connection_pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let mut transaction = connection_pool.begin().await?;
+    Db::set_location_calendar(
+        &location_id,
+        &calendar.hours,
+        &calendar.closures,
+        &mut transaction,
+    )
+    .await
+    .on_constraint("location_opening_hours_pkey", |_| {
+        Error::unprocessable_entity([("hours", "lists a weekday more than once")])
+    })
+    .on_constraint("location_opening_hours_weekday_check", |_| {
+        Error::unprocessable_entity([("hours", "weekday must be between 1 and 7")])
+    })
+    .on_constraint("location_opening_hours_order_check", |_| {
+        Error::unprocessable_entity([("hours", "must close after they open")])
+    })
+    .on_constraint("location_closure_order_check", |_| {
+        Error::unprocessable_entity([("closures", "must not end before they start")])
+    })?;
+    transaction.commit().await?;
+
+    get_calendar(State(api_context), Path(location_id)).await
+}
@@ -0,0 +1,185 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+use time::{Date, OffsetDateTime};
+
+use crate::circulation::Circulation;
+use crate::db::Db;
+use crate::routes::pagination::{decode_cursor, page_size};
+use crate::routes::{ApiContext, Error, Hold};
+
+/// Used to namespace our JSON query
+/// { "loan": <T> }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct LoanBody<T> {
+    pub loan: T,
+}
+
+/// The base return structure for a loan to the client
+#[derive(serde::Serialize)]
+pub struct Loan {
+    pub id: Uuid,
+    pub copy_id: Uuid,
+    pub patron_id: Uuid,
+    #[serde(with = "time::serde::rfc3339")]
+    pub checked_out_at: OffsetDateTime,
+    pub due_on: Date,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub returned_at: Option<OffsetDateTime>,
+    pub renewals: i32,
+}
+
+/// What a check-in returns to the client: the closed loan, and the hold the
+/// copy was trapped for if it now belongs on the hold shelf
+#[derive(serde::Serialize)]
+pub struct Checkin {
+    #[serde(flatten)]
+    pub loan: Loan,
+    pub trapped_hold: Option<Hold>,
+}
+
+/// Checkout Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct CheckoutQuery {
+    pub book_copy_id: Uuid,
+    pub patron_id: Uuid,
+}
+
+/// Checkin Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct CheckinQuery {
+    pub book_copy_id: Uuid,
+}
+
+/// Renew Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct RenewQuery {
+    pub id: Uuid,
+}
+
+/// List Loans Query coming from Client, the paging optional
+#[derive(serde::Deserialize)]
+pub struct ListLoansQuery {
+    pub patron_id: Uuid,
+    #[serde(default)]
+    pub include_returned: bool,
+    pub page_size: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+/// Where a page of loans ended: the last loan's checkout time and id
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LoanCursor {
+    #[serde(with = "time::serde::rfc3339")]
+    pub checked_out_at: OffsetDateTime,
+    pub id: Uuid,
+}
+
+/// Array of loans to the client, one page at a time. `next_cursor` fetches
+/// the following page and is `null` on the last one.
+#[derive(serde::Serialize, Default)]
+pub struct LoansQuery {
+    pub loans: Vec<Loan>,
+    pub next_cursor: Option<String>,
+}
+
+/// Database Object to be cast into a Loan
+#[derive(Clone)]
+pub struct LoanFromQuery {
+    pub loan_id: Uuid,
+    pub book_copy_id: Uuid,
+    pub patron_id: Uuid,
+    pub checked_out_at: OffsetDateTime,
+    pub due_on: Date,
+    pub returned_at: Option<OffsetDateTime>,
+    pub renewals: i32,
+}
+
+impl LoanFromQuery {
+    fn to_loan(&self) -> Loan {
+        let this = self.to_owned();
+        Loan {
+            id: this.loan_id,
+            copy_id: this.book_copy_id,
+            patron_id: this.patron_id,
+            checked_out_at: this.checked_out_at,
+            due_on: this.due_on,
+            returned_at: this.returned_at,
+            renewals: this.renewals,
+        }
+    }
+}
+
+/// Gets a page of a patron's loans, newest first, by default only the ones
+/// still out
+pub async fn get_list_loans(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LoanBody<ListLoansQuery>>,
+) -> Result<(StatusCode, Json<LoansQuery>), Error> {
+    let connection_pool = &api_context.db;
+    let query = request.loan;
+
+    let page_size = page_size(query.page_size);
+    let cursor: Option<LoanCursor> = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let page = Db::get_patron_loan_list(
+        &query.patron_id,
+        query.include_returned,
+        page_size,
+        cursor.as_ref(),
+        connection_pool,
+    )
+    .await?;
+    let next_cursor = page.next_cursor(|loan| LoanCursor {
+        checked_out_at: loan.checked_out_at,
+        id: loan.loan_id,
+    });
+    let loans = page.rows.iter().map(|loan| loan.to_loan()).collect();
+
+    Ok((StatusCode::OK, Json(LoansQuery { loans, next_cursor })))
+}
+
+/// Checks a copy out to a patron
+pub async fn checkout(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LoanBody<CheckoutQuery>>,
+) -> Result<(StatusCode, Json<Loan>), Error> {
+    let connection_pool = &api_context.db;
+    let query = request.loan;
+
+    let loan =
+        Circulation::checkout(&query.book_copy_id, &query.patron_id, connection_pool).await?;
+
+    Ok((StatusCode::OK, Json(loan.to_loan())))
+}
+
+/// Checks a copy back in
+pub async fn checkin(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LoanBody<CheckinQuery>>,
+) -> Result<(StatusCode, Json<Checkin>), Error> {
+    let connection_pool = &api_context.db;
+
+    let (loan, hold) = Circulation::checkin(&request.loan.book_copy_id, connection_pool).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(Checkin {
+            loan: loan.to_loan(),
+            trapped_hold: hold.map(|hold| hold.to_hold()),
+        }),
+    ))
+}
+
+/// Renews a loan
+pub async fn renew(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LoanBody<RenewQuery>>,
+) -> Result<(StatusCode, Json<Loan>), Error> {
+    let connection_pool = &api_context.db;
+
+    let loan = Circulation::renew(&request.loan.id, connection_pool).await?;
+
+    Ok((StatusCode::OK, Json(loan.to_loan())))
+}
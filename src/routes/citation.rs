@@ -0,0 +1,84 @@
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
+
+use crate::catalog::citation;
+use crate::routes::export::accepted_media_types;
+use crate::routes::Book;
+
+/// The formats a book can be cited in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CitationFormat {
+    /// A BibTeX `@book` entry
+    Bibtex,
+    /// A RIS record, as reference managers such as Zotero and EndNote read
+    Ris,
+    /// A CSL-JSON item, as citation processors read
+    CslJson,
+}
+
+impl CitationFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Bibtex => "application/x-bibtex",
+            Self::Ris => "application/x-research-info-systems",
+            Self::CslJson => "application/vnd.citationstyles.csl+json",
+        }
+    }
+
+    /// The citation format served as `media_type`, if it is one
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        [Self::Bibtex, Self::Ris, Self::CslJson]
+            .into_iter()
+            .find(|format| format.content_type().eq_ignore_ascii_case(media_type))
+    }
+
+    /// The citation format a request's `Accept` header asks for, if it names
+    /// one
+    pub fn from_accept(headers: &HeaderMap) -> Option<Self> {
+        accepted_media_types(headers).find_map(Self::from_media_type)
+    }
+
+    /// The format asked for by `?format=`, or failing that by `Accept`
+    pub fn requested(format: Option<Self>, headers: &HeaderMap) -> Option<Self> {
+        format.or_else(|| Self::from_accept(headers))
+    }
+}
+
+/// Citation Query coming from Client as `?format=`
+#[derive(serde::Deserialize)]
+pub struct CitationQuery {
+    pub format: Option<CitationFormat>,
+}
+
+/// A single book cited in `format`
+pub(crate) fn book_citation(format: CitationFormat, book: &Book) -> Response {
+    match format {
+        CitationFormat::CslJson => citation_response(format, citation::csl_json(book).to_string()),
+        _ => book_citations(format, std::slice::from_ref(book)),
+    }
+}
+
+/// A set of books cited in `format`, a CSL-JSON set being an array of items
+pub(crate) fn book_citations(format: CitationFormat, books: &[Book]) -> Response {
+    let body = match format {
+        CitationFormat::Bibtex => citation::bibtex(books),
+        CitationFormat::Ris => citation::ris(books),
+        CitationFormat::CslJson => {
+            Value::Array(books.iter().map(citation::csl_json).collect()).to_string()
+        }
+    };
+
+    citation_response(format, body)
+}
+
+fn citation_response(format: CitationFormat, body: String) -> Response {
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, format.content_type())],
+        body,
+    )
+        .into_response()
+}
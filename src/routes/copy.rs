@@ -0,0 +1,253 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+use time::{Date, OffsetDateTime};
+
+use crate::db::Db;
+use crate::routes::{ApiContext, Error, Location, ResultExt};
+
+/// Used to namespace our JSON query
+/// { "copy": <T> }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct BookCopyBody<T = BookCopyQuery> {
+    pub copy: T,
+}
+
+/// The base return structure for a physical copy of a book to the client
+#[derive(serde::Serialize)]
+pub struct BookCopy {
+    pub id: Uuid,
+    pub master_id: Uuid,
+    pub location_id: Option<Uuid>,
+    pub item_type: String,
+    pub barcode: String,
+    pub condition: Option<String>,
+    pub acquisition_date: Option<Date>,
+    pub price_cents: Option<i64>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub withdrawn_at: Option<OffsetDateTime>,
+}
+
+/// Query coming from Client
+#[derive(serde::Deserialize, Clone)]
+pub struct BookCopyQuery {
+    pub master_book_id: Uuid,
+    pub location_id: Option<Uuid>,
+    #[serde(default = "default_item_type")]
+    pub item_type: String,
+    pub barcode: String,
+    pub condition: Option<String>,
+    pub acquisition_date: Option<Date>,
+    pub price_cents: Option<i64>,
+}
+
+pub(crate) fn default_item_type() -> String {
+    "book".to_string()
+}
+
+/// Array Query coming from Client
+#[derive(serde::Serialize, Default)]
+pub struct BookCopiesQuery {
+    pub copies: Vec<BookCopy>,
+}
+
+/// List Copies Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct ListBookCopiesQuery {
+    pub master_book_id: Uuid,
+    #[serde(default)]
+    pub include_withdrawn: bool,
+}
+
+/// Update Query coming from Client
+#[derive(serde::Deserialize, Clone)]
+pub struct BookCopyUpdateQuery {
+    pub id: Uuid,
+    pub location_id: Option<Uuid>,
+    pub item_type: Option<String>,
+    pub barcode: Option<String>,
+    pub condition: Option<String>,
+    pub acquisition_date: Option<Date>,
+    pub price_cents: Option<i64>,
+}
+
+/// Get Copy Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct GetBookCopyQuery {
+    pub id: Uuid,
+}
+
+/// Withdraw Copy Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct WithdrawBookCopy {
+    pub id: Uuid,
+}
+
+/// Database Object to be cast into a BookCopy
+#[derive(Clone)]
+pub struct BookCopyFromQuery {
+    pub book_copy_id: Uuid,
+    pub master_book_id: Uuid,
+    pub location_id: Option<Uuid>,
+    pub item_type: String,
+    pub barcode: String,
+    pub condition: Option<String>,
+    pub acquisition_date: Option<Date>,
+    pub price_cents: Option<i64>,
+    pub withdrawn_at: Option<OffsetDateTime>,
+}
+
+impl BookCopyFromQuery {
+    fn to_book_copy(&self) -> BookCopy {
+        let this = self.to_owned();
+        BookCopy {
+            id: this.book_copy_id,
+            master_id: this.master_book_id,
+            location_id: this.location_id,
+            item_type: this.item_type,
+            barcode: this.barcode,
+            condition: this.condition,
+            acquisition_date: this.acquisition_date,
+            price_cents: this.price_cents,
+            withdrawn_at: this.withdrawn_at,
+        }
+    }
+}
+
+/// Where a copy on the shelves physically lives, as reported on a `Book`
+#[derive(serde::Serialize)]
+pub struct BookHolding {
+    pub copy_id: Uuid,
+    pub barcode: String,
+    pub location: Option<Location>,
+}
+
+/// Database Object to be cast into a BookHolding
+#[derive(Clone)]
+pub struct BookHoldingFromQuery {
+    pub book_copy_id: Uuid,
+    pub barcode: String,
+    pub location_id: Option<Uuid>,
+    pub parent_location_id: Option<Uuid>,
+    pub location_name: Option<String>,
+    pub floor: Option<String>,
+    pub shelf_start: Option<String>,
+    pub shelf_end: Option<String>,
+}
+
+impl BookHoldingFromQuery {
+    pub(crate) fn to_book_holding(&self) -> BookHolding {
+        let this = self.to_owned();
+        let location = match (this.location_id, this.location_name) {
+            (Some(id), Some(name)) => Some(Location {
+                id,
+                parent_id: this.parent_location_id,
+                name,
+                floor: this.floor,
+                shelf_start: this.shelf_start,
+                shelf_end: this.shelf_end,
+            }),
+            _ => None,
+        };
+
+        BookHolding {
+            copy_id: this.book_copy_id,
+            barcode: this.barcode,
+            location,
+        }
+    }
+}
+
+/// Maps the constraint violations a copy write can hit into `422`s
+fn on_copy_constraints<T>(result: Result<T, sqlx::Error>) -> Result<T, Error> {
+    result
+        .on_constraint("book_copy_barcode_key", |_| {
+            Error::unprocessable_entity([("barcode", "already in use")])
+        })
+        .on_constraint("book_copy_master_book_id_fkey", |_| {
+            Error::unprocessable_entity([("master_book_id", "does not exist")])
+        })
+        .on_constraint("book_copy_location_id_fkey", |_| {
+            Error::unprocessable_entity([("location_id", "does not exist")])
+        })
+        .on_constraint("book_copy_item_type_fkey", |_| {
+            Error::unprocessable_entity([("item_type", "is not a known item type")])
+        })
+        .on_constraint("book_copy_price_cents_check", |_| {
+            Error::unprocessable_entity([("price_cents", "must not be negative")])
+        })
+}
+
+/// Gets the copies held for a master record
+pub async fn get_list_book_copies(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<BookCopyBody<ListBookCopiesQuery>>,
+) -> Result<(StatusCode, Json<BookCopiesQuery>), Error> {
+    let connection_pool = &api_context.db;
+    let query = request.copy;
+
+    let list = Db::get_book_copy_list(
+        &query.master_book_id,
+        query.include_withdrawn,
+        connection_pool,
+    )
+    .await?;
+    let copies = list.iter().map(|copy| copy.to_book_copy()).collect();
+
+    Ok((StatusCode::OK, Json(BookCopiesQuery { copies })))
+}
+
+/// Get a specific copy
+pub async fn get_book_copy(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<BookCopyBody<GetBookCopyQuery>>,
+) -> Result<(StatusCode, Json<BookCopy>), Error> {
+    let connection_pool = &api_context.db;
+
+    let copy = Db::get_book_copy(&request.copy.id, connection_pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok((StatusCode::OK, Json(copy.to_book_copy())))
+}
+
+/// Creates a copy of an existing master record
+pub async fn create_book_copy(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<BookCopyBody<BookCopyQuery>>,
+) -> Result<(StatusCode, String), Error> {
+    let connection_pool = &api_context.db;
+
+    let copy_id = on_copy_constraints(Db::create_book_copy(request.copy, connection_pool).await)?;
+
+    Ok((StatusCode::OK, copy_id.to_string()))
+}
+
+/// Updates a copy
+pub async fn update_book_copy(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<BookCopyBody<BookCopyUpdateQuery>>,
+) -> Result<(StatusCode, Json<BookCopy>), Error> {
+    let connection_pool = &api_context.db;
+
+    let updated_copy =
+        on_copy_constraints(Db::update_book_copy(request.copy, connection_pool).await)?
+            .ok_or(Error::NotFound)?;
+
+    Ok((StatusCode::OK, Json(updated_copy.to_book_copy())))
+}
+
+/// Withdraws a copy from the collection
+pub async fn withdraw_book_copy(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<BookCopyBody<WithdrawBookCopy>>,
+) -> Result<(StatusCode, Json<BookCopy>), Error> {
+    let connection_pool = &api_context.db;
+
+    let withdrawn_copy = Db::withdraw_book_copy(request.copy.id, connection_pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok((StatusCode::OK, Json(withdrawn_copy.to_book_copy())))
+}
@@ -0,0 +1,79 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+
+use crate::catalog::enrichment::BookMetadata;
+use crate::catalog::publish_date;
+use crate::db::Db;
+use crate::routes::{ApiContext, Book, BookFromQuery, BookUpdateQuery, Error};
+
+/// Fills in a book's missing title, author and publication date from the
+/// metadata source, looking it up by its ISBN. Anything the catalog already
+/// records is kept as it is.
+pub async fn enrich_book(
+    State(api_context): State<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> Result<(StatusCode, Json<Book>), Error> {
+    let book = match Db::get_book(&id, &api_context.db).await {
+        Err(sqlx::Error::RowNotFound) => return Err(Error::NotFound),
+        book => book?,
+    };
+
+    let book = enrich(&api_context, &book).await?;
+
+    Ok((StatusCode::OK, Json(book.to_book())))
+}
+
+/// Looks a book up and writes what it was missing, returning it as it now
+/// stands. A book with nothing missing is still looked up, so a bad ISBN is
+/// reported either way.
+pub(crate) async fn enrich(
+    api_context: &ApiContext,
+    book: &BookFromQuery,
+) -> Result<BookFromQuery, Error> {
+    let isbn_13 = book
+        .isbn_13
+        .as_deref()
+        .ok_or_else(|| Error::unprocessable_entity([("isbn", "is needed to look the book up")]))?;
+
+    let metadata = api_context
+        .metadata_source
+        .lookup_isbn(isbn_13)
+        .await
+        .map_err(|error| {
+            tracing::warn!("looking up ISBN {isbn_13} failed: {error:?}");
+            Error::bad_gateway(error.to_string())
+        })?
+        .ok_or_else(|| {
+            Error::unprocessable_entity([("isbn", "is not known to the metadata source")])
+        })?;
+
+    let Some(update) = missing_fields(book, metadata) else {
+        return Ok(book.clone());
+    };
+    let published = update.publish_date.as_deref().and_then(publish_date::parse);
+
+    Ok(Db::update_book(update, None, None, published, &api_context.db).await?)
+}
+
+/// An update setting each field the book is missing to what the source
+/// knows of it, if that is anything
+fn missing_fields(book: &BookFromQuery, metadata: BookMetadata) -> Option<BookUpdateQuery> {
+    let missing = |field: &Option<String>, found: Option<String>| match field.as_deref() {
+        Some(field) if !field.trim().is_empty() => None,
+        _ => found,
+    };
+
+    let update = BookUpdateQuery {
+        id: book.master_book_id,
+        lccn: None,
+        isbn: None,
+        title: missing(&book.title, metadata.title),
+        author: missing(&book.author, metadata.author),
+        publish_date: missing(&book.publish_date, metadata.publish_date),
+    };
+
+    (update.title.is_some() || update.author.is_some() || update.publish_date.is_some())
+        .then_some(update)
+}
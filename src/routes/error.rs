@@ -33,6 +33,21 @@ pub enum Error {
     #[error("request path not found")]
     NotFound,
 
+    /// Return `409 Conflict`
+    ///
+    /// Used when a request is well formed but clashes with the current state of a record,
+    /// e.g. checking out a copy that is already on loan. The message is returned to the
+    /// client as a plain text body.
+    #[error("{0}")]
+    Conflict(Cow<'static, str>),
+
+    /// Return `502 Bad Gateway`
+    ///
+    /// Used when a request depends on another service, e.g. the metadata source books are
+    /// enriched from, and it fails. The message is returned to the client as a plain text body.
+    #[error("{0}")]
+    BadGateway(Cow<'static, str>),
+
     /// Return `422 Unprocessable Entity`
     ///
     /// This also serializes the `errors` map to JSON to satisfy the requirement for
@@ -105,11 +120,23 @@ impl Error {
         Self::UnprocessableEntity { errors: error_map }
     }
 
+    /// Convenient constructor for `Error::Conflict`.
+    pub fn conflict(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::Conflict(message.into())
+    }
+
+    /// Convenient constructor for `Error::BadGateway`.
+    pub fn bad_gateway(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::BadGateway(message.into())
+    }
+
     fn status_code(&self) -> StatusCode {
         match self {
             Self::Unauthorized => StatusCode::UNAUTHORIZED,
             Self::Forbidden => StatusCode::FORBIDDEN,
             Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::BadGateway(_) => StatusCode::BAD_GATEWAY,
             Self::UnprocessableEntity { .. } => StatusCode::UNPROCESSABLE_ENTITY,
             Self::Sqlx(_) | Self::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -0,0 +1,120 @@
+use axum::body::StreamBody;
+use axum::extract::{Query, State};
+use axum::http::header::{ACCEPT, CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+
+use crate::catalog::export::Exports;
+use crate::routes::{ApiContext, BookFromQuery, Error};
+
+/// The formats the catalog can be exported in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// MARC 21 in ISO 2709
+    Marc,
+    /// MARC 21 in MARCXML
+    #[default]
+    Marcxml,
+    /// A header row, then a row of the raw fields of each book
+    Csv,
+    /// Each book as a line of JSON, as the API returns it
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Marc => "application/marc",
+            Self::Marcxml => "application/marc+xml",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Jsonl => "application/jsonl",
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Marc => "catalog.mrc",
+            Self::Marcxml => "catalog.xml",
+            Self::Csv => "catalog.csv",
+            Self::Jsonl => "catalog.jsonl",
+        }
+    }
+
+    /// The MARC format served as `media_type`, if it is one
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        [Self::Marc, Self::Marcxml]
+            .into_iter()
+            .find(|format| format.content_type().eq_ignore_ascii_case(media_type))
+    }
+}
+
+/// The media types in a request's `Accept` header without their parameters,
+/// those the client rates highest first and ties in the order listed. Those
+/// rated `q=0` are ones the client refuses, so are left out.
+pub(crate) fn accepted_media_types(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    let mut accepted: Vec<(&str, f32)> = headers
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|media_range| {
+            let mut params = media_range.split(';');
+            let media_type = params.next().unwrap_or_default().trim();
+            let quality = params
+                .filter_map(|param| param.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                .and_then(|(_, quality)| quality.trim().parse().ok())
+                .unwrap_or(1.0);
+
+            (!media_type.is_empty() && quality > 0.0).then_some((media_type, quality))
+        })
+        .collect();
+    accepted.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    accepted.into_iter().map(|(media_type, _)| media_type)
+}
+
+/// Export Query coming from Client as `?format=`
+#[derive(serde::Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// A single book as a record in `format`, or a `422` if it is too long to
+/// write as ISO 2709
+pub(crate) fn book_record(format: ExportFormat, book: &BookFromQuery) -> Response {
+    match Exports::book(format, book) {
+        Ok(record) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, format.content_type())],
+            record,
+        )
+            .into_response(),
+        Err(error) => Error::unprocessable_entity([("format", error.to_string())]).into_response(),
+    }
+}
+
+/// Streams the whole catalog as a download, in MARCXML unless `format` asks
+/// for `marc`, `csv` or `jsonl`
+pub async fn export_books(
+    State(api_context): State<ApiContext>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, Error> {
+    let format = query.format;
+    let body = StreamBody::new(Exports::books(format, api_context.db.clone()));
+
+    Ok((
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", format.file_name()),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
@@ -0,0 +1,272 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+use time::{Date, OffsetDateTime};
+
+use crate::circulation::hold::Holds;
+use crate::db::Db;
+use crate::routes::pagination::{decode_cursor, page_size};
+use crate::routes::{ApiContext, Error, ResultExt};
+
+/// Used to namespace our JSON query
+/// { "hold": <T> }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct HoldBody<T = HoldQuery> {
+    pub hold: T,
+}
+
+/// The base return structure for a hold to the client.
+///
+/// `status` is `waiting` while in the queue and `trapped` once a copy is on
+/// the hold shelf for the patron to collect by `pickup_by`. A hold not
+/// collected in time is `expired`.
+#[derive(serde::Serialize)]
+pub struct Hold {
+    pub id: Uuid,
+    pub master_id: Uuid,
+    pub patron_id: Uuid,
+    pub pickup_location_id: Uuid,
+    pub queue_position: i32,
+    pub status: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub placed_at: OffsetDateTime,
+    pub expires_on: Option<Date>,
+    pub suspended_from: Option<Date>,
+    pub suspended_until: Option<Date>,
+    pub copy_id: Option<Uuid>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub trapped_at: Option<OffsetDateTime>,
+    pub pickup_by: Option<Date>,
+}
+
+/// Query coming from Client
+#[derive(serde::Deserialize, Clone)]
+pub struct HoldQuery {
+    pub master_book_id: Uuid,
+    pub patron_id: Uuid,
+    pub pickup_location_id: Uuid,
+    pub expires_on: Option<Date>,
+    pub suspended_from: Option<Date>,
+    pub suspended_until: Option<Date>,
+}
+
+/// Array Query coming from Client, one page at a time. `next_cursor` fetches
+/// the following page and is `null` on the last one.
+#[derive(serde::Serialize, Default)]
+pub struct HoldsQuery {
+    pub holds: Vec<Hold>,
+    pub next_cursor: Option<String>,
+}
+
+/// List Holds Query coming from Client. Lists the queue for a title, or
+/// everything a patron is waiting on; the paging is optional.
+#[derive(serde::Deserialize)]
+pub struct ListHoldsQuery {
+    pub master_book_id: Option<Uuid>,
+    pub patron_id: Option<Uuid>,
+    pub page_size: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+/// Where a page of holds ended. A title's queue picks up after the last
+/// hold's `queue_position`, a patron's holds after its `placed_at`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HoldCursor {
+    pub queue_position: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub placed_at: OffsetDateTime,
+    pub id: Uuid,
+}
+
+/// Update Query coming from Client
+#[derive(serde::Deserialize, Clone)]
+pub struct HoldUpdateQuery {
+    pub id: Uuid,
+    pub pickup_location_id: Option<Uuid>,
+    pub expires_on: Option<Date>,
+    pub suspended_from: Option<Date>,
+    pub suspended_until: Option<Date>,
+    #[serde(default)]
+    pub resume: bool,
+}
+
+/// Reorder Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct ReorderHoldQuery {
+    pub id: Uuid,
+    pub queue_position: usize,
+}
+
+/// Cancel Hold Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct CancelHold {
+    pub id: Uuid,
+}
+
+/// Database Object to be cast into a Hold
+#[derive(Clone)]
+pub struct HoldFromQuery {
+    pub hold_id: Uuid,
+    pub master_book_id: Uuid,
+    pub patron_id: Uuid,
+    pub pickup_location_id: Uuid,
+    pub queue_position: i32,
+    pub status: String,
+    pub placed_at: OffsetDateTime,
+    pub expires_on: Option<Date>,
+    pub suspended_from: Option<Date>,
+    pub suspended_until: Option<Date>,
+    pub book_copy_id: Option<Uuid>,
+    pub trapped_at: Option<OffsetDateTime>,
+    pub pickup_by: Option<Date>,
+}
+
+impl HoldFromQuery {
+    pub(crate) fn to_hold(&self) -> Hold {
+        let this = self.to_owned();
+        Hold {
+            id: this.hold_id,
+            master_id: this.master_book_id,
+            patron_id: this.patron_id,
+            pickup_location_id: this.pickup_location_id,
+            queue_position: this.queue_position,
+            status: this.status,
+            placed_at: this.placed_at,
+            expires_on: this.expires_on,
+            suspended_from: this.suspended_from,
+            suspended_until: this.suspended_until,
+            copy_id: this.book_copy_id,
+            trapped_at: this.trapped_at,
+            pickup_by: this.pickup_by,
+        }
+    }
+}
+
+/// Gets the hold queue for a title, or a patron's holds
+pub async fn get_list_holds(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<HoldBody<ListHoldsQuery>>,
+) -> Result<(StatusCode, Json<HoldsQuery>), Error> {
+    let connection_pool = &api_context.db;
+    let query = request.hold;
+
+    let page_size = page_size(query.page_size);
+    let cursor: Option<HoldCursor> = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let page = match (query.master_book_id, query.patron_id) {
+        (Some(master_book_id), _) => {
+            Db::get_hold_list_for_book(&master_book_id, page_size, cursor.as_ref(), connection_pool)
+                .await?
+        }
+        (None, Some(patron_id)) => {
+            Db::get_hold_list_for_patron(&patron_id, page_size, cursor.as_ref(), connection_pool)
+                .await?
+        }
+        (None, None) => {
+            return Err(Error::unprocessable_entity([(
+                "master_book_id",
+                "give either a master_book_id or a patron_id",
+            )]))
+        }
+    };
+    let next_cursor = page.next_cursor(|hold| HoldCursor {
+        queue_position: hold.queue_position,
+        placed_at: hold.placed_at,
+        id: hold.hold_id,
+    });
+    let holds = page.rows.iter().map(|hold| hold.to_hold()).collect();
+
+    Ok((StatusCode::OK, Json(HoldsQuery { holds, next_cursor })))
+}
+
+/// Places a hold at the back of a title's queue
+pub async fn place_hold(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<HoldBody<HoldQuery>>,
+) -> Result<(StatusCode, Json<Hold>), Error> {
+    let connection_pool = &api_context.db;
+
+    let hold = Holds::place(request.hold, connection_pool).await?;
+
+    Ok((StatusCode::OK, Json(hold.to_hold())))
+}
+
+/// Updates the pickup location, expiry or suspension of a hold
+pub async fn update_hold(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<HoldBody<HoldUpdateQuery>>,
+) -> Result<(StatusCode, Json<Hold>), Error> {
+    let connection_pool = &api_context.db;
+
+    let updated_hold = Db::update_hold(request.hold, connection_pool)
+        .await
+        .on_constraint("hold_pickup_location_id_fkey", |_| {
+            Error::unprocessable_entity([("pickup_location_id", "does not exist")])
+        })
+        .on_constraint("hold_suspension_order_check", |_| {
+            Error::unprocessable_entity([("suspended_until", "must not be before suspended_from")])
+        })
+        .on_constraint("hold_suspension_check", |_| {
+            Error::unprocessable_entity([("suspended_from", "is required to suspend a hold")])
+        })?
+        .ok_or(Error::NotFound)?;
+
+    Ok((StatusCode::OK, Json(updated_hold.to_hold())))
+}
+
+/// Moves a hold within its title's queue, returning the whole reordered queue
+pub async fn reorder_hold(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<HoldBody<ReorderHoldQuery>>,
+) -> Result<(StatusCode, Json<HoldsQuery>), Error> {
+    let connection_pool = &api_context.db;
+
+    let queue = Holds::reorder(
+        &request.hold.id,
+        request.hold.queue_position,
+        connection_pool,
+    )
+    .await?;
+    let holds = queue.iter().map(|hold| hold.to_hold()).collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(HoldsQuery {
+            holds,
+            next_cursor: None,
+        }),
+    ))
+}
+
+/// Expires the trapped holds nobody collected by their `pickup_by` date,
+/// passing each copy on to the next hold on its title, and returns the holds
+/// it expired. Safe to run as often as you like.
+pub async fn expire_holds(
+    State(api_context): State<ApiContext>,
+) -> Result<(StatusCode, Json<HoldsQuery>), Error> {
+    let connection_pool = &api_context.db;
+
+    let list = Holds::expire_uncollected(connection_pool).await?;
+    let holds = list.iter().map(|hold| hold.to_hold()).collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(HoldsQuery {
+            holds,
+            next_cursor: None,
+        }),
+    ))
+}
+
+/// Cancels a hold
+pub async fn cancel_hold(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<HoldBody<CancelHold>>,
+) -> Result<(StatusCode, Json<Hold>), Error> {
+    let connection_pool = &api_context.db;
+
+    let hold = Holds::cancel(&request.hold.id, connection_pool).await?;
+
+    Ok((StatusCode::OK, Json(hold.to_hold())))
+}
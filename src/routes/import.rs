@@ -0,0 +1,206 @@
+use axum::extract::{Multipart, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+
+use crate::catalog::import::{Imports, CSV_FIELDS};
+use crate::routes::{ApiContext, Error};
+
+/// What became of each record in an imported file
+#[derive(serde::Serialize, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub failed: usize,
+    pub records: Vec<ImportedRecord>,
+}
+
+/// One record of an import: its place in the file, counting from 1, and
+/// either the id of the book made from it or why none was
+#[derive(serde::Serialize)]
+pub struct ImportedRecord {
+    pub record: usize,
+    pub control_number: Option<String>,
+    pub title: Option<String>,
+    pub id: Option<Uuid>,
+    pub errors: Vec<String>,
+}
+
+impl ImportedRecord {
+    pub(crate) fn failed(errors: Vec<String>) -> Self {
+        Self {
+            record: 0,
+            control_number: None,
+            title: None,
+            id: None,
+            errors,
+        }
+    }
+}
+
+impl ImportReport {
+    pub(crate) fn push(&mut self, record: usize, mut imported: ImportedRecord) {
+        if imported.id.is_some() {
+            self.imported += 1;
+        } else {
+            self.failed += 1;
+        }
+        imported.record = record;
+        self.records.push(imported);
+    }
+}
+
+/// What a CSV row did, or would have done on a dry run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowAction {
+    Created,
+    Updated,
+    Skipped,
+    Failed,
+}
+
+/// What became of each row of a CSV import. Nothing is saved unless every
+/// row succeeds, and nothing at all on a dry run.
+#[derive(serde::Serialize, Default)]
+pub struct CsvImportReport {
+    pub dry_run: bool,
+    pub committed: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub rows: Vec<CsvImportRow>,
+}
+
+/// One row of a CSV import, numbered as a spreadsheet shows it with the
+/// header as row 1. `errors` has the same shape as a `422` response body's.
+#[derive(serde::Serialize)]
+pub struct CsvImportRow {
+    pub row: usize,
+    pub action: RowAction,
+    pub id: Option<Uuid>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub errors: HashMap<String, Vec<String>>,
+}
+
+impl CsvImportRow {
+    pub(crate) fn new(action: RowAction, id: Option<Uuid>) -> Self {
+        Self {
+            row: 0,
+            action,
+            id,
+            errors: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn failed(errors: HashMap<String, Vec<String>>) -> Self {
+        Self {
+            errors,
+            ..Self::new(RowAction::Failed, None)
+        }
+    }
+}
+
+impl CsvImportReport {
+    pub(crate) fn push(&mut self, row: usize, mut imported: CsvImportRow) {
+        match imported.action {
+            RowAction::Created => self.created += 1,
+            RowAction::Updated => self.updated += 1,
+            RowAction::Skipped => self.skipped += 1,
+            RowAction::Failed => self.failed += 1,
+        }
+        imported.row = row;
+        self.rows.push(imported);
+    }
+}
+
+/// CSV Import Query coming from Client. `?dry_run=true` reports what the
+/// import would do without saving anything. Each book field is read from the
+/// column named for it, unless a parameter names another, as in
+/// `?title=Book%20Title`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CsvImportQuery {
+    pub dry_run: bool,
+    pub columns: HashMap<String, String>,
+}
+
+impl CsvImportQuery {
+    pub fn from_params(params: HashMap<String, String>) -> Result<Self, Error> {
+        let mut query = Self::default();
+        let mut errors = Vec::new();
+
+        for (key, value) in params {
+            if key == "dry_run" {
+                match value.parse() {
+                    Ok(dry_run) => query.dry_run = dry_run,
+                    Err(_) => errors.push((key, "must be true or false".to_string())),
+                }
+            } else if CSV_FIELDS.contains(&key.as_str()) {
+                query.columns.insert(key, value);
+            } else {
+                errors.push((key, "is not a book field".to_string()));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(query)
+        } else {
+            Err(Error::unprocessable_entity(errors))
+        }
+    }
+}
+
+/// Reads the `file` part of a multipart upload
+async fn read_file(mut multipart: Multipart) -> Result<Vec<u8>, Error> {
+    let multipart_error = |error: axum::extract::multipart::MultipartError| -> Error {
+        Error::unprocessable_entity([("file", error.to_string())])
+    };
+
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() == Some("file") {
+            return Ok(field.bytes().await.map_err(multipart_error)?.to_vec());
+        }
+    }
+
+    Err(Error::unprocessable_entity([("file", "is required")]))
+}
+
+/// Imports the books in an uploaded MARC file, sent as the `file` part of a
+/// multipart form, in ISO 2709 or MARCXML
+pub async fn import_marc(
+    State(api_context): State<ApiContext>,
+    multipart: Multipart,
+) -> Result<(StatusCode, Json<ImportReport>), Error> {
+    let connection_pool = &api_context.db;
+
+    let file = read_file(multipart).await?;
+    let report = Imports::marc(&file, connection_pool).await?;
+
+    Ok((StatusCode::OK, Json(report)))
+}
+
+/// Imports or updates the books in an uploaded CSV file, sent as the `file`
+/// part of a multipart form. A row with the ISBN or LCCN of a book already
+/// catalogued fills in that book's fields instead of making a new one.
+///
+/// Answers `422` with the report if any row of a real import failed, in
+/// which case nothing was saved.
+pub async fn import_csv(
+    State(api_context): State<ApiContext>,
+    Query(params): Query<HashMap<String, String>>,
+    multipart: Multipart,
+) -> Result<(StatusCode, Json<CsvImportReport>), Error> {
+    let connection_pool = &api_context.db;
+    let query = CsvImportQuery::from_params(params)?;
+
+    let file = read_file(multipart).await?;
+    let report = Imports::csv(&file, query, connection_pool).await?;
+    let status = if report.failed > 0 && !report.dry_run {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+
+    Ok((status, Json(report)))
+}
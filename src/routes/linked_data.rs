@@ -0,0 +1,49 @@
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+use crate::catalog::linked_data;
+use crate::routes::Book;
+
+/// The linked data a book can be described as
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkedDataFormat {
+    /// A schema.org `Book` in JSON-LD
+    JsonLd,
+    /// A simple Dublin Core record in XML
+    DublinCore,
+}
+
+impl LinkedDataFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::JsonLd => "application/ld+json",
+            Self::DublinCore => "application/dc+xml",
+        }
+    }
+
+    /// The linked data format served as `media_type`, if it is one
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        [Self::JsonLd, Self::DublinCore]
+            .into_iter()
+            .find(|format| format.content_type().eq_ignore_ascii_case(media_type))
+    }
+}
+
+/// A single book described in `format`
+pub(crate) fn book_linked_data(format: LinkedDataFormat, book: &Book) -> Response {
+    let body = match format {
+        LinkedDataFormat::JsonLd => linked_data::schema_org(book).to_string(),
+        LinkedDataFormat::DublinCore => format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}\n",
+            linked_data::dublin_core(book)
+        ),
+    };
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, format.content_type())],
+        body,
+    )
+        .into_response()
+}
@@ -0,0 +1,347 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+
+use crate::circulation::policy;
+use crate::db::Db;
+use crate::routes::{default_item_type, ApiContext, Error, ResultExt};
+
+/// Used to namespace our JSON query
+/// { "policy": <T> }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct LoanPolicyBody<T = LoanPolicyQuery> {
+    pub policy: T,
+}
+
+/// The base return structure for a loan policy to the client. A missing key
+/// matches any patron category, item type or location.
+///
+/// Overdue loans accrue `daily_fine_cents` a day up to `max_fine_cents`, and
+/// patrons owing more than `fine_threshold_cents` may not borrow.
+#[derive(serde::Serialize)]
+pub struct LoanPolicy {
+    pub id: Uuid,
+    pub patron_category: Option<String>,
+    pub item_type: Option<String>,
+    pub location_id: Option<Uuid>,
+    pub loan_period_days: i32,
+    pub max_renewals: i32,
+    pub max_items: i32,
+    pub daily_fine_cents: i32,
+    pub max_fine_cents: Option<i32>,
+    pub fine_threshold_cents: Option<i32>,
+}
+
+/// Query coming from Client
+#[derive(serde::Deserialize, Clone)]
+pub struct LoanPolicyQuery {
+    pub patron_category: Option<String>,
+    pub item_type: Option<String>,
+    pub location_id: Option<Uuid>,
+    pub loan_period_days: i32,
+    pub max_renewals: i32,
+    pub max_items: i32,
+    #[serde(default)]
+    pub daily_fine_cents: i32,
+    pub max_fine_cents: Option<i32>,
+    pub fine_threshold_cents: Option<i32>,
+}
+
+/// Array Query coming from Client
+#[derive(serde::Serialize, Default)]
+pub struct LoanPoliciesQuery {
+    pub policies: Vec<LoanPolicy>,
+}
+
+/// Update Query coming from Client. The keys a policy matches on are fixed;
+/// to move a rule, delete it and create a new one.
+#[derive(serde::Deserialize, Clone)]
+pub struct LoanPolicyUpdateQuery {
+    pub id: Uuid,
+    pub loan_period_days: Option<i32>,
+    pub max_renewals: Option<i32>,
+    pub max_items: Option<i32>,
+    pub daily_fine_cents: Option<i32>,
+    pub max_fine_cents: Option<i32>,
+    pub fine_threshold_cents: Option<i32>,
+}
+
+/// Get Loan Policy Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct GetLoanPolicyQuery {
+    pub id: Uuid,
+}
+
+/// Delete Loan Policy Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct DeleteLoanPolicy {
+    pub id: Uuid,
+}
+
+/// Explain Query coming from Client.
+///
+/// Either name a patron and a copy and let their category, item type and
+/// location be looked up, or give the keys directly.
+#[derive(serde::Deserialize)]
+pub struct ExplainLoanPolicyQuery {
+    pub patron_id: Option<Uuid>,
+    pub book_copy_id: Option<Uuid>,
+    pub patron_category: Option<String>,
+    pub item_type: Option<String>,
+    pub location_id: Option<Uuid>,
+}
+
+/// A policy that matched the keys being explained
+#[derive(serde::Serialize)]
+pub struct LoanPolicyCandidate {
+    pub policy: LoanPolicy,
+    /// Levels between the copy's location and the policy's, if it names one
+    pub location_distance: Option<i32>,
+    pub applies: bool,
+}
+
+/// Which rule applies to a loan, and every rule it beat
+#[derive(serde::Serialize)]
+pub struct LoanPolicyExplanation {
+    pub patron_category: String,
+    pub item_type: String,
+    pub location_id: Option<Uuid>,
+    pub policy: Option<LoanPolicy>,
+    pub candidates: Vec<LoanPolicyCandidate>,
+}
+
+/// Database Object to be cast into a LoanPolicy
+#[derive(Clone)]
+pub struct LoanPolicyFromQuery {
+    pub loan_policy_id: Uuid,
+    pub patron_category: Option<String>,
+    pub item_type: Option<String>,
+    pub location_id: Option<Uuid>,
+    pub loan_period_days: i32,
+    pub max_renewals: i32,
+    pub max_items: i32,
+    pub daily_fine_cents: i32,
+    pub max_fine_cents: Option<i32>,
+    pub fine_threshold_cents: Option<i32>,
+}
+
+impl LoanPolicyFromQuery {
+    fn to_loan_policy(&self) -> LoanPolicy {
+        let this = self.to_owned();
+        LoanPolicy {
+            id: this.loan_policy_id,
+            patron_category: this.patron_category,
+            item_type: this.item_type,
+            location_id: this.location_id,
+            loan_period_days: this.loan_period_days,
+            max_renewals: this.max_renewals,
+            max_items: this.max_items,
+            daily_fine_cents: this.daily_fine_cents,
+            max_fine_cents: this.max_fine_cents,
+            fine_threshold_cents: this.fine_threshold_cents,
+        }
+    }
+}
+
+/// Database Object for a policy matching a loan, to be cast into a
+/// LoanPolicyCandidate
+#[derive(Clone, Debug)]
+pub struct LoanPolicyMatchFromQuery {
+    pub loan_policy_id: Uuid,
+    pub patron_category: Option<String>,
+    pub item_type: Option<String>,
+    pub location_id: Option<Uuid>,
+    pub loan_period_days: i32,
+    pub max_renewals: i32,
+    pub max_items: i32,
+    pub daily_fine_cents: i32,
+    pub max_fine_cents: Option<i32>,
+    pub fine_threshold_cents: Option<i32>,
+    pub location_distance: Option<i32>,
+}
+
+impl LoanPolicyMatchFromQuery {
+    fn to_loan_policy(&self) -> LoanPolicy {
+        let this = self.to_owned();
+        LoanPolicy {
+            id: this.loan_policy_id,
+            patron_category: this.patron_category,
+            item_type: this.item_type,
+            location_id: this.location_id,
+            loan_period_days: this.loan_period_days,
+            max_renewals: this.max_renewals,
+            max_items: this.max_items,
+            daily_fine_cents: this.daily_fine_cents,
+            max_fine_cents: this.max_fine_cents,
+            fine_threshold_cents: this.fine_threshold_cents,
+        }
+    }
+}
+
+/// Maps the constraint violations a policy write can hit into `422`s
+fn on_loan_policy_constraints<T>(result: Result<T, sqlx::Error>) -> Result<T, Error> {
+    result
+        .on_constraint("loan_policy_key", |_| {
+            Error::unprocessable_entity([(
+                "policy",
+                "a policy already exists for this patron category, item type and location",
+            )])
+        })
+        .on_constraint("loan_policy_patron_category_fkey", |_| {
+            Error::unprocessable_entity([("patron_category", "is not a known patron category")])
+        })
+        .on_constraint("loan_policy_item_type_fkey", |_| {
+            Error::unprocessable_entity([("item_type", "is not a known item type")])
+        })
+        .on_constraint("loan_policy_location_id_fkey", |_| {
+            Error::unprocessable_entity([("location_id", "does not exist")])
+        })
+        .on_constraint("loan_policy_loan_period_days_check", |_| {
+            Error::unprocessable_entity([("loan_period_days", "must be at least one day")])
+        })
+        .on_constraint("loan_policy_max_renewals_check", |_| {
+            Error::unprocessable_entity([("max_renewals", "must not be negative")])
+        })
+        .on_constraint("loan_policy_max_items_check", |_| {
+            Error::unprocessable_entity([("max_items", "must be at least one")])
+        })
+        .on_constraint("loan_policy_daily_fine_cents_check", |_| {
+            Error::unprocessable_entity([("daily_fine_cents", "must not be negative")])
+        })
+        .on_constraint("loan_policy_max_fine_cents_check", |_| {
+            Error::unprocessable_entity([("max_fine_cents", "must not be negative")])
+        })
+        .on_constraint("loan_policy_fine_threshold_cents_check", |_| {
+            Error::unprocessable_entity([("fine_threshold_cents", "must not be negative")])
+        })
+}
+
+/// Gets every loan policy
+pub async fn get_list_loan_policies(
+    State(api_context): State<ApiContext>,
+) -> Result<(StatusCode, Json<LoanPoliciesQuery>), Error> {
+    let connection_pool = &api_context.db;
+
+    let list = Db::get_loan_policy_list(connection_pool).await?;
+    let policies = list.iter().map(|policy| policy.to_loan_policy()).collect();
+
+    Ok((StatusCode::OK, Json(LoanPoliciesQuery { policies })))
+}
+
+/// Get a specific loan policy
+pub async fn get_loan_policy(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LoanPolicyBody<GetLoanPolicyQuery>>,
+) -> Result<(StatusCode, Json<LoanPolicy>), Error> {
+    let connection_pool = &api_context.db;
+
+    let policy = Db::get_loan_policy(&request.policy.id, connection_pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok((StatusCode::OK, Json(policy.to_loan_policy())))
+}
+
+/// Creates a loan policy
+pub async fn create_loan_policy(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LoanPolicyBody<LoanPolicyQuery>>,
+) -> Result<(StatusCode, String), Error> {
+    let connection_pool = &api_context.db;
+
+    let policy_id =
+        on_loan_policy_constraints(Db::create_loan_policy(request.policy, connection_pool).await)?;
+
+    Ok((StatusCode::OK, policy_id.to_string()))
+}
+
+/// Updates the limits of a loan policy
+pub async fn update_loan_policy(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LoanPolicyBody<LoanPolicyUpdateQuery>>,
+) -> Result<(StatusCode, Json<LoanPolicy>), Error> {
+    let connection_pool = &api_context.db;
+
+    let updated_policy =
+        on_loan_policy_constraints(Db::update_loan_policy(request.policy, connection_pool).await)?
+            .ok_or(Error::NotFound)?;
+
+    Ok((StatusCode::OK, Json(updated_policy.to_loan_policy())))
+}
+
+/// Deletes a loan policy
+pub async fn delete_loan_policy(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LoanPolicyBody<DeleteLoanPolicy>>,
+) -> Result<(StatusCode, String), Error> {
+    let connection_pool = &api_context.db;
+
+    let result = Db::delete_loan_policy(request.policy.id, connection_pool).await?;
+
+    Ok((StatusCode::OK, result.rows_affected().to_string()))
+}
+
+/// Explains which loan policy applies to a patron borrowing a copy
+pub async fn explain_loan_policy(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LoanPolicyBody<ExplainLoanPolicyQuery>>,
+) -> Result<(StatusCode, Json<LoanPolicyExplanation>), Error> {
+    let connection_pool = &api_context.db;
+    let query = request.policy;
+
+    let patron_category = match (query.patron_id, query.patron_category) {
+        (Some(patron_id), _) => {
+            Db::get_patron(&patron_id, connection_pool)
+                .await?
+                .ok_or_else(|| Error::unprocessable_entity([("patron_id", "does not exist")]))?
+                .patron_category
+        }
+        (None, Some(patron_category)) => patron_category,
+        (None, None) => {
+            return Err(Error::unprocessable_entity([(
+                "patron_category",
+                "give either a patron_id or a patron_category",
+            )]))
+        }
+    };
+
+    let (item_type, location_id) = match query.book_copy_id {
+        Some(book_copy_id) => {
+            let copy = Db::get_book_copy(&book_copy_id, connection_pool)
+                .await?
+                .ok_or_else(|| Error::unprocessable_entity([("book_copy_id", "does not exist")]))?;
+            (copy.item_type, copy.location_id)
+        }
+        None => (
+            query.item_type.unwrap_or_else(default_item_type),
+            query.location_id,
+        ),
+    };
+
+    let matches =
+        Db::get_loan_policy_matches(&patron_category, &item_type, location_id, connection_pool)
+            .await?;
+    let ranked = policy::rank(matches);
+
+    let candidates = ranked
+        .iter()
+        .enumerate()
+        .map(|(index, candidate)| LoanPolicyCandidate {
+            policy: candidate.to_loan_policy(),
+            location_distance: candidate.location_distance,
+            applies: index == 0,
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(LoanPolicyExplanation {
+            patron_category,
+            item_type,
+            location_id,
+            policy: ranked.first().map(|candidate| candidate.to_loan_policy()),
+            candidates,
+        }),
+    ))
+}
@@ -0,0 +1,206 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+
+use crate::db::Db;
+use crate::routes::{ApiContext, Error, ResultExt};
+
+/// Used to namespace our JSON query
+/// { "location": <T> }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct LocationBody<T = LocationQuery> {
+    pub location: T,
+}
+
+/// The base return structure for a location to the client.
+///
+/// A location without a parent is a branch; floors and shelf ranges hang off
+/// of it as children.
+#[derive(serde::Serialize)]
+pub struct Location {
+    pub id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub name: String,
+    pub floor: Option<String>,
+    pub shelf_start: Option<String>,
+    pub shelf_end: Option<String>,
+}
+
+/// Query coming from Client
+#[derive(serde::Deserialize, Clone)]
+pub struct LocationQuery {
+    pub parent_location_id: Option<Uuid>,
+    pub name: String,
+    pub floor: Option<String>,
+    pub shelf_start: Option<String>,
+    pub shelf_end: Option<String>,
+}
+
+/// Array Query coming from Client
+#[derive(serde::Serialize, Default)]
+pub struct LocationsQuery {
+    pub locations: Vec<Location>,
+}
+
+/// List Locations Query coming from Client. Leaving out the parent lists the
+/// branches.
+#[derive(serde::Deserialize, Default)]
+pub struct ListLocationsQuery {
+    pub parent_location_id: Option<Uuid>,
+}
+
+/// Update Query coming from Client. Fields left out are kept; a location
+/// is moved back to the top level with `clear_parent`.
+#[derive(serde::Deserialize, Clone)]
+pub struct LocationUpdateQuery {
+    pub id: Uuid,
+    pub parent_location_id: Option<Uuid>,
+    #[serde(default)]
+    pub clear_parent: bool,
+    pub name: Option<String>,
+    pub floor: Option<String>,
+    pub shelf_start: Option<String>,
+    pub shelf_end: Option<String>,
+}
+
+/// Get Location Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct GetLocationQuery {
+    pub id: Uuid,
+}
+
+/// Delete Location Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct DeleteLocation {
+    pub id: Uuid,
+}
+
+/// Database Object to be cast into a Location
+#[derive(Clone)]
+pub struct LocationFromQuery {
+    pub location_id: Uuid,
+    pub parent_location_id: Option<Uuid>,
+    pub name: String,
+    pub floor: Option<String>,
+    pub shelf_start: Option<String>,
+    pub shelf_end: Option<String>,
+}
+
+impl LocationFromQuery {
+    pub(crate) fn to_location(&self) -> Location {
+        let this = self.to_owned();
+        Location {
+            id: this.location_id,
+            parent_id: this.parent_location_id,
+            name: this.name,
+            floor: this.floor,
+            shelf_start: this.shelf_start,
+            shelf_end: this.shelf_end,
+        }
+    }
+}
+
+/// Maps the constraint violations a location write can hit into `422`s
+fn on_location_constraints<T>(result: Result<T, sqlx::Error>) -> Result<T, Error> {
+    result
+        .on_constraint("location_parent_location_id_fkey", |_| {
+            Error::unprocessable_entity([("parent_location_id", "does not exist")])
+        })
+        .on_constraint("location_parent_check", |_| {
+            Error::unprocessable_entity([("parent_location_id", "cannot be the location itself")])
+        })
+}
+
+/// Gets the branches, or the children of a location
+pub async fn get_list_locations(
+    State(api_context): State<ApiContext>,
+    request: Option<Json<LocationBody<ListLocationsQuery>>>,
+) -> Result<(StatusCode, Json<LocationsQuery>), Error> {
+    let connection_pool = &api_context.db;
+    let query = request.map(|Json(body)| body.location).unwrap_or_default();
+
+    let list = Db::get_location_list(query.parent_location_id, connection_pool).await?;
+    let locations = list.iter().map(|location| location.to_location()).collect();
+
+    Ok((StatusCode::OK, Json(LocationsQuery { locations })))
+}
+
+/// Get a specific location
+pub async fn get_location(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LocationBody<GetLocationQuery>>,
+) -> Result<(StatusCode, Json<Location>), Error> {
+    let connection_pool = &api_context.db;
+
+    let location = Db::get_location(&request.location.id, connection_pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok((StatusCode::OK, Json(location.to_location())))
+}
+
+/// Creates a location
+pub async fn create_location(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LocationBody<LocationQuery>>,
+) -> Result<(StatusCode, String), Error> {
+    let connection_pool = &api_context.db;
+
+    let location_id =
+        on_location_constraints(Db::create_location(request.location, connection_pool).await)?;
+
+    Ok((StatusCode::OK, location_id.to_string()))
+}
+
+/// Updates a location. Moving a location underneath one of its own children
+/// is refused so the hierarchy stays a tree; the move is checked and made in
+/// one transaction, holding the rows a concurrent move could loop through.
+pub async fn update_location(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LocationBody<LocationUpdateQuery>>,
+) -> Result<(StatusCode, Json<Location>), Error> {
+    let connection_pool = &api_context.db;
+    let location = request.location;
+    if location.clear_parent && location.parent_location_id.is_some() {
+        return Err(Error::unprocessable_entity([(
+            "clear_parent",
+            "cannot be given with parent_location_id",
+        )]));
+    }
+    let mut transaction = connection_pool.begin().await?;
+
+    if let Some(parent_id) = &location.parent_location_id {
+        Db::lock_location_move(&location.id, parent_id, &mut transaction).await?;
+        if Db::location_is_within(parent_id, &location.id, &mut transaction).await? {
+            return Err(Error::unprocessable_entity([(
+                "parent_location_id",
+                "cannot be the location itself or one of its children",
+            )]));
+        }
+    }
+
+    let updated_location =
+        on_location_constraints(Db::update_location(location, &mut transaction).await)?
+            .ok_or(Error::NotFound)?;
+
+    transaction.commit().await?;
+
+    Ok((StatusCode::OK, Json(updated_location.to_location())))
+}
+
+/// Deletes a location that no longer has children
+pub async fn delete_location(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<LocationBody<DeleteLocation>>,
+) -> Result<(StatusCode, String), Error> {
+    let connection_pool = &api_context.db;
+
+    let result = Db::delete_location(request.location.id, connection_pool)
+        .await
+        .on_constraint("location_parent_location_id_fkey", |_| {
+            Error::unprocessable_entity([("id", "location still has child locations")])
+        })?;
+
+    Ok((StatusCode::OK, result.rows_affected().to_string()))
+}
@@ -0,0 +1,261 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+use crate::catalog::merge::{Merges, DEFAULT_DUPLICATE_THRESHOLD};
+use crate::db::Db;
+use crate::routes::pagination::{decode_cursor, page_size};
+use crate::routes::{ApiContext, Book, BookFromQuery, Error};
+
+/// Used to namespace our JSON query
+/// { "merge": <T> }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct MergeBody<T = MergeQuery> {
+    pub merge: T,
+}
+
+/// A pair of records that look like the same work. `reason` is `isbn` or
+/// `lccn` when they share one, scoring 1, or `title_author` when their titles
+/// and authors are merely alike, scoring how alike from 0 to 1.
+#[derive(serde::Serialize)]
+pub struct Duplicate {
+    pub reason: String,
+    pub score: f32,
+    pub book: Book,
+    pub duplicate: Book,
+}
+
+/// Array of candidate duplicates to the client, one page at a time: those
+/// sharing an ISBN, then an LCCN, then the rest likeliest first.
+/// `next_cursor` fetches the following page and is `null` on the last one.
+#[derive(serde::Serialize, Default)]
+pub struct DuplicatesQuery {
+    pub duplicates: Vec<Duplicate>,
+    pub next_cursor: Option<String>,
+}
+
+/// Duplicates Query coming from Client as
+/// `?master_book_id=&threshold=&page_size=&cursor=`, all optional
+#[derive(serde::Deserialize)]
+pub struct ListDuplicatesQuery {
+    pub master_book_id: Option<Uuid>,
+    pub threshold: Option<f32>,
+    pub page_size: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+/// Where a page of duplicates ended: the last pair's reason, score and ids
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DuplicateCursor {
+    pub reason: String,
+    pub score: f32,
+    pub master_book_id: Uuid,
+    pub duplicate_master_book_id: Uuid,
+}
+
+/// Merge Query coming from Client: `merge_id` is merged into `keep_id`
+#[derive(serde::Deserialize)]
+pub struct MergeQuery {
+    pub keep_id: Uuid,
+    pub merge_id: Uuid,
+    pub merged_by: Option<String>,
+}
+
+/// Merge History Query coming from Client as
+/// `?master_book_id=&page_size=&cursor=`, the paging optional
+#[derive(serde::Deserialize)]
+pub struct ListMergesQuery {
+    pub master_book_id: Uuid,
+    pub page_size: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+/// Where a page of merges ended: the last merge's time and id
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BookMergeCursor {
+    #[serde(with = "time::serde::rfc3339")]
+    pub merged_at: OffsetDateTime,
+    pub id: Uuid,
+}
+
+/// The base return structure for a merge to the client. `merged_record` is
+/// the merged book as it stood just before it was removed.
+#[derive(serde::Serialize)]
+pub struct BookMerge {
+    pub id: Uuid,
+    pub kept_id: Uuid,
+    pub merged_id: Uuid,
+    pub merged_record: serde_json::Value,
+    pub copies_moved: i32,
+    pub holds_moved: i32,
+    pub holds_cancelled: i32,
+    pub merged_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub merged_at: OffsetDateTime,
+}
+
+/// Array of merges to the client, oldest first, one page at a time.
+/// `next_cursor` fetches the following page and is `null` on the last one.
+#[derive(serde::Serialize, Default)]
+pub struct BookMergesQuery {
+    pub merges: Vec<BookMerge>,
+    pub next_cursor: Option<String>,
+}
+
+/// Database Object for a candidate pair
+#[derive(Clone)]
+pub struct DuplicateFromQuery {
+    pub master_book_id: Uuid,
+    pub duplicate_master_book_id: Uuid,
+    pub reason: String,
+    pub score: f32,
+}
+
+/// Database Object to be cast into a BookMerge
+#[derive(Clone)]
+pub struct BookMergeFromQuery {
+    pub master_book_merge_id: Uuid,
+    pub kept_master_book_id: Uuid,
+    pub merged_master_book_id: Uuid,
+    pub merged_record: serde_json::Value,
+    pub copies_moved: i32,
+    pub holds_moved: i32,
+    pub holds_cancelled: i32,
+    pub merged_by: Option<String>,
+    pub merged_at: OffsetDateTime,
+}
+
+impl BookMergeFromQuery {
+    fn to_book_merge(&self) -> BookMerge {
+        let this = self.to_owned();
+        BookMerge {
+            id: this.master_book_merge_id,
+            kept_id: this.kept_master_book_id,
+            merged_id: this.merged_master_book_id,
+            merged_record: this.merged_record,
+            copies_moved: this.copies_moved,
+            holds_moved: this.holds_moved,
+            holds_cancelled: this.holds_cancelled,
+            merged_by: this.merged_by,
+            merged_at: this.merged_at,
+        }
+    }
+}
+
+/// Lists a page of pairs of books that may be duplicates: those sharing an
+/// ISBN-13 or normalized LCCN, and those whose titles and authors are both at
+/// least `threshold` alike. Given a `master_book_id`, lists only the
+/// duplicates of that book.
+pub async fn get_list_duplicates(
+    State(api_context): State<ApiContext>,
+    Query(query): Query<ListDuplicatesQuery>,
+) -> Result<(StatusCode, Json<DuplicatesQuery>), Error> {
+    let connection_pool = &api_context.db;
+
+    let threshold = query.threshold.unwrap_or(DEFAULT_DUPLICATE_THRESHOLD);
+    if threshold <= 0.0 || threshold > 1.0 {
+        return Err(Error::unprocessable_entity([(
+            "threshold",
+            "must be more than 0 and at most 1",
+        )]));
+    }
+    let page_size = page_size(query.page_size);
+    let cursor: Option<DuplicateCursor> = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let page = Db::get_duplicate_list(
+        query.master_book_id,
+        threshold,
+        page_size,
+        cursor.as_ref(),
+        connection_pool,
+    )
+    .await?;
+    let next_cursor = page.next_cursor(|pair| DuplicateCursor {
+        reason: pair.reason.to_owned(),
+        score: pair.score,
+        master_book_id: pair.master_book_id,
+        duplicate_master_book_id: pair.duplicate_master_book_id,
+    });
+    let ids: Vec<Uuid> = page
+        .rows
+        .iter()
+        .flat_map(|pair| [pair.master_book_id, pair.duplicate_master_book_id])
+        .collect();
+    let books: HashMap<Uuid, BookFromQuery> = Db::get_books_by_ids(&ids, connection_pool)
+        .await?
+        .into_iter()
+        .map(|book| (book.master_book_id, book))
+        .collect();
+
+    let duplicates = page
+        .rows
+        .iter()
+        .filter_map(|pair| {
+            Some(Duplicate {
+                reason: pair.reason.to_owned(),
+                score: pair.score,
+                book: books.get(&pair.master_book_id)?.to_book(),
+                duplicate: books.get(&pair.duplicate_master_book_id)?.to_book(),
+            })
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(DuplicatesQuery {
+            duplicates,
+            next_cursor,
+        }),
+    ))
+}
+
+/// Merges one book into another, moving its copies and holds across
+pub async fn merge_books(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<MergeBody<MergeQuery>>,
+) -> Result<(StatusCode, Json<BookMerge>), Error> {
+    let connection_pool = &api_context.db;
+
+    let merge = Merges::merge(request.merge, connection_pool).await?;
+
+    Ok((StatusCode::OK, Json(merge.to_book_merge())))
+}
+
+/// Gets a page of the merges folded into a book, oldest first
+pub async fn get_list_merges(
+    State(api_context): State<ApiContext>,
+    Query(query): Query<ListMergesQuery>,
+) -> Result<(StatusCode, Json<BookMergesQuery>), Error> {
+    let connection_pool = &api_context.db;
+
+    let page_size = page_size(query.page_size);
+    let cursor: Option<BookMergeCursor> = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let page = Db::get_book_merge_list(
+        &query.master_book_id,
+        page_size,
+        cursor.as_ref(),
+        connection_pool,
+    )
+    .await?;
+    let next_cursor = page.next_cursor(|merge| BookMergeCursor {
+        merged_at: merge.merged_at,
+        id: merge.master_book_merge_id,
+    });
+    let merges = page
+        .rows
+        .iter()
+        .map(|merge| merge.to_book_merge())
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(BookMergesQuery {
+            merges,
+            next_cursor,
+        }),
+    ))
+}
@@ -1,7 +1,42 @@
+mod account;
 mod book;
+mod calendar;
+mod citation;
+mod circulation;
+mod copy;
+mod enrichment;
 mod error;
+mod export;
+mod hold;
+mod import;
+mod linked_data;
+mod loan_policy;
+mod location;
+mod merge;
+mod oai;
+mod opds;
+mod patron;
+pub mod pagination;
 mod server;
+mod sru;
 
+pub use account::*;
 pub use book::*;
+pub use calendar::*;
+pub use citation::*;
+pub use circulation::*;
+pub use copy::*;
+pub use enrichment::*;
 pub use error::*;
+pub use export::*;
+pub use hold::*;
+pub use import::*;
+pub use linked_data::*;
+pub use loan_policy::*;
+pub use location::*;
+pub use merge::*;
+pub use oai::*;
+pub use opds::*;
+pub use patron::*;
 pub use server::*;
+pub use sru::*;
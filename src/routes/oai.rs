@@ -0,0 +1,89 @@
+use axum::extract::{Query, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Form;
+use sqlx::types::Uuid;
+use time::{Date, OffsetDateTime};
+
+use crate::catalog::oai::OaiPmh;
+use crate::routes::{ApiContext, BookFromQuery, Error};
+
+/// Database Object to be cast into an OAI-PMH record: a book and when it
+/// last changed. A book merged away is `deleted`, with only its id and when
+/// it was merged.
+#[derive(Clone)]
+pub struct OaiBookFromQuery {
+    pub master_book_id: Uuid,
+    pub author: Option<String>,
+    pub title: Option<String>,
+    pub lccn: Option<String>,
+    pub lccn_normalized: Option<String>,
+    pub isbn: Option<String>,
+    pub isbn_13: Option<String>,
+    pub publish_date: Option<String>,
+    pub published_on: Option<Date>,
+    pub published_precision: Option<String>,
+    pub published_approximate: bool,
+    pub datestamp: OffsetDateTime,
+    pub deleted: bool,
+}
+
+impl OaiBookFromQuery {
+    pub(crate) fn to_book_from_query(&self) -> BookFromQuery {
+        let this = self.to_owned();
+        BookFromQuery {
+            master_book_id: this.master_book_id,
+            author: this.author,
+            title: this.title,
+            lccn: this.lccn,
+            lccn_normalized: this.lccn_normalized,
+            isbn: this.isbn,
+            isbn_13: this.isbn_13,
+            publish_date: this.publish_date,
+            published_on: this.published_on,
+            published_precision: this.published_precision,
+            published_approximate: this.published_approximate,
+        }
+    }
+}
+
+/// OAI-PMH 2.0 requests sent as a query string
+pub async fn oai_pmh(
+    State(api_context): State<ApiContext>,
+    Query(arguments): Query<Vec<(String, String)>>,
+) -> Result<Response, Error> {
+    respond(&api_context, &arguments).await
+}
+
+/// OAI-PMH 2.0 requests sent as a form, as the protocol allows for requests
+/// too long for a URL
+pub async fn oai_pmh_form(
+    State(api_context): State<ApiContext>,
+    Form(arguments): Form<Vec<(String, String)>>,
+) -> Result<Response, Error> {
+    respond(&api_context, &arguments).await
+}
+
+/// Answers with the protocol's XML whatever happens short of a database
+/// failure. OAI-PMH reports its own errors inside a `200`.
+async fn respond(
+    api_context: &ApiContext,
+    arguments: &[(String, String)],
+) -> Result<Response, Error> {
+    let config = &api_context.config;
+
+    let provider = OaiPmh {
+        base_url: &format!("{}/api/oai", config.base_url),
+        repository_identifier: &config.repository_identifier,
+        admin_email: &config.admin_email,
+    };
+    let body = provider.respond(arguments, &api_context.db).await?;
+
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/xml; charset=utf-8")],
+        body,
+    )
+        .into_response())
+}
@@ -0,0 +1,135 @@
+use axum::extract::{Query, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+use crate::catalog::opds::{
+    Opds, SearchPage, ACQUISITION_FEED_TYPE, NAVIGATION_FEED_TYPE, OPEN_SEARCH_TYPE,
+};
+use crate::catalog::search::{prefix_query, DEFAULT_SEARCH_LIMIT, MAX_SEARCH_LIMIT};
+use crate::db::Db;
+use crate::routes::{ApiContext, Book, BookCursor, Error, ListBooksQuery};
+
+/// OPDS Search Query coming from Client, as the OpenSearch template fills it
+/// in. `startIndex` counts from 1.
+#[derive(serde::Deserialize)]
+pub struct OpdsSearchQuery {
+    pub q: String,
+    #[serde(rename = "startIndex")]
+    pub start_index: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// The root of the OPDS catalog: a navigation feed of the ways to browse it
+pub async fn opds_root(State(api_context): State<ApiContext>) -> Response {
+    let feed = opds(&api_context).navigation();
+
+    feed_response(NAVIGATION_FEED_TYPE, feed)
+}
+
+/// A page of the book list as an acquisition feed. It takes the same
+/// parameters as `/api/books/list`, and links on to the next page by its
+/// cursor.
+pub async fn opds_books(
+    State(api_context): State<ApiContext>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Result<Response, Error> {
+    let connection_pool = &api_context.db;
+    let query = ListBooksQuery::from_params(params.iter().cloned().collect::<HashMap<_, _>>())?;
+
+    let page = Db::get_book_list(&query, connection_pool).await?;
+    let next_cursor = page.next_cursor(|book| BookCursor {
+        sort: query.sort,
+        order: query.order,
+        value: query.sort.value_of(book),
+        id: book.master_book_id,
+    });
+    let books = page.rows.iter().map(|book| book.to_book()).collect();
+    let books = with_datestamps(books, connection_pool).await?;
+
+    let feed = opds(&api_context).books(&params, next_cursor.as_deref(), &books);
+
+    Ok(feed_response(ACQUISITION_FEED_TYPE, feed))
+}
+
+/// Searches titles and authors as `/api/books/search` does, answering with
+/// an acquisition feed of a page of the matches, best first
+pub async fn opds_search(
+    State(api_context): State<ApiContext>,
+    Query(query): Query<OpdsSearchQuery>,
+) -> Result<Response, Error> {
+    let connection_pool = &api_context.db;
+
+    let tsquery = prefix_query(&query.q)
+        .ok_or_else(|| Error::unprocessable_entity([("q", "must contain a word to search for")]))?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+    let start_index = query.start_index.unwrap_or(1);
+    if start_index < 1 {
+        return Err(Error::unprocessable_entity([(
+            "startIndex",
+            "must be at least 1",
+        )]));
+    }
+
+    let list = Db::search_books(&tsquery, start_index - 1, limit, connection_pool).await?;
+    let total_results = Db::count_search_results(&tsquery, connection_pool).await?;
+    let books = list
+        .iter()
+        .map(|book| book.to_book_search_result().book)
+        .collect();
+    let books = with_datestamps(books, connection_pool).await?;
+
+    let page = SearchPage {
+        start_index,
+        items_per_page: limit,
+        total_results,
+    };
+    let feed = opds(&api_context).search_results(&query.q, &page, &books);
+
+    Ok(feed_response(ACQUISITION_FEED_TYPE, feed))
+}
+
+/// The OpenSearch description the feeds link to for searching
+pub async fn opds_open_search(State(api_context): State<ApiContext>) -> Response {
+    let description = opds(&api_context).open_search_description();
+
+    feed_response(OPEN_SEARCH_TYPE, description)
+}
+
+/// The feeds, linking back through the configured base URL
+fn opds(api_context: &ApiContext) -> Opds<'_> {
+    Opds {
+        base_url: &api_context.config.base_url,
+        updated: OffsetDateTime::now_utc(),
+    }
+}
+
+/// Pairs each book with when it last changed, for its entry's `<updated>`
+async fn with_datestamps(
+    books: Vec<Book>,
+    connection_pool: &PgPool,
+) -> Result<Vec<(Book, OffsetDateTime)>, sqlx::Error> {
+    let ids: Vec<_> = books.iter().map(|book| book.id).collect();
+    let datestamps: HashMap<_, _> = Db::get_book_datestamps(&ids, connection_pool)
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(books
+        .into_iter()
+        .filter_map(|book| {
+            let datestamp = *datestamps.get(&book.id)?;
+            Some((book, datestamp))
+        })
+        .collect())
+}
+
+fn feed_response(content_type: &'static str, body: String) -> Response {
+    (StatusCode::OK, [(CONTENT_TYPE, content_type)], body).into_response()
+}
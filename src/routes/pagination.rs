@@ -0,0 +1,68 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::routes::Error;
+
+/// How many rows a page holds when the client doesn't say
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// The most rows a single page may ask for
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+/// Keyset pagination shared by the list endpoints.
+///
+/// A list is read in a stable order that always ends in the row's primary
+/// key. Each page is fetched with one row more than asked for; if that extra
+/// row turns up there is another page, and the cursor for it records where
+/// the last row returned sat in the order. The next request carries that
+/// cursor back and continues strictly after it, so rows inserted meanwhile
+/// never shift or repeat what the client has already seen.
+///
+/// Cursors are opaque to clients: serialized to JSON and base64 encoded.
+pub struct Page<T> {
+    pub rows: Vec<T>,
+    pub has_more: bool,
+}
+
+impl<T> Page<T> {
+    /// Splits off the extra row fetched beyond `page_size`
+    pub fn from_rows(mut rows: Vec<T>, page_size: i64) -> Self {
+        let page_size = usize::try_from(page_size).unwrap_or(0);
+        let has_more = rows.len() > page_size;
+        rows.truncate(page_size);
+
+        Self { rows, has_more }
+    }
+
+    /// The cursor for the page after this one, built from its last row
+    pub fn next_cursor<C: Serialize>(&self, cursor: impl FnOnce(&T) -> C) -> Option<String> {
+        match (self.has_more, self.rows.last()) {
+            (true, Some(last)) => Some(encode_cursor(&cursor(last))),
+            _ => None,
+        }
+    }
+}
+
+/// The page size to use given what the client asked for
+pub fn page_size(requested: Option<i64>) -> i64 {
+    requested
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE)
+}
+
+pub fn encode_cursor<C: Serialize>(cursor: &C) -> String {
+    let json = serde_json::to_vec(cursor).expect("cursors always serialize");
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+/// Reads a cursor sent back by the client, refusing anything we didn't hand
+/// out with a `422`
+pub fn decode_cursor<C: DeserializeOwned>(cursor: &str) -> Result<C, Error> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| Error::unprocessable_entity([("cursor", "is not a valid cursor")]))
+}
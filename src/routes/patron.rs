@@ -0,0 +1,258 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Uuid;
+use time::Date;
+
+use crate::db::Db;
+use crate::routes::pagination::{decode_cursor, page_size};
+use crate::routes::{ApiContext, Error, ResultExt};
+
+/// Used to namespace our JSON query
+/// { "patron": <T> }
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct PatronBody<T = PatronQuery> {
+    pub patron: T,
+}
+
+/// The base return structure for a patron to the client
+#[derive(serde::Serialize)]
+pub struct Patron {
+    pub id: Uuid,
+    pub card_number: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+    pub category: String,
+    pub expires_on: Option<Date>,
+    pub blocked: bool,
+    pub blocked_reason: Option<String>,
+}
+
+/// A category of patron such as adult, child or staff
+#[derive(serde::Serialize)]
+pub struct PatronCategory {
+    pub category: String,
+    pub description: String,
+}
+
+/// Array of patron categories to the client
+#[derive(serde::Serialize, Default)]
+pub struct PatronCategoriesQuery {
+    pub categories: Vec<PatronCategory>,
+}
+
+/// Query coming from Client
+#[derive(serde::Deserialize, Clone)]
+pub struct PatronQuery {
+    pub card_number: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+    pub category: String,
+    pub expires_on: Option<Date>,
+}
+
+/// Array Query coming from Client, one page at a time. `next_cursor` fetches
+/// the following page and is `null` on the last one.
+#[derive(serde::Serialize, Default)]
+pub struct PatronsQuery {
+    pub patrons: Vec<Patron>,
+    pub next_cursor: Option<String>,
+}
+
+/// List Patrons Query coming from Client, all optional
+#[derive(serde::Deserialize, Default)]
+pub struct ListPatronsQuery {
+    pub category: Option<String>,
+    pub page_size: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+/// Where a page of patrons ended: the last patron's name and id
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PatronCursor {
+    pub last_name: String,
+    pub first_name: String,
+    pub id: Uuid,
+}
+
+/// Update Query coming from Client. Unblocking a patron clears the reason
+/// they were blocked.
+#[derive(serde::Deserialize, Clone)]
+pub struct PatronUpdateQuery {
+    pub id: Uuid,
+    pub card_number: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+    pub category: Option<String>,
+    pub expires_on: Option<Date>,
+    pub blocked: Option<bool>,
+    pub blocked_reason: Option<String>,
+}
+
+/// Get Patron Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct GetPatronQuery {
+    pub id: Uuid,
+}
+
+/// Delete Patron Query coming from Client
+#[derive(serde::Deserialize)]
+pub struct DeletePatron {
+    pub id: Uuid,
+}
+
+/// Database Object to be cast into a Patron
+#[derive(Clone)]
+pub struct PatronFromQuery {
+    pub patron_id: Uuid,
+    pub card_number: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+    pub patron_category: String,
+    pub expires_on: Option<Date>,
+    pub blocked: bool,
+    pub blocked_reason: Option<String>,
+}
+
+impl PatronFromQuery {
+    fn to_patron(&self) -> Patron {
+        let this = self.to_owned();
+        Patron {
+            id: this.patron_id,
+            card_number: this.card_number,
+            first_name: this.first_name,
+            last_name: this.last_name,
+            email: this.email,
+            phone: this.phone,
+            address: this.address,
+            category: this.patron_category,
+            expires_on: this.expires_on,
+            blocked: this.blocked,
+            blocked_reason: this.blocked_reason,
+        }
+    }
+}
+
+/// Maps the constraint violations a patron write can hit into `422`s
+fn on_patron_constraints<T>(result: Result<T, sqlx::Error>) -> Result<T, Error> {
+    result
+        .on_constraint("patron_card_number_key", |_| {
+            Error::unprocessable_entity([("card_number", "already in use")])
+        })
+        .on_constraint("patron_patron_category_fkey", |_| {
+            Error::unprocessable_entity([("category", "is not a known patron category")])
+        })
+}
+
+/// Gets the patron categories
+pub async fn get_list_patron_categories(
+    State(api_context): State<ApiContext>,
+) -> Result<(StatusCode, Json<PatronCategoriesQuery>), Error> {
+    let connection_pool = &api_context.db;
+
+    let categories = Db::get_patron_categories(connection_pool).await?;
+
+    Ok((StatusCode::OK, Json(PatronCategoriesQuery { categories })))
+}
+
+/// Gets a page of patrons by name, optionally of a single category
+pub async fn get_list_patrons(
+    State(api_context): State<ApiContext>,
+    request: Option<Json<PatronBody<ListPatronsQuery>>>,
+) -> Result<(StatusCode, Json<PatronsQuery>), Error> {
+    let connection_pool = &api_context.db;
+    let query = request.map(|Json(body)| body.patron).unwrap_or_default();
+
+    let page_size = page_size(query.page_size);
+    let cursor: Option<PatronCursor> = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let page =
+        Db::get_patron_list(query.category, page_size, cursor.as_ref(), connection_pool).await?;
+    let next_cursor = page.next_cursor(|patron| PatronCursor {
+        last_name: patron.last_name.clone(),
+        first_name: patron.first_name.clone(),
+        id: patron.patron_id,
+    });
+    let patrons = page.rows.iter().map(|patron| patron.to_patron()).collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(PatronsQuery {
+            patrons,
+            next_cursor,
+        }),
+    ))
+}
+
+/// Get a specific patron
+pub async fn get_patron(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<PatronBody<GetPatronQuery>>,
+) -> Result<(StatusCode, Json<Patron>), Error> {
+    let connection_pool = &api_context.db;
+
+    let patron = Db::get_patron(&request.patron.id, connection_pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok((StatusCode::OK, Json(patron.to_patron())))
+}
+
+/// Creates a patron
+pub async fn create_patron(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<PatronBody<PatronQuery>>,
+) -> Result<(StatusCode, String), Error> {
+    let connection_pool = &api_context.db;
+
+    let patron_id =
+        on_patron_constraints(Db::create_patron(request.patron, connection_pool).await)?;
+
+    Ok((StatusCode::OK, patron_id.to_string()))
+}
+
+/// Updates a patron, including blocking and unblocking them
+pub async fn update_patron(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<PatronBody<PatronUpdateQuery>>,
+) -> Result<(StatusCode, Json<Patron>), Error> {
+    let connection_pool = &api_context.db;
+
+    let updated_patron =
+        on_patron_constraints(Db::update_patron(request.patron, connection_pool).await)?
+            .ok_or(Error::NotFound)?;
+
+    Ok((StatusCode::OK, Json(updated_patron.to_patron())))
+}
+
+/// Deletes a patron. Patrons with loans or account history are kept for the
+/// record and should be blocked instead.
+pub async fn delete_patron(
+    State(api_context): State<ApiContext>,
+    Json(request): Json<PatronBody<DeletePatron>>,
+) -> Result<(StatusCode, String), Error> {
+    let connection_pool = &api_context.db;
+
+    let result = Db::delete_patron(request.patron.id, connection_pool)
+        .await
+        .on_constraint("loan_patron_id_fkey", |_| {
+            Error::conflict("patron has loan history")
+        })
+        .on_constraint("ledger_entry_patron_id_fkey", |_| {
+            Error::conflict("patron has account history")
+        })?;
+
+    Ok((StatusCode::OK, result.rows_affected().to_string()))
+}
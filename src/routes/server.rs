@@ -1,7 +1,8 @@
 use axum::http::StatusCode;
-use axum::routing::get;
+use axum::routing::{get, post, put};
 use axum::Router;
 use sqlx::PgPool;
+use std::sync::Arc;
 
 use tower::ServiceBuilder;
 use tower_http::trace::{
@@ -10,12 +11,73 @@ use tower_http::trace::{
 use tower_http::LatencyUnit;
 use tracing::Level;
 
+use crate::catalog::enrichment::{
+    CachedSource, MetadataSource, OpenLibrary, DEFAULT_METADATA_SOURCE_URL,
+    METADATA_CACHE_CAPACITY, METADATA_CACHE_TTL,
+};
+
 use crate::routes::{
     get_book,
+    get_book_by_id,
     get_list_books,
+    search_books,
+    get_book_by_lccn,
+    enrich_book,
+    get_list_duplicates,
+    merge_books,
+    get_list_merges,
+    import_marc,
+    import_csv,
+    export_books,
+    oai_pmh,
+    oai_pmh_form,
+    opds_root,
+    opds_books,
+    opds_search,
+    opds_open_search,
+    sru,
     create_book,
     update_book,
-    delete_book
+    delete_book,
+    get_book_copy,
+    get_list_book_copies,
+    create_book_copy,
+    update_book_copy,
+    withdraw_book_copy,
+    get_location,
+    get_list_locations,
+    create_location,
+    update_location,
+    delete_location,
+    get_calendar,
+    update_calendar,
+    get_patron,
+    get_list_patrons,
+    get_list_patron_categories,
+    create_patron,
+    update_patron,
+    delete_patron,
+    get_list_loans,
+    checkout,
+    checkin,
+    renew,
+    get_loan_policy,
+    get_list_loan_policies,
+    explain_loan_policy,
+    create_loan_policy,
+    update_loan_policy,
+    delete_loan_policy,
+    get_list_holds,
+    place_hold,
+    update_hold,
+    reorder_hold,
+    cancel_hold,
+    expire_holds,
+    get_account,
+    create_charge,
+    create_payment,
+    create_waiver,
+    accrue_fines,
 };
 
 /// Healthcheck GET
@@ -28,16 +90,140 @@ async fn hello_post() -> (StatusCode, String) {
     (StatusCode::OK, "hello post".to_string())
 }
 
+/// Where the API is served when `PUBLIC_BASE_URL` isn't set
+pub const DEFAULT_BASE_URL: &str = "http://localhost:8081";
+
+/// Where `Identify` tells harvesters to write to when `OAI_ADMIN_EMAIL`
+/// isn't set
+pub const DEFAULT_ADMIN_EMAIL: &str = "library@localhost";
+
 /// Parameters to hold in our State 
 #[derive(Clone)]
 pub struct ApiContext {
    pub db: PgPool,
+   pub config: Arc<ServerConfig>,
+   pub metadata_source: Arc<dyn MetadataSource>,
+}
+
+/// How the API describes itself to the outside world, read once at startup.
+/// The protocol endpoints link back to it, and harvesters key what they keep
+/// on it, so it is configured rather than taken from request headers any
+/// client could set.
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Where clients reach the API, such as `https://library.example`,
+    /// without a trailing slash
+    pub base_url: String,
+    /// The repository's name in OAI identifiers, `oai:<this>:<id>`
+    pub repository_identifier: String,
+    /// Who harvesters write to about the repository
+    pub admin_email: String,
+    /// Where books are looked up when they are enriched
+    pub metadata_source_url: String,
+}
+
+impl ServerConfig {
+    /// Reads `PUBLIC_BASE_URL`, `OAI_REPOSITORY_IDENTIFIER`,
+    /// `OAI_ADMIN_EMAIL` and `METADATA_SOURCE_URL`. The repository
+    /// identifier defaults to the base URL's host.
+    pub fn from_env() -> Self {
+        let base_url = std::env::var("PUBLIC_BASE_URL")
+            .unwrap_or_else(|_| DEFAULT_BASE_URL.to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let repository_identifier = std::env::var("OAI_REPOSITORY_IDENTIFIER")
+            .unwrap_or_else(|_| BaseUrl::parse(&base_url).host.to_string());
+
+        Self {
+            base_url,
+            repository_identifier,
+            admin_email: std::env::var("OAI_ADMIN_EMAIL")
+                .unwrap_or_else(|_| DEFAULT_ADMIN_EMAIL.to_string()),
+            metadata_source_url: std::env::var("METADATA_SOURCE_URL")
+                .unwrap_or_else(|_| DEFAULT_METADATA_SOURCE_URL.to_string()),
+        }
+    }
+
+    /// The base URL's host, without its scheme, port or path
+    pub fn host(&self) -> &str {
+        BaseUrl::parse(&self.base_url).host
+    }
+
+    /// The base URL's port, or its scheme's when it doesn't give one
+    pub fn port(&self) -> u16 {
+        let base_url = BaseUrl::parse(&self.base_url);
+        match base_url.port {
+            Some(port) => port,
+            None if base_url.scheme == "https" => 443,
+            None => 80,
+        }
+    }
+
+    /// The base URL's path, such as `/library` when the API is served under
+    /// one, and empty when it is served from the root
+    pub fn path(&self) -> &str {
+        BaseUrl::parse(&self.base_url).path
+    }
+}
+
+/// The parts of the configured base URL the protocols describe themselves by
+struct BaseUrl<'a> {
+    scheme: &'a str,
+    host: &'a str,
+    port: Option<u16>,
+    path: &'a str,
+}
+
+impl<'a> BaseUrl<'a> {
+    fn parse(url: &'a str) -> Self {
+        let (scheme, rest) = url.split_once("://").unwrap_or(("http", url));
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()),
+            None => (authority, None),
+        };
+
+        Self {
+            scheme,
+            host,
+            port,
+            path,
+        }
+    }
 }
 
-/// Builds the base app
+/// Builds the base app, configured from the environment
 pub fn app(db: PgPool) -> Router {
-    let api_context = ApiContext { db };
-    return api_router(api_context);
+    app_with_config(db, ServerConfig::from_env())
+}
+
+/// Builds the app with a particular configuration, looking books up at its
+/// `metadata_source_url` when they are enriched
+pub fn app_with_config(db: PgPool, config: ServerConfig) -> Router {
+    let metadata_source = CachedSource::new(
+        OpenLibrary::new(&config.metadata_source_url),
+        METADATA_CACHE_TTL,
+        METADATA_CACHE_CAPACITY,
+    );
+
+    app_with_metadata_source(db, config, Arc::new(metadata_source))
+}
+
+/// Builds the app around a particular metadata source
+pub fn app_with_metadata_source(
+    db: PgPool,
+    config: ServerConfig,
+    metadata_source: Arc<dyn MetadataSource>,
+) -> Router {
+    let api_context = ApiContext {
+        db,
+        config: Arc::new(config),
+        metadata_source,
+    };
+    api_router(api_context)
 }
 
 /// Sets our API routes, our State, and our Trace Layer
@@ -45,6 +231,16 @@ fn api_router(api_context: ApiContext) -> Router {
     Router::new()
         .route("/api/healthcheck", get(hello).post(hello_post))
         .route("/api/books/list", get(get_list_books))
+        .route("/api/books/search", get(search_books))
+        .route("/api/books/by-lccn/:lccn", get(get_book_by_lccn))
+        .route("/api/books/duplicates", get(get_list_duplicates))
+        .route("/api/books/merge", post(merge_books))
+        .route("/api/books/merges", get(get_list_merges))
+        .route("/api/books/import/marc", post(import_marc))
+        .route("/api/books/import/csv", post(import_csv))
+        .route("/api/books/export", get(export_books))
+        .route("/api/books/:id", get(get_book_by_id))
+        .route("/api/books/:id/enrich", post(enrich_book))
         .route(
             "/api/books",
             get(get_book)
@@ -52,6 +248,66 @@ fn api_router(api_context: ApiContext) -> Router {
                 .put(update_book)
                 .delete(delete_book),
         )
+        .route("/api/oai", get(oai_pmh).post(oai_pmh_form))
+        .route("/api/sru", get(sru))
+        .route("/api/opds", get(opds_root))
+        .route("/api/opds/books", get(opds_books))
+        .route("/api/opds/search", get(opds_search))
+        .route("/api/opds/opensearch.xml", get(opds_open_search))
+        .route("/api/copies/list", get(get_list_book_copies))
+        .route(
+            "/api/copies",
+            get(get_book_copy)
+                .post(create_book_copy)
+                .put(update_book_copy)
+                .delete(withdraw_book_copy),
+        )
+        .route("/api/locations/list", get(get_list_locations))
+        .route(
+            "/api/locations",
+            get(get_location)
+                .post(create_location)
+                .put(update_location)
+                .delete(delete_location),
+        )
+        .route(
+            "/api/locations/:id/calendar",
+            get(get_calendar).put(update_calendar),
+        )
+        .route("/api/patrons/list", get(get_list_patrons))
+        .route("/api/patrons/categories", get(get_list_patron_categories))
+        .route(
+            "/api/patrons",
+            get(get_patron)
+                .post(create_patron)
+                .put(update_patron)
+                .delete(delete_patron),
+        )
+        .route("/api/circulation/loans", get(get_list_loans))
+        .route("/api/circulation/checkout", post(checkout))
+        .route("/api/circulation/checkin", post(checkin))
+        .route("/api/circulation/renew", post(renew))
+        .route("/api/policies/list", get(get_list_loan_policies))
+        .route("/api/policies/explain", get(explain_loan_policy))
+        .route(
+            "/api/policies",
+            get(get_loan_policy)
+                .post(create_loan_policy)
+                .put(update_loan_policy)
+                .delete(delete_loan_policy),
+        )
+        .route("/api/holds/list", get(get_list_holds))
+        .route("/api/holds/reorder", put(reorder_hold))
+        .route("/api/holds/expire", post(expire_holds))
+        .route(
+            "/api/holds",
+            post(place_hold).put(update_hold).delete(cancel_hold),
+        )
+        .route("/api/accounts", get(get_account))
+        .route("/api/accounts/charges", post(create_charge))
+        .route("/api/accounts/payments", post(create_payment))
+        .route("/api/accounts/waivers", post(create_waiver))
+        .route("/api/accounts/accrue", post(accrue_fines))
         .with_state(api_context)
         .layer(
             ServiceBuilder::new().layer(
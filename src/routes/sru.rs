@@ -0,0 +1,34 @@
+use axum::extract::{Query, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+
+use crate::catalog::sru::Sru;
+use crate::routes::{ApiContext, Error};
+
+/// SRU searchRetrieve and explain requests. Like OAI-PMH, SRU reports its
+/// own errors as diagnostics inside a `200`.
+pub async fn sru(
+    State(api_context): State<ApiContext>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, Error> {
+    let config = &api_context.config;
+    let database = format!("{}/api/sru", config.path())
+        .trim_start_matches('/')
+        .to_string();
+
+    let server = Sru {
+        host: config.host(),
+        port: config.port(),
+        database: &database,
+    };
+    let body = server.respond(&params, &api_context.db).await?;
+
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/xml; charset=utf-8")],
+        body,
+    )
+        .into_response())
+}
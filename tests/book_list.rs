@@ -0,0 +1,147 @@
+use axum::http::StatusCode;
+use library_api_rir::routes::{BookSortField, ListBooksQuery, SortOrder};
+use serde_json::Value;
+use std::collections::HashMap;
+use time::{Date, Month};
+
+mod common;
+
+use common::{send, send_json, test_app, unique_suffix};
+
+fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn list_parameters_are_parsed_and_unknown_ones_refused() {
+    let query = ListBooksQuery::from_params(params(&[
+        ("author", "le guin"),
+        ("publish_date_from", "1960"),
+        ("publish_date_to", "[196-?]"),
+        ("sort", "publish_date"),
+        ("order", "desc"),
+    ]))
+    .unwrap();
+    assert_eq!(query.author.as_deref(), Some("le guin"));
+    assert_eq!(
+        query.publish_date_from,
+        Some(Date::from_calendar_date(1960, Month::January, 1).unwrap())
+    );
+    assert_eq!(
+        query.publish_date_to,
+        Some(Date::from_calendar_date(1969, Month::December, 31).unwrap())
+    );
+    assert_eq!(query.sort, BookSortField::PublishDate);
+    assert_eq!(query.order, SortOrder::Desc);
+
+    assert!(ListBooksQuery::from_params(params(&[("publisher", "Ace")])).is_err());
+    assert!(ListBooksQuery::from_params(params(&[("sort", "price")])).is_err());
+    assert!(ListBooksQuery::from_params(params(&[("order", "up")])).is_err());
+}
+
+#[tokio::test]
+async fn books_are_filtered_and_sorted() {
+    let app = test_app().await;
+    let author = format!("Filter Author {}", unique_suffix());
+
+    for (title, publish_date) in [("Alpha", "1969"), ("Beta", "1974"), ("Gamma", "1985")] {
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/api/books",
+            serde_json::json!({ "book": {
+                "author": author,
+                "title": title,
+                "lccn": "",
+                "isbn": "",
+                "publish_date": publish_date
+            }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let uri = format!(
+        "/api/books/list?author={}&publish_date_from=1970&sort=title&order=desc",
+        author.replace(' ', "%20")
+    );
+    let (status, body) = send_json(&app, "GET", &uri, Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let titles: Vec<_> = body["books"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|book| book["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, vec!["Gamma", "Beta"]);
+
+    let (status, body) = send_json(&app, "GET", "/api/books/list?colour=red", Value::Null).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["errors"]["colour"].is_array());
+}
+
+#[tokio::test]
+async fn book_list_pages_with_a_stable_cursor() {
+    let app = test_app().await;
+    let author = format!("Paging Author {}", unique_suffix());
+    let create = |title: &'static str| {
+        let app = app.clone();
+        let author = author.clone();
+        async move {
+            let (status, id) = send(
+                &app,
+                "POST",
+                "/api/books",
+                serde_json::json!({ "book": {
+                    "author": author,
+                    "title": title,
+                    "lccn": "",
+                    "isbn": "",
+                    "publish_date": ""
+                }}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            String::from_utf8(id).unwrap()
+        }
+    };
+
+    for title in ["B", "C", "D", "E", "F"] {
+        create(title).await;
+    }
+
+    let base = format!(
+        "/api/books/list?author={}&page_size=2",
+        author.replace(' ', "%20")
+    );
+    let mut titles = Vec::new();
+    let mut uri = base.clone();
+    loop {
+        let (status, body) = send_json(&app, "GET", &uri, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let books = body["books"].as_array().unwrap();
+        assert!(books.len() <= 2);
+        titles.extend(
+            books
+                .iter()
+                .map(|book| book["title"].as_str().unwrap().to_string()),
+        );
+
+        // A book inserted ahead of the cursor mid-walk doesn't shift the pages
+        if titles.len() == 2 {
+            create("A").await;
+        }
+
+        match body["next_cursor"].as_str() {
+            Some(cursor) => uri = format!("{base}&cursor={cursor}"),
+            None => break,
+        }
+    }
+    assert_eq!(titles, vec!["B", "C", "D", "E", "F"]);
+
+    let (status, _) = send(&app, "GET", &format!("{base}&cursor=nonsense"), Value::Null).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
@@ -0,0 +1,127 @@
+use axum::http::StatusCode;
+use library_api_rir::circulation::calendar::next_open_day;
+use library_api_rir::routes::{ClosureFromQuery, OpeningHours};
+use serde_json::json;
+use sqlx::types::Uuid;
+use time::{Date, Duration, Month, OffsetDateTime, Time};
+
+mod common;
+
+use common::{create_book, create_copy, create_patron, send, send_json, test_app};
+
+fn weekdays(days: &[i16]) -> Vec<OpeningHours> {
+    days.iter()
+        .map(|weekday| OpeningHours {
+            weekday: *weekday,
+            opens_at: Time::from_hms(9, 0, 0).unwrap(),
+            closes_at: Time::from_hms(17, 0, 0).unwrap(),
+        })
+        .collect()
+}
+
+fn closure(starts_on: Date, ends_on: Date) -> ClosureFromQuery {
+    ClosureFromQuery {
+        location_closure_id: Uuid::nil(),
+        starts_on,
+        ends_on,
+        reason: None,
+    }
+}
+
+#[test]
+fn due_date_rolls_forward_past_closed_days() {
+    // A Saturday
+    let saturday = Date::from_calendar_date(2026, Month::October, 17).unwrap();
+    let monday = saturday + Duration::days(2);
+    let tuesday = saturday + Duration::days(3);
+    let monday_to_friday = weekdays(&[1, 2, 3, 4, 5]);
+
+    assert_eq!(next_open_day(saturday, &[], &[]), saturday);
+    assert_eq!(next_open_day(saturday, &monday_to_friday, &[]), monday);
+    assert_eq!(
+        next_open_day(saturday, &monday_to_friday, &[closure(monday, monday)]),
+        tuesday
+    );
+    assert_eq!(
+        next_open_day(saturday, &[], &[closure(saturday, tuesday)]),
+        tuesday + Duration::days(1)
+    );
+}
+
+#[tokio::test]
+async fn branch_closure_pushes_back_due_dates_on_its_shelves() {
+    let app = test_app().await;
+    let today = OffsetDateTime::now_utc().date();
+
+    let (_, branch_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "name": "Seasonal Branch" }}),
+    )
+    .await;
+    let branch_id = String::from_utf8(branch_id).unwrap();
+    let (_, shelf_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "parent_location_id": branch_id, "name": "Fiction" }}),
+    )
+    .await;
+    let shelf_id = String::from_utf8(shelf_id).unwrap();
+
+    let reopens_on = today + Duration::days(120);
+    let (status, calendar) = send_json(
+        &app,
+        "PUT",
+        &format!("/api/locations/{branch_id}/calendar"),
+        json!({ "calendar": {
+            "hours": [{ "weekday": 1, "opens_at": "09:00", "closes_at": "17:30" }],
+            "closures": [{
+                "starts_on": today.to_string(),
+                "ends_on": (reopens_on - Duration::days(1)).to_string(),
+                "reason": "Refurbishment"
+            }]
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(calendar["hours"][0]["closes_at"], "17:30");
+    assert_eq!(calendar["closures"][0]["reason"], "Refurbishment");
+
+    let (status, _) = send(
+        &app,
+        "PUT",
+        &format!("/api/locations/{branch_id}/calendar"),
+        json!({ "calendar": { "hours": [{ "weekday": 8, "opens_at": "09:00", "closes_at": "17:00" }]}}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let book_id = create_book(&app, "The Dispossessed", "Ursula K. Le Guin").await;
+    let copy_id = create_copy(&app, &book_id).await;
+    send(
+        &app,
+        "PUT",
+        "/api/copies",
+        json!({ "copy": { "id": copy_id, "location_id": shelf_id }}),
+    )
+    .await;
+    let patron_id = create_patron(&app).await;
+
+    let (status, loan) = send_json(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let due_on = next_open_day(
+        reopens_on,
+        &weekdays(&[1]),
+        &[closure(today, reopens_on - Duration::days(1))],
+    );
+    assert_eq!(loan["due_on"], due_on.to_string());
+}
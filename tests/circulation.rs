@@ -0,0 +1,232 @@
+use axum::http::StatusCode;
+use library_api_rir::circulation::renewal_due_date;
+use serde_json::json;
+use time::{Date, Duration, Month};
+
+mod common;
+
+use common::{create_book, create_copy, create_patron, send, send_json, test_app, test_db};
+
+#[test]
+fn renewal_extends_from_the_later_of_due_date_and_today() {
+    let due_on = Date::from_calendar_date(2026, Month::March, 10).unwrap();
+    let days = |days: i64| due_on + Duration::days(days);
+
+    assert_eq!(renewal_due_date(due_on, days(-5), 21), days(21));
+    assert_eq!(renewal_due_date(due_on, due_on, 21), days(21));
+    assert_eq!(renewal_due_date(due_on, days(4), 21), days(25));
+}
+
+#[tokio::test]
+async fn copy_is_checked_out_renewed_and_checked_in() {
+    let app = test_app().await;
+    let book_id = create_book(&app, "The Left Hand of Darkness", "Ursula K. Le Guin").await;
+    let copy_id = create_copy(&app, &book_id).await;
+    let patron_id = create_patron(&app).await;
+    let other_patron_id = create_patron(&app).await;
+
+    let (status, loan) = send_json(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(loan["renewals"], 0);
+    let loan_id = loan["id"].clone();
+
+    let (status, body) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": other_patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, b"copy is already checked out");
+
+    let (status, loan) = send_json(
+        &app,
+        "POST",
+        "/api/circulation/renew",
+        json!({ "loan": { "id": loan_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(loan["renewals"], 1);
+
+    let (status, loan) = send_json(
+        &app,
+        "POST",
+        "/api/circulation/checkin",
+        json!({ "loan": { "book_copy_id": copy_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(loan["returned_at"].is_string());
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkin",
+        json!({ "loan": { "book_copy_id": copy_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn blocked_patron_is_refused() {
+    let app = test_app().await;
+    let book_id = create_book(&app, "Parable of the Sower", "Octavia E. Butler").await;
+    let copy_id = create_copy(&app, &book_id).await;
+    let patron_id = create_patron(&app).await;
+
+    let (status, _) = send(
+        &app,
+        "PUT",
+        "/api/patrons",
+        json!({ "patron": { "id": patron_id, "blocked": true }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, b"patron is blocked");
+}
+
+#[tokio::test]
+async fn loans_are_listed_a_page_at_a_time() {
+    let app = test_app().await;
+    let book_id = create_book(&app, "Kindred", "Octavia E. Butler").await;
+    let first_copy = create_copy(&app, &book_id).await;
+    let second_copy = create_copy(&app, &book_id).await;
+    let patron_id = create_patron(&app).await;
+
+    for copy_id in [&first_copy, &second_copy] {
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/api/circulation/checkout",
+            json!({ "loan": { "book_copy_id": copy_id, "patron_id": patron_id }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, page) = send_json(
+        &app,
+        "GET",
+        "/api/circulation/loans",
+        json!({ "loan": { "patron_id": patron_id, "page_size": 1 }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["loans"].as_array().unwrap().len(), 1);
+    assert_eq!(page["loans"][0]["copy_id"], second_copy.as_str());
+    assert!(page["next_cursor"].is_string());
+
+    let (status, page) = send_json(
+        &app,
+        "GET",
+        "/api/circulation/loans",
+        json!({ "loan": {
+            "patron_id": patron_id,
+            "page_size": 1,
+            "cursor": page["next_cursor"]
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["loans"].as_array().unwrap().len(), 1);
+    assert_eq!(page["loans"][0]["copy_id"], first_copy.as_str());
+    assert!(page["next_cursor"].is_null());
+
+    let (status, _) = send(
+        &app,
+        "GET",
+        "/api/circulation/loans",
+        json!({ "loan": { "patron_id": patron_id, "cursor": "not a cursor" }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn book_with_loans_on_record_is_not_deleted() {
+    let app = test_app().await;
+    let book_id = create_book(&app, "Dawn", "Octavia E. Butler").await;
+    let copy_id = create_copy(&app, &book_id).await;
+    let patron_id = create_patron(&app).await;
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(
+        &app,
+        "DELETE",
+        "/api/books",
+        json!({ "book": { "id": book_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, b"book has copies with loans on record");
+}
+
+#[tokio::test]
+async fn overdue_checkin_renewal_and_accrual_do_not_deadlock() {
+    let app = test_app().await;
+    let db = test_db().await;
+    let book_id = create_book(&app, "Wild Seed", "Octavia E. Butler").await;
+
+    for _ in 0..10 {
+        let copy_id = create_copy(&app, &book_id).await;
+        let patron_id = create_patron(&app).await;
+        let (status, loan) = send_json(
+            &app,
+            "POST",
+            "/api/circulation/checkout",
+            json!({ "loan": { "book_copy_id": copy_id, "patron_id": patron_id }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        sqlx::query("update loan set due_on = current_date - 3 where loan_id = $1::uuid")
+            .bind(loan["id"].as_str().unwrap())
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let (checkin, renewal, accrual) = tokio::join!(
+            send(
+                &app,
+                "POST",
+                "/api/circulation/checkin",
+                json!({ "loan": { "book_copy_id": copy_id }}),
+            ),
+            send(
+                &app,
+                "POST",
+                "/api/circulation/renew",
+                json!({ "loan": { "id": loan["id"] }}),
+            ),
+            send(&app, "POST", "/api/accounts/accrue", json!({})),
+        );
+        assert_eq!(checkin.0, StatusCode::OK);
+        assert_ne!(renewal.0, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(accrual.0, StatusCode::OK);
+    }
+}
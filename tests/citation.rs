@@ -0,0 +1,169 @@
+use axum::body::Body;
+use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::{Request, StatusCode};
+use library_api_rir::catalog::citation::{bibtex, csl_json, ris};
+use library_api_rir::catalog::publish_date::DatePrecision;
+use library_api_rir::routes::{Book, Published};
+use serde_json::{json, Value};
+use time::{Date, Month};
+use tower::ServiceExt;
+
+mod common;
+
+use common::{create_book, send, test_app, unique_suffix};
+
+fn book(author: &str, title: &str, precision: DatePrecision) -> Book {
+    Book {
+        author: Some(author.to_string()),
+        title: Some(title.to_string()),
+        isbn: Some("0-441-47812-3".to_string()),
+        isbn_13: Some("9780441478125".to_string()),
+        publish_date: Some("March 1969".to_string()),
+        published: Some(Published {
+            on: Date::from_calendar_date(1969, Month::March, 1).unwrap(),
+            precision,
+            approximate: false,
+        }),
+        ..Book::default()
+    }
+}
+
+#[test]
+fn books_cite_as_bibtex_with_distinct_keys() {
+    let first = book(
+        "Le Guin, Ursula K.",
+        "The Left Hand of Darkness",
+        DatePrecision::Month,
+    );
+    let second = book("Le Guin, Ursula K.", "Left & Right", DatePrecision::Year);
+
+    assert_eq!(
+        bibtex(&[first, second]),
+        "@book{leguin1969left,\n  \
+           author = {Le Guin, Ursula K.},\n  \
+           title = {The Left Hand of Darkness},\n  \
+           year = {1969},\n  \
+           month = {3},\n  \
+           isbn = {9780441478125}\n\
+         }\n\n\
+         @book{leguin1969lefta,\n  \
+           author = {Le Guin, Ursula K.},\n  \
+           title = {Left \\& Right},\n  \
+           year = {1969},\n  \
+           isbn = {9780441478125}\n\
+         }\n"
+    );
+}
+
+#[test]
+fn books_cite_as_ris_and_csl_json() {
+    let cited = book(
+        "Le Guin, Ursula K.",
+        "The Left Hand of Darkness",
+        DatePrecision::Month,
+    );
+
+    let record = ris(std::slice::from_ref(&cited));
+    let tags: Vec<&str> = record.split("\r\n").collect();
+    assert_eq!(
+        tags,
+        [
+            "TY  - BOOK",
+            "AU  - Le Guin, Ursula K.",
+            "TI  - The Left Hand of Darkness",
+            "PY  - 1969/03",
+            "SN  - 9780441478125",
+            &format!("ID  - {}", cited.id),
+            "ER  - ",
+            "",
+        ]
+    );
+
+    let item = csl_json(&cited);
+    assert_eq!(item["type"], "book");
+    assert_eq!(
+        item["author"],
+        json!([{ "family": "Le Guin", "given": "Ursula K." }])
+    );
+    assert_eq!(item["issued"], json!({ "date-parts": [[1969, 3]] }));
+    assert_eq!(item["ISBN"], "9780441478125");
+
+    let mut decade = book("Anonymous", "Sagas", DatePrecision::Decade);
+    decade.publish_date = Some("196-".to_string());
+    decade.published.as_mut().unwrap().approximate = true;
+    let item = csl_json(&decade);
+    assert_eq!(item["author"], json!([{ "literal": "Anonymous" }]));
+    assert_eq!(item["issued"], json!({ "literal": "196-", "circa": true }));
+}
+
+#[tokio::test]
+async fn books_and_search_results_are_cited_on_request() {
+    let app = test_app().await;
+    let word = format!("nebula{}", unique_suffix());
+    let title = format!("The {word} Papers");
+    let id = create_book(&app, &title, "Doe, Jane").await;
+    create_book(&app, &format!("More {word} Papers"), "Doe, Jane").await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/books/{id}"))
+                .header(ACCEPT, "application/x-research-info-systems")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()[CONTENT_TYPE],
+        "application/x-research-info-systems"
+    );
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let record = String::from_utf8(body.to_vec()).unwrap();
+    assert!(record.contains(&format!("TI  - {title}\r\n")));
+
+    let (status, body) = send(
+        &app,
+        "GET",
+        &format!("/api/books/{id}?format=csl_json"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let item: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(item["id"], id);
+    assert_eq!(item["title"], title);
+
+    let (status, body) = send(
+        &app,
+        "GET",
+        &format!("/api/books/search?q={word}&format=bibtex"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let entries = String::from_utf8(body).unwrap();
+    assert_eq!(entries.matches("@book{").count(), 2);
+    assert!(entries.contains(&format!("title = {{{title}}}")));
+
+    let (status, body) = send(
+        &app,
+        "GET",
+        &format!("/api/books/search?q={word}&format=csl_json"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let items: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(items.as_array().unwrap().len(), 2);
+
+    let (status, _) = send(
+        &app,
+        "GET",
+        &format!("/api/books/{id}?format=mla"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,89 @@
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+
+mod common;
+
+use common::{send, test_app};
+
+#[tokio::test]
+async fn copies_are_created_listed_and_withdrawn() {
+    let app = test_app().await;
+
+    let (status, master_id) = send(
+        &app,
+        "POST",
+        "/api/books",
+        json!({ "book": {
+            "author": "Ursula K. Le Guin",
+            "title": "The Dispossessed",
+            "lccn": "",
+            "isbn": "",
+            "publish_date": "1974"
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let master_id = String::from_utf8(master_id).unwrap();
+    let barcode = format!("copy-test-{}", master_id);
+
+    let (status, copy_id) = send(
+        &app,
+        "POST",
+        "/api/copies",
+        json!({ "copy": {
+            "master_book_id": master_id,
+            "barcode": barcode,
+            "condition": "good",
+            "acquisition_date": "2022-12-01",
+            "price_cents": 1599
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let copy_id = String::from_utf8(copy_id).unwrap();
+
+    let (status, body) = send(
+        &app,
+        "POST",
+        "/api/copies",
+        json!({ "copy": { "master_book_id": master_id, "barcode": barcode }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let errors: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(errors["errors"]["barcode"][0], "already in use");
+
+    let (status, body) = send(
+        &app,
+        "DELETE",
+        "/api/copies",
+        json!({ "copy": { "id": copy_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let copy: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(copy["acquisition_date"], "2022-12-01");
+    assert!(copy["withdrawn_at"].is_string());
+
+    let (status, body) = send(
+        &app,
+        "GET",
+        "/api/copies/list",
+        json!({ "copy": { "master_book_id": master_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let list: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(list["copies"].as_array().unwrap().len(), 0);
+
+    let (status, body) = send(
+        &app,
+        "GET",
+        "/api/copies/list",
+        json!({ "copy": { "master_book_id": master_id, "include_withdrawn": true }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let list: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(list["copies"][0]["id"], copy_id);
+}
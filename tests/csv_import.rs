@@ -0,0 +1,127 @@
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+
+mod common;
+
+use common::{send_file, send_json, test_app, unique_suffix};
+
+/// A valid ISBN-13 made from the last 9 digits of `number`
+fn isbn13(number: u128) -> String {
+    let body = format!("978{:09}", number % 1_000_000_000);
+    let sum: u32 = body
+        .chars()
+        .zip([1, 3].iter().cycle())
+        .map(|(digit, weight)| digit.to_digit(10).unwrap() * weight)
+        .sum();
+
+    format!("{body}{}", (10 - sum % 10) % 10)
+}
+
+async fn books_with_isbn(app: &axum::Router, isbn_13: &str) -> Vec<Value> {
+    let (status, body) = send_json(
+        app,
+        "GET",
+        &format!("/api/books/list?isbn={isbn_13}"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    body["books"].as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn csv_imports_create_and_update_books_all_or_nothing() {
+    let app = test_app().await;
+    let suffix = unique_suffix();
+    let isbn = isbn13(suffix);
+    let good_rows = format!(
+        "Book Title,Writer,ISBN,Publish Date,Shelf Notes\n\
+         \"Donated Book, {suffix}\",A. Donor,{isbn},1999,good condition\n\
+         ,,,,\n\
+         \"Donated Book, {suffix}\",B. Donor,{isbn},,\n"
+    );
+    let columns = "title=Book%20Title&author=Writer";
+
+    let mut bad_file = good_rows.clone();
+    bad_file.push_str("Misprinted,C. Donor,978-0-00-000000-1,,\n,D. Donor,,,\n");
+    let (status, report) = send_file(
+        &app,
+        &format!("/api/books/import/csv?{columns}"),
+        "donations.csv",
+        bad_file.as_bytes(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(report["committed"], false);
+    assert_eq!(report["failed"], 2);
+    assert_eq!(report["rows"][3]["row"], 5);
+    assert_eq!(
+        report["rows"][3]["errors"],
+        json!({ "isbn": ["check digit does not match"] })
+    );
+    assert_eq!(
+        report["rows"][4]["errors"],
+        json!({ "title": ["is missing"] })
+    );
+    assert!(books_with_isbn(&app, &isbn).await.is_empty());
+
+    let (status, report) = send_file(
+        &app,
+        &format!("/api/books/import/csv?{columns}&dry_run=true"),
+        "donations.csv",
+        good_rows.as_bytes(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["committed"], false);
+    let actions: Vec<&Value> = report["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| &row["action"])
+        .collect();
+    assert_eq!(
+        actions,
+        [&json!("created"), &json!("skipped"), &json!("updated")]
+    );
+    assert!(books_with_isbn(&app, &isbn).await.is_empty());
+
+    let (status, report) = send_file(
+        &app,
+        &format!("/api/books/import/csv?{columns}"),
+        "donations.csv",
+        good_rows.as_bytes(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["committed"], true);
+    assert_eq!(report["created"], 1);
+    assert_eq!(report["updated"], 1);
+
+    let books = books_with_isbn(&app, &isbn).await;
+    assert_eq!(books.len(), 1);
+    assert_eq!(books[0]["id"], report["rows"][0]["id"]);
+    assert_eq!(books[0]["author"], "B. Donor");
+    assert_eq!(books[0]["publish_date"], "1999");
+
+    let (status, body) = send_file(
+        &app,
+        "/api/books/import/csv?title=Name&shelf=Shelf%20Notes",
+        "donations.csv",
+        good_rows.as_bytes(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["errors"]["shelf"], json!(["is not a book field"]));
+
+    let (status, body) = send_file(
+        &app,
+        "/api/books/import/csv?title=Name",
+        "donations.csv",
+        good_rows.as_bytes(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["errors"]["title"], json!(["no column is named Name"]));
+}
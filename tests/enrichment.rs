@@ -0,0 +1,229 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use futures::future::BoxFuture;
+use library_api_rir::catalog::enrichment::{
+    read_books_response, BookMetadata, CachedSource, EnrichmentError, MetadataSource, OpenLibrary,
+    METADATA_CACHE_CAPACITY, METADATA_CACHE_TTL,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod common;
+
+use common::{create_book, send, test_app_with_metadata_source};
+
+/// Serves `books` as the Open Library Books API would on a local port,
+/// returning its base URL and a count of the requests it has answered
+async fn stub_source(books: Value) -> (String, Arc<AtomicUsize>) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let stub = Router::new()
+        .route(
+            "/api/books",
+            get(
+                |State((books, requests)): State<(Value, Arc<AtomicUsize>)>,
+                 Query(params): Query<HashMap<String, String>>| async move {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    let bibkey = &params["bibkeys"];
+                    let mut found = json!({});
+                    if let Some(book) = books.get(bibkey) {
+                        found[bibkey] = book.clone();
+                    }
+                    Json(found)
+                },
+            ),
+        )
+        .with_state((books, requests.clone()));
+
+    let server =
+        axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(stub.into_make_service());
+    let base_url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+
+    (base_url, requests)
+}
+
+/// A source that knows every book, after `delay`, counting its lookups
+struct SlowSource {
+    delay: Duration,
+    lookups: Arc<AtomicUsize>,
+}
+
+impl MetadataSource for SlowSource {
+    fn lookup_isbn<'a>(
+        &'a self,
+        _isbn_13: &'a str,
+    ) -> BoxFuture<'a, Result<Option<BookMetadata>, EnrichmentError>> {
+        Box::pin(async move {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            Ok(Some(BookMetadata {
+                title: Some("Found".to_string()),
+                ..BookMetadata::default()
+            }))
+        })
+    }
+}
+
+/// The app, enriching from a cached client of the source at `base_url`
+async fn app(base_url: &str) -> Router {
+    test_app_with_metadata_source(Arc::new(CachedSource::new(
+        OpenLibrary::new(base_url),
+        METADATA_CACHE_TTL,
+        METADATA_CACHE_CAPACITY,
+    )))
+    .await
+}
+
+/// Creates a book with only an ISBN and an author, returning its id
+async fn create_isbn_book(app: &Router, isbn: &str, query: &str) -> String {
+    let (status, id) = send(
+        app,
+        "POST",
+        &format!("/api/books{query}"),
+        json!({ "book": {
+            "author": "Kept, Author",
+            "title": "",
+            "lccn": "",
+            "isbn": isbn,
+            "publish_date": ""
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    String::from_utf8(id).unwrap()
+}
+
+async fn get_book(app: &Router, id: &str) -> Value {
+    let (status, body) = send(app, "GET", &format!("/api/books/{id}"), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    serde_json::from_slice(&body).unwrap()
+}
+
+async fn enrich(app: &Router, id: &str) -> (StatusCode, Value) {
+    let (status, body) = send(app, "POST", &format!("/api/books/{id}/enrich"), Value::Null).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[test]
+fn books_api_responses_are_read() {
+    let body = json!({
+        "ISBN:9780306406157": {
+            "title": "Ground Control",
+            "subtitle": "A Guide ",
+            "authors": [{ "name": "Jane Doe" }, { "name": "Richard Roe" }],
+            "publish_date": "March 1969"
+        }
+    });
+    assert_eq!(
+        read_books_response("9780306406157", &body).unwrap(),
+        Some(BookMetadata {
+            title: Some("Ground Control: A Guide".to_string()),
+            author: Some("Jane Doe; Richard Roe".to_string()),
+            publish_date: Some("March 1969".to_string()),
+        })
+    );
+    assert_eq!(read_books_response("9780441478125", &body).unwrap(), None);
+    assert!(matches!(
+        read_books_response("9780306406157", &json!([])),
+        Err(EnrichmentError::Malformed)
+    ));
+}
+
+#[tokio::test]
+async fn books_are_filled_in_from_the_source() {
+    let (base_url, requests) = stub_source(json!({
+        "ISBN:9780306406157": {
+            "title": "Ground Control",
+            "authors": [{ "name": "Jane Doe" }],
+            "publish_date": "March 1969"
+        }
+    }))
+    .await;
+    let app = app(&base_url).await;
+
+    let id = create_isbn_book(&app, "0-306-40615-2", "").await;
+    let (status, book) = enrich(&app, &id).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(book["title"], "Ground Control");
+    assert_eq!(book["author"], "Kept, Author");
+    assert_eq!(book["publish_date"], "March 1969");
+    assert_eq!(book["published"]["on"], "1969-03-01");
+    assert_eq!(get_book(&app, &id).await["title"], "Ground Control");
+
+    let (status, _) = enrich(&app, &id).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    let id = create_isbn_book(&app, "9780306406157", "?enrich=true").await;
+    assert_eq!(get_book(&app, &id).await["title"], "Ground Control");
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    let id = create_isbn_book(&app, "9780441478125", "?enrich=true").await;
+    assert_eq!(get_book(&app, &id).await["title"], "");
+    let (status, body) = enrich(&app, &id).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        body["errors"]["isbn"][0],
+        "is not known to the metadata source"
+    );
+
+    let id = create_book(&app, "No ISBN", "Doe, Jane").await;
+    let (status, body) = enrich(&app, &id).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["errors"]["isbn"][0], "is needed to look the book up");
+
+    let (status, _) = enrich(&app, "00000000-0000-0000-0000-000000000000").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn a_failing_source_is_a_bad_gateway() {
+    let (base_url, _) = stub_source(json!({})).await;
+    let app = app(&format!("{base_url}/missing")).await;
+
+    let id = create_isbn_book(&app, "9780306406157", "?enrich=true").await;
+    assert_eq!(get_book(&app, &id).await["title"], "");
+
+    let (status, _) = enrich(&app, &id).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn the_cache_forgets_the_oldest_lookup_once_full() {
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let source = SlowSource {
+        delay: Duration::ZERO,
+        lookups: lookups.clone(),
+    };
+    let cache = CachedSource::new(source, METADATA_CACHE_TTL, 2);
+
+    for isbn_13 in ["9780306406157", "9780441478125", "9780306406157"] {
+        cache.lookup_isbn(isbn_13).await.unwrap();
+    }
+    assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+    cache.lookup_isbn("9780131103627").await.unwrap();
+    cache.lookup_isbn("9780441478125").await.unwrap();
+    assert_eq!(lookups.load(Ordering::SeqCst), 3);
+    cache.lookup_isbn("9780306406157").await.unwrap();
+    assert_eq!(lookups.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn a_slow_source_does_not_hold_up_creating_a_book() {
+    let source = SlowSource {
+        delay: Duration::from_secs(60),
+        lookups: Arc::new(AtomicUsize::new(0)),
+    };
+    let app = test_app_with_metadata_source(Arc::new(source)).await;
+
+    let started = Instant::now();
+    let id = create_isbn_book(&app, "9780306406157", "?enrich=true").await;
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(get_book(&app, &id).await["title"], "");
+}
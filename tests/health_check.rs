@@ -3,7 +3,7 @@ use axum::{
     http::{Request, StatusCode},
 };
 use sqlx::postgres::PgPoolOptions;
-use library_app_rir::routes::app;
+use library_api_rir::routes::app;
 use tower::ServiceExt;
 
 #[tokio::test]
@@ -43,7 +43,7 @@ async fn hello_post() {
 
     let db = PgPoolOptions::new()
         .max_connections(5)
-        .connect(&database_url)
+        .connect(database_url)
         .await
         .expect("couldnt connect to database url");
 
@@ -0,0 +1,402 @@
+use axum::http::StatusCode;
+use library_api_rir::circulation::hold::move_in_queue;
+use serde_json::{json, Value};
+use sqlx::types::Uuid;
+
+mod common;
+
+use common::{create_book, create_copy, create_patron, send, send_json, test_app, test_db};
+
+#[test]
+fn hold_moves_within_queue() {
+    let queue: Vec<Uuid> = (1..=4).map(Uuid::from_u128).collect();
+
+    assert_eq!(
+        move_in_queue(queue.clone(), &queue[3], 1),
+        vec![queue[3], queue[0], queue[1], queue[2]]
+    );
+    assert_eq!(
+        move_in_queue(queue.clone(), &queue[0], 10),
+        vec![queue[1], queue[2], queue[3], queue[0]]
+    );
+    assert_eq!(move_in_queue(queue.clone(), &Uuid::nil(), 1), queue);
+}
+
+#[tokio::test]
+async fn returned_copy_is_trapped_for_front_of_queue() {
+    let app = test_app().await;
+    let book_id = create_book(&app, "Beloved", "Toni Morrison").await;
+    let copy_id = create_copy(&app, &book_id).await;
+    let borrower = create_patron(&app).await;
+    let first = create_patron(&app).await;
+    let second = create_patron(&app).await;
+
+    let (_, pickup_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "name": "Hold Shelf Branch" }}),
+    )
+    .await;
+    let pickup_id = String::from_utf8(pickup_id).unwrap();
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": borrower }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut holds = Vec::new();
+    for patron_id in [&first, &second] {
+        let (status, hold) = send_json(
+            &app,
+            "POST",
+            "/api/holds",
+            json!({ "hold": {
+                "master_book_id": book_id,
+                "patron_id": patron_id,
+                "pickup_location_id": pickup_id
+            }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        holds.push(hold);
+    }
+    assert_eq!(holds[0]["queue_position"], 1);
+    assert_eq!(holds[1]["queue_position"], 2);
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/holds",
+        json!({ "hold": {
+            "master_book_id": book_id,
+            "patron_id": first,
+            "pickup_location_id": pickup_id
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // With holds waiting the borrower can't renew
+    let (status, loans) = send_json(
+        &app,
+        "GET",
+        "/api/circulation/loans",
+        json!({ "loan": { "patron_id": borrower }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/circulation/renew",
+        json!({ "loan": { "id": loans["loans"][0]["id"] }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, queue) = send_json(
+        &app,
+        "PUT",
+        "/api/holds/reorder",
+        json!({ "hold": { "id": holds[1]["id"], "queue_position": 1 }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let order: Vec<&Value> = queue["holds"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hold| &hold["patron_id"])
+        .collect();
+    assert_eq!(order, vec![&json!(second), &json!(first)]);
+
+    let (status, checkin) = send_json(
+        &app,
+        "POST",
+        "/api/circulation/checkin",
+        json!({ "loan": { "book_copy_id": copy_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(checkin["trapped_hold"]["patron_id"], second.as_str());
+    assert_eq!(checkin["trapped_hold"]["status"], "trapped");
+    assert_eq!(checkin["trapped_hold"]["copy_id"], copy_id.as_str());
+
+    let (status, body) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": first }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, b"copy is on hold for another patron");
+
+    // Cancelling the trapped hold passes the copy on to the next in line
+    let (status, cancelled) = send_json(
+        &app,
+        "DELETE",
+        "/api/holds",
+        json!({ "hold": { "id": holds[1]["id"] }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cancelled["status"], "cancelled");
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": first }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, queue) = send_json(
+        &app,
+        "GET",
+        "/api/holds/list",
+        json!({ "hold": { "master_book_id": book_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(queue["holds"].as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn hold_queue_is_listed_a_page_at_a_time() {
+    let app = test_app().await;
+    let book_id = create_book(&app, "Song of Solomon", "Toni Morrison").await;
+    let copy_id = create_copy(&app, &book_id).await;
+    let borrower = create_patron(&app).await;
+    let first = create_patron(&app).await;
+    let second = create_patron(&app).await;
+
+    let (_, pickup_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "name": "Queue Paging Branch" }}),
+    )
+    .await;
+    let pickup_id = String::from_utf8(pickup_id).unwrap();
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": borrower }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    for patron_id in [&first, &second] {
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/api/holds",
+            json!({ "hold": {
+                "master_book_id": book_id,
+                "patron_id": patron_id,
+                "pickup_location_id": pickup_id
+            }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, page) = send_json(
+        &app,
+        "GET",
+        "/api/holds/list",
+        json!({ "hold": { "master_book_id": book_id, "page_size": 1 }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["holds"].as_array().unwrap().len(), 1);
+    assert_eq!(page["holds"][0]["patron_id"], first.as_str());
+    assert!(page["next_cursor"].is_string());
+
+    let (status, page) = send_json(
+        &app,
+        "GET",
+        "/api/holds/list",
+        json!({ "hold": {
+            "master_book_id": book_id,
+            "page_size": 1,
+            "cursor": page["next_cursor"]
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["holds"].as_array().unwrap().len(), 1);
+    assert_eq!(page["holds"][0]["patron_id"], second.as_str());
+    assert!(page["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn checkin_traps_a_hold_while_its_queue_is_reordered() {
+    let app = test_app().await;
+    let (_, pickup_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "name": "Busy Hold Shelf Branch" }}),
+    )
+    .await;
+    let pickup_id = String::from_utf8(pickup_id).unwrap();
+
+    for _ in 0..10 {
+        let book_id = create_book(&app, "Jazz", "Toni Morrison").await;
+        let copy_id = create_copy(&app, &book_id).await;
+        let borrower = create_patron(&app).await;
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/api/circulation/checkout",
+            json!({ "loan": { "book_copy_id": copy_id, "patron_id": borrower }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let mut holds = Vec::new();
+        for _ in 0..2 {
+            let patron_id = create_patron(&app).await;
+            let (status, hold) = send_json(
+                &app,
+                "POST",
+                "/api/holds",
+                json!({ "hold": {
+                    "master_book_id": book_id,
+                    "patron_id": patron_id,
+                    "pickup_location_id": pickup_id
+                }}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            holds.push(hold);
+        }
+
+        let (checkin, reorder) = tokio::join!(
+            send_json(
+                &app,
+                "POST",
+                "/api/circulation/checkin",
+                json!({ "loan": { "book_copy_id": copy_id }}),
+            ),
+            send(
+                &app,
+                "PUT",
+                "/api/holds/reorder",
+                json!({ "hold": { "id": holds[1]["id"], "queue_position": 1 }}),
+            ),
+        );
+        assert_eq!(checkin.0, StatusCode::OK);
+        assert_eq!(reorder.0, StatusCode::OK);
+        assert_eq!(checkin.1["trapped_hold"]["status"], "trapped");
+    }
+}
+
+#[tokio::test]
+async fn uncollected_holds_expire_and_pass_the_copy_on() {
+    let app = test_app().await;
+    let db = test_db().await;
+    let book_id = create_book(&app, "Paradise", "Toni Morrison").await;
+    let copy_id = create_copy(&app, &book_id).await;
+    let borrower = create_patron(&app).await;
+    let first = create_patron(&app).await;
+    let second = create_patron(&app).await;
+
+    let (_, pickup_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "name": "Forgetful Branch" }}),
+    )
+    .await;
+    let pickup_id = String::from_utf8(pickup_id).unwrap();
+
+    send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": borrower }}),
+    )
+    .await;
+    for patron_id in [&first, &second] {
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/api/holds",
+            json!({ "hold": {
+                "master_book_id": book_id,
+                "patron_id": patron_id,
+                "pickup_location_id": pickup_id
+            }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, checkin) = send_json(
+        &app,
+        "POST",
+        "/api/circulation/checkin",
+        json!({ "loan": { "book_copy_id": copy_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let trapped_id = checkin["trapped_hold"]["id"].clone();
+    assert_eq!(checkin["trapped_hold"]["patron_id"], first.as_str());
+
+    // Not due to expire yet
+    let (status, expired) = send_json(&app, "POST", "/api/holds/expire", json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(expired["holds"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|hold| hold["id"] != trapped_id));
+
+    sqlx::query("update hold set pickup_by = current_date - 1 where hold_id = $1::uuid")
+        .bind(trapped_id.as_str().unwrap())
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let (status, expired) = send_json(&app, "POST", "/api/holds/expire", json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let expired_hold = expired["holds"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|hold| hold["id"] == trapped_id)
+        .unwrap();
+    assert_eq!(expired_hold["status"], "expired");
+
+    let (status, queue) = send_json(
+        &app,
+        "GET",
+        "/api/holds/list",
+        json!({ "hold": { "master_book_id": book_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(queue["holds"].as_array().unwrap().len(), 1);
+    assert_eq!(queue["holds"][0]["patron_id"], second.as_str());
+    assert_eq!(queue["holds"][0]["status"], "trapped");
+    assert_eq!(queue["holds"][0]["copy_id"], copy_id.as_str());
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": second }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
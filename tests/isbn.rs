@@ -0,0 +1,142 @@
+use axum::http::StatusCode;
+use library_api_rir::catalog::backfill::Backfills;
+use library_api_rir::catalog::isbn::{isbn13_to_isbn10, normalize, IsbnError};
+use serde_json::{json, Value};
+use sqlx::types::Uuid;
+
+mod common;
+
+use common::{send, send_json, test_app, test_db, unique_suffix};
+
+#[test]
+fn isbns_are_checked_and_normalized_to_isbn_13() {
+    assert_eq!(normalize("0-306-40615-2").unwrap(), "9780306406157");
+    assert_eq!(normalize("978-0-306-40615-7").unwrap(), "9780306406157");
+    assert_eq!(normalize("0 8044 2957 x").unwrap(), "9780804429573");
+
+    assert_eq!(normalize("0-306-40615-3"), Err(IsbnError::CheckDigit));
+    assert_eq!(normalize("978-0-306-40615-8"), Err(IsbnError::CheckDigit));
+    assert_eq!(normalize("12345"), Err(IsbnError::Length));
+    assert_eq!(normalize("03064061X2"), Err(IsbnError::Characters));
+    assert_eq!(normalize("4006381333931"), Err(IsbnError::Prefix));
+}
+
+#[test]
+fn isbn_13_converts_back_to_isbn_10() {
+    assert_eq!(
+        isbn13_to_isbn10("9780306406157").as_deref(),
+        Some("0306406152")
+    );
+    assert_eq!(
+        isbn13_to_isbn10("9780804429573").as_deref(),
+        Some("080442957X")
+    );
+    assert_eq!(isbn13_to_isbn10("9791234567896"), None);
+}
+
+#[tokio::test]
+async fn books_store_an_isbn_13_and_refuse_bad_isbns() {
+    let app = test_app().await;
+
+    let (status, body) = send_json(
+        &app,
+        "POST",
+        "/api/books",
+        json!({ "book": {
+            "author": "Test Author",
+            "title": "Bad ISBN",
+            "lccn": "",
+            "isbn": "0-306-40615-3",
+            "publish_date": ""
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["errors"]["isbn"][0], "check digit does not match");
+
+    let (status, body) = send_json(
+        &app,
+        "POST",
+        "/api/books",
+        json!({ "book": {
+            "author": "Test Author",
+            "title": "Not a Book EAN",
+            "lccn": "",
+            "isbn": "4006381333931",
+            "publish_date": ""
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        body["errors"]["isbn"][0],
+        "must start with 978 or 979 to be an ISBN-13"
+    );
+
+    let suffix = unique_suffix();
+    let (status, id) = send(
+        &app,
+        "POST",
+        "/api/books",
+        json!({ "book": {
+            "author": "Test Author",
+            "title": format!("Good ISBN {suffix}"),
+            "lccn": "",
+            "isbn": "0-306-40615-2",
+            "publish_date": ""
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let id = String::from_utf8(id).unwrap();
+
+    let (status, book) = send_json(&app, "GET", "/api/books", json!({ "book": { "id": id }})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(book["isbn"], "0-306-40615-2");
+    assert_eq!(book["isbn_13"], "9780306406157");
+
+    let (status, body) = send_json(
+        &app,
+        "GET",
+        &format!("/api/books/list?isbn=978-0-306-40615-7&title={suffix}"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["books"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|book| book["id"] == id.as_str()));
+
+    let (status, _) = send(
+        &app,
+        "PUT",
+        "/api/books",
+        json!({ "book": { "id": id, "isbn": "not an isbn" }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn books_written_before_isbn_13_was_kept_are_backfilled() {
+    let db = test_db().await;
+    let id: Uuid = sqlx::query_scalar(
+        r#"insert into "master_book" (title, isbn)
+        values ('Legacy ISBN Book', '0-306-40615-2') returning master_book_id"#,
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+
+    assert!(Backfills::run(&db).await.unwrap().filled >= 1);
+
+    let isbn_13: Option<String> =
+        sqlx::query_scalar(r#"select isbn_13 from "master_book" where master_book_id = $1"#)
+            .bind(id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert_eq!(isbn_13.as_deref(), Some("9780306406157"));
+}
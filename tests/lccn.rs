@@ -0,0 +1,90 @@
+use axum::http::StatusCode;
+use library_api_rir::catalog::backfill::Backfills;
+use library_api_rir::catalog::lccn::{normalize, LccnError};
+use serde_json::{json, Value};
+use sqlx::types::Uuid;
+
+mod common;
+
+use common::{send, send_json, test_app, test_db, unique_suffix};
+
+#[test]
+fn lccns_are_normalized_the_library_of_congress_way() {
+    assert_eq!(normalize("n78-890351").unwrap(), "n78890351");
+    assert_eq!(normalize("n 78890351 ").unwrap(), "n78890351");
+    assert_eq!(normalize(" 85000002 ").unwrap(), "85000002");
+    assert_eq!(normalize("85-2 ").unwrap(), "85000002");
+    assert_eq!(normalize("2001-000002").unwrap(), "2001000002");
+    assert_eq!(normalize("75-425165//r75").unwrap(), "75425165");
+    assert_eq!(normalize(" 79139101 /AC/r932").unwrap(), "79139101");
+    assert_eq!(normalize("SH85-26371").unwrap(), "sh85026371");
+
+    assert_eq!(normalize("85-1234567"), Err(LccnError::Serial));
+    assert_eq!(normalize("abcd85000002"), Err(LccnError::Format));
+    assert_eq!(normalize("850002"), Err(LccnError::Format));
+}
+
+#[tokio::test]
+async fn books_are_found_by_lccn_however_it_was_typed() {
+    let app = test_app().await;
+    // A serial unlikely to clash with other runs
+    let serial = unique_suffix() % 1_000_000;
+
+    let (status, id) = send(
+        &app,
+        "POST",
+        "/api/books",
+        json!({ "book": {
+            "author": "Test Author",
+            "title": "Catalogued Book",
+            "lccn": format!("zz 99-{serial}"),
+            "isbn": "",
+            "publish_date": ""
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let id = String::from_utf8(id).unwrap();
+
+    let (status, book) = send_json(
+        &app,
+        "GET",
+        &format!("/api/books/by-lccn/zz99{serial:06}"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(book["id"], id);
+    assert_eq!(book["lccn_normalized"], format!("zz99{serial:06}"));
+
+    let (status, _) = send(&app, "GET", "/api/books/by-lccn/not-an-lccn", Value::Null).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, _) = send(&app, "GET", "/api/books/by-lccn/yyy00000001", Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn books_written_before_lccns_were_normalized_are_backfilled() {
+    let db = test_db().await;
+    let serial = unique_suffix() % 1_000_000;
+    let id: Uuid = sqlx::query_scalar(
+        r#"insert into "master_book" (title, lccn)
+        values ('Legacy LCCN Book', $1) returning master_book_id"#,
+    )
+    .bind(format!("zz 97-{serial} /AC"))
+    .fetch_one(&db)
+    .await
+    .unwrap();
+
+    assert!(Backfills::run(&db).await.unwrap().filled >= 1);
+
+    let lccn_normalized: Option<String> = sqlx::query_scalar(
+        r#"select lccn_normalized from "master_book" where master_book_id = $1"#,
+    )
+    .bind(id)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(lccn_normalized, Some(format!("zz97{serial:06}")));
+}
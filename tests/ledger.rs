@@ -0,0 +1,198 @@
+use axum::http::StatusCode;
+use library_api_rir::circulation::fines::overdue_fine_cents;
+use serde_json::json;
+use time::{Date, Duration, Month};
+
+mod common;
+
+use common::{create_book, create_copy, create_patron, send, send_json, test_app};
+
+#[test]
+fn overdue_fine_is_charged_per_day_up_to_the_cap() {
+    let due_on = Date::from_calendar_date(2026, Month::March, 10).unwrap();
+    let days = |days: i64| due_on + Duration::days(days);
+
+    assert_eq!(overdue_fine_cents(due_on, days(-9), 25, None), 0);
+    assert_eq!(overdue_fine_cents(due_on, due_on, 25, None), 0);
+    assert_eq!(overdue_fine_cents(due_on, days(4), 25, None), 100);
+    assert_eq!(overdue_fine_cents(due_on, days(50), 25, Some(500)), 500);
+    assert_eq!(overdue_fine_cents(due_on, days(50), 0, None), 0);
+}
+
+#[tokio::test]
+async fn charges_payments_and_waivers_keep_a_running_balance() {
+    let app = test_app().await;
+    let patron_id = create_patron(&app).await;
+
+    let (status, charge) = send_json(
+        &app,
+        "POST",
+        "/api/accounts/charges",
+        json!({ "entry": {
+            "patron_id": patron_id,
+            "kind": "lost",
+            "amount_cents": 1500,
+            "note": "Lost on holiday",
+            "created_by": "desk"
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(charge["amount_cents"], 1500);
+
+    let (status, payment) = send_json(
+        &app,
+        "POST",
+        "/api/accounts/payments",
+        json!({ "entry": {
+            "patron_id": patron_id,
+            "amount_cents": 1000,
+            "reference_id": charge["id"]
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(payment["amount_cents"], -1000);
+    assert_eq!(payment["reference_id"], charge["id"]);
+
+    let (status, body) = send(
+        &app,
+        "POST",
+        "/api/accounts/waivers",
+        json!({ "entry": { "patron_id": patron_id, "amount_cents": 600 }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, b"amount is more than the patron owes");
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/accounts/waivers",
+        json!({ "entry": { "patron_id": patron_id, "amount_cents": 500 }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, account) = send_json(
+        &app,
+        "GET",
+        "/api/accounts",
+        json!({ "account": { "patron_id": patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(account["balance_cents"], 0);
+    let kinds: Vec<_> = account["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, vec!["lost", "payment", "waiver"]);
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/accounts/charges",
+        json!({ "entry": { "patron_id": patron_id, "kind": "overdue", "amount_cents": 100 }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, body) = send(
+        &app,
+        "DELETE",
+        "/api/patrons",
+        json!({ "patron": { "id": patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, b"patron has account history");
+}
+
+#[tokio::test]
+async fn patron_over_the_fine_threshold_may_not_borrow() {
+    let app = test_app().await;
+
+    let (_, location_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "name": "Strict Branch" }}),
+    )
+    .await;
+    let location_id = String::from_utf8(location_id).unwrap();
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/policies",
+        json!({ "policy": {
+            "location_id": location_id,
+            "loan_period_days": 14,
+            "max_renewals": 1,
+            "max_items": 5,
+            "daily_fine_cents": 20,
+            "max_fine_cents": 400,
+            "fine_threshold_cents": 0
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let book_id = create_book(&app, "Kindred", "Octavia E. Butler").await;
+    let copy_id = create_copy(&app, &book_id).await;
+    send(
+        &app,
+        "PUT",
+        "/api/copies",
+        json!({ "copy": { "id": copy_id, "location_id": location_id }}),
+    )
+    .await;
+    let patron_id = create_patron(&app).await;
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/accounts/charges",
+        json!({ "entry": { "patron_id": patron_id, "kind": "damaged", "amount_cents": 300 }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, b"patron owes more than the fine threshold");
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/accounts/payments",
+        json!({ "entry": { "patron_id": patron_id, "amount_cents": 300 }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copy_id, "patron_id": patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, accrued) = send_json(&app, "POST", "/api/accounts/accrue", json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(accrued["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|entry| entry["patron_id"] != patron_id.as_str()));
+}
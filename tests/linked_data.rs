@@ -0,0 +1,172 @@
+use axum::body::Body;
+use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use library_api_rir::catalog::linked_data::{dublin_core, schema_org};
+use library_api_rir::catalog::publish_date::DatePrecision;
+use library_api_rir::routes::{Book, Published};
+use serde_json::{json, Value};
+use time::{Date, Month};
+use tower::ServiceExt;
+
+mod common;
+
+use common::{send, test_app, unique_suffix};
+
+/// Gets `uri` asking for `accept`, returning the content type and body
+async fn get(app: &Router, uri: &str, accept: &str) -> (String, Vec<u8>) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .header(ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let content_type = response.headers()[CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (content_type, body.to_vec())
+}
+
+#[test]
+fn books_are_described_as_schema_org_and_dublin_core() {
+    let book = Book {
+        author: Some("Le Guin, Ursula K.".to_string()),
+        title: Some("Lavinia & <Other> Stories".to_string()),
+        isbn_13: Some("9780151014248".to_string()),
+        lccn_normalized: Some("2007047651".to_string()),
+        publish_date: Some("2008".to_string()),
+        published: Some(Published {
+            on: Date::from_calendar_date(2008, Month::January, 1).unwrap(),
+            precision: DatePrecision::Year,
+            approximate: false,
+        }),
+        ..Book::default()
+    };
+
+    let item = schema_org(&book);
+    assert_eq!(item["@context"], "https://schema.org");
+    assert_eq!(item["@type"], "Book");
+    assert_eq!(item["@id"], format!("urn:uuid:{}", book.id));
+    assert_eq!(item["name"], "Lavinia & <Other> Stories");
+    assert_eq!(
+        item["author"],
+        json!({ "@type": "Person", "name": "Le Guin, Ursula K." })
+    );
+    assert_eq!(item["isbn"], "9780151014248");
+    assert_eq!(item["datePublished"], "2008");
+    assert_eq!(item["sameAs"], "https://lccn.loc.gov/2007047651");
+
+    let record = dublin_core(&book);
+    assert!(record.starts_with("<oai_dc:dc "));
+    for element in [
+        "<dc:title>Lavinia &amp; &lt;Other&gt; Stories</dc:title>",
+        "<dc:creator>Le Guin, Ursula K.</dc:creator>",
+        "<dc:date>2008</dc:date>",
+        "<dc:type>Text</dc:type>",
+        "<dc:identifier>urn:isbn:9780151014248</dc:identifier>",
+        "<dc:identifier>https://lccn.loc.gov/2007047651</dc:identifier>",
+    ] {
+        assert!(record.contains(element), "{element} missing from {record}");
+    }
+}
+
+#[tokio::test]
+async fn book_routes_negotiate_linked_data() {
+    let app = test_app().await;
+    // A serial unlikely to clash with other runs
+    let serial = unique_suffix() % 1_000_000;
+    let title = format!("Linked Book {serial}");
+
+    let (status, id) = send(
+        &app,
+        "POST",
+        "/api/books",
+        json!({ "book": {
+            "author": "Test Author",
+            "title": title,
+            "lccn": format!("zw99{serial:06}"),
+            "isbn": "",
+            "publish_date": "May 1999"
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let id = String::from_utf8(id).unwrap();
+
+    let (content_type, body) = get(
+        &app,
+        &format!("/api/books/by-lccn/zw99{serial:06}"),
+        "application/ld+json",
+    )
+    .await;
+    assert_eq!(content_type, "application/ld+json");
+    let item: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(item["identifier"], id);
+    assert_eq!(item["name"], title);
+    assert_eq!(item["datePublished"], "1999-05");
+
+    let (content_type, body) = get(
+        &app,
+        &format!("/api/books/{id}"),
+        "text/html, application/dc+xml;q=0.9",
+    )
+    .await;
+    assert_eq!(content_type, "application/dc+xml");
+    let record = String::from_utf8(body).unwrap();
+    assert!(record.starts_with("<?xml"));
+    assert!(record.contains(&format!("<dc:title>{title}</dc:title>")));
+    assert!(record.contains(&format!("<dc:identifier>urn:uuid:{id}</dc:identifier>")));
+
+    let (content_type, body) = get(&app, &format!("/api/books/{id}"), "application/json").await;
+    assert_eq!(content_type, "application/json");
+    let book: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(book["copies"], json!([]));
+}
+
+#[tokio::test]
+async fn the_representation_rated_highest_is_served() {
+    let app = test_app().await;
+    let (_, id) = send(
+        &app,
+        "POST",
+        "/api/books",
+        json!({ "book": {
+            "author": "Doe, Jane",
+            "title": format!("Negotiated {}", unique_suffix()),
+            "lccn": "",
+            "isbn": "",
+            "publish_date": ""
+        }}),
+    )
+    .await;
+    let uri = format!("/api/books/{}", String::from_utf8(id).unwrap());
+
+    for (accept, expected) in [
+        (
+            "application/marc+xml, application/ld+json;q=0.5",
+            "application/marc+xml",
+        ),
+        (
+            "application/x-bibtex;q=0.2, application/ld+json",
+            "application/ld+json",
+        ),
+        (
+            "application/ld+json;q=0, application/dc+xml;q=0.1",
+            "application/dc+xml",
+        ),
+        ("application/ld+json;q=0.5, */*", "application/json"),
+        ("application/ld+json;q=0", "application/json"),
+    ] {
+        let (content_type, _) = get(&app, &uri, accept).await;
+        assert_eq!(content_type, expected, "Accept: {accept}");
+    }
+}
@@ -0,0 +1,179 @@
+use axum::http::StatusCode;
+use library_api_rir::circulation::policy;
+use library_api_rir::routes::LoanPolicyMatchFromQuery;
+use serde_json::json;
+use sqlx::types::Uuid;
+use time::{Duration, OffsetDateTime};
+
+mod common;
+
+use common::{create_book, create_copy, send, send_json, test_app, unique_suffix};
+
+fn policy_match(
+    patron_category: Option<&str>,
+    item_type: Option<&str>,
+    location_distance: Option<i32>,
+) -> LoanPolicyMatchFromQuery {
+    LoanPolicyMatchFromQuery {
+        loan_policy_id: Uuid::nil(),
+        patron_category: patron_category.map(str::to_string),
+        item_type: item_type.map(str::to_string),
+        location_id: location_distance.map(|_| Uuid::nil()),
+        loan_period_days: 21,
+        max_renewals: 2,
+        max_items: 10,
+        daily_fine_cents: 0,
+        max_fine_cents: None,
+        fine_threshold_cents: None,
+        location_distance,
+    }
+}
+
+#[test]
+fn most_specific_policy_applies() {
+    let default = policy_match(None, None, None);
+    let staff = policy_match(Some("staff"), None, None);
+    let staff_dvd = policy_match(Some("staff"), Some("audiovisual"), None);
+    let branch = policy_match(None, None, Some(2));
+    let shelf = policy_match(None, None, Some(0));
+
+    let ranked = policy::rank(vec![
+        default.clone(),
+        staff_dvd.clone(),
+        branch.clone(),
+        staff.clone(),
+        shelf.clone(),
+    ]);
+    let distances: Vec<_> = ranked
+        .iter()
+        .map(|candidate| {
+            (
+                candidate.location_distance,
+                candidate.patron_category.is_some(),
+                candidate.item_type.is_some(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        distances,
+        vec![
+            (Some(0), false, false),
+            (Some(2), false, false),
+            (None, true, true),
+            (None, true, false),
+            (None, false, false),
+        ]
+    );
+    assert!(policy::resolve(vec![]).is_none());
+}
+
+#[tokio::test]
+async fn branch_policy_sets_loan_period_and_limit() {
+    let app = test_app().await;
+
+    let (_, branch_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "name": "Children's Branch" }}),
+    )
+    .await;
+    let branch_id = String::from_utf8(branch_id).unwrap();
+    let (_, shelf_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "parent_location_id": branch_id, "name": "Picture Books" }}),
+    )
+    .await;
+    let shelf_id = String::from_utf8(shelf_id).unwrap();
+
+    let (status, policy_id) = send(
+        &app,
+        "POST",
+        "/api/policies",
+        json!({ "policy": {
+            "patron_category": "child",
+            "location_id": branch_id,
+            "loan_period_days": 7,
+            "max_renewals": 0,
+            "max_items": 1
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let policy_id = String::from_utf8(policy_id).unwrap();
+
+    let (status, patron_id) = send(
+        &app,
+        "POST",
+        "/api/patrons",
+        json!({ "patron": {
+            "card_number": format!("C-{}", unique_suffix()),
+            "first_name": "Young",
+            "last_name": "Reader",
+            "category": "child"
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let patron_id = String::from_utf8(patron_id).unwrap();
+
+    let book_id = create_book(&app, "Where the Wild Things Are", "Maurice Sendak").await;
+    let mut copies = Vec::new();
+    for _ in 0..2 {
+        let copy_id = create_copy(&app, &book_id).await;
+        let (status, _) = send(
+            &app,
+            "PUT",
+            "/api/copies",
+            json!({ "copy": { "id": copy_id, "location_id": shelf_id }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        copies.push(copy_id);
+    }
+
+    let (status, explanation) = send_json(
+        &app,
+        "GET",
+        "/api/policies/explain",
+        json!({ "policy": { "patron_id": patron_id, "book_copy_id": copies[0] }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(explanation["policy"]["id"], policy_id);
+    assert_eq!(explanation["candidates"][0]["location_distance"], 1);
+
+    let (status, loan) = send_json(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copies[0], "patron_id": patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let due_on = OffsetDateTime::now_utc().date() + Duration::days(7);
+    assert_eq!(loan["due_on"], due_on.to_string());
+
+    let (status, body) = send(
+        &app,
+        "POST",
+        "/api/circulation/checkout",
+        json!({ "loan": { "book_copy_id": copies[1], "patron_id": patron_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, b"patron has reached their loan limit");
+
+    let (status, body) = send(
+        &app,
+        "POST",
+        "/api/circulation/renew",
+        json!({ "loan": { "id": loan["id"] }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body, b"loan has reached its renewal limit");
+}
@@ -0,0 +1,185 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+mod common;
+
+use common::{create_book, send, send_json, test_app};
+
+#[tokio::test]
+async fn book_reports_where_its_copies_live() {
+    let app = test_app().await;
+
+    let (status, branch_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "name": "Main Branch" }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let branch_id = String::from_utf8(branch_id).unwrap();
+
+    let (status, shelf_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": {
+            "parent_location_id": branch_id,
+            "name": "Fiction",
+            "floor": "2",
+            "shelf_start": "A",
+            "shelf_end": "F"
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let shelf_id = String::from_utf8(shelf_id).unwrap();
+
+    // A branch can't be moved underneath its own shelves
+    let (status, _) = send(
+        &app,
+        "PUT",
+        "/api/locations",
+        json!({ "location": { "id": branch_id, "parent_location_id": shelf_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, _) = send(
+        &app,
+        "DELETE",
+        "/api/locations",
+        json!({ "location": { "id": branch_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let book_id = create_book(&app, "Kindred", "Octavia E. Butler").await;
+    let (status, copy_id) = send(
+        &app,
+        "POST",
+        "/api/copies",
+        json!({ "copy": {
+            "master_book_id": book_id,
+            "location_id": shelf_id,
+            "barcode": format!("location-test-{}", book_id)
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let copy_id = String::from_utf8(copy_id).unwrap();
+
+    let (status, book) = send_json(
+        &app,
+        "GET",
+        "/api/books",
+        json!({ "book": { "id": book_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(book["copies"][0]["copy_id"], copy_id);
+    assert_eq!(book["copies"][0]["location"]["name"], "Fiction");
+    assert_eq!(book["copies"][0]["location"]["parent_id"], branch_id);
+}
+
+#[tokio::test]
+async fn concurrent_moves_cannot_close_a_loop() {
+    let app = test_app().await;
+
+    for _ in 0..10 {
+        let mut branches = Vec::new();
+        for name in ["East Branch", "West Branch"] {
+            let (status, id) = send(
+                &app,
+                "POST",
+                "/api/locations",
+                json!({ "location": { "name": name }}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            branches.push(String::from_utf8(id).unwrap());
+        }
+
+        let (east_under_west, west_under_east) = tokio::join!(
+            send(
+                &app,
+                "PUT",
+                "/api/locations",
+                json!({ "location": { "id": branches[0], "parent_location_id": branches[1] }}),
+            ),
+            send(
+                &app,
+                "PUT",
+                "/api/locations",
+                json!({ "location": { "id": branches[1], "parent_location_id": branches[0] }}),
+            ),
+        );
+        let statuses = [east_under_west.0, west_under_east.0];
+        assert!(statuses.contains(&StatusCode::OK));
+        assert!(statuses.contains(&StatusCode::UNPROCESSABLE_ENTITY));
+    }
+}
+
+#[tokio::test]
+async fn locations_can_be_moved_back_to_the_top_level() {
+    let app = test_app().await;
+
+    let mut ids = Vec::new();
+    for name in ["North Branch", "Reference"] {
+        let (status, id) = send(
+            &app,
+            "POST",
+            "/api/locations",
+            json!({ "location": { "name": name }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        ids.push(String::from_utf8(id).unwrap());
+    }
+    let (branch_id, room_id) = (&ids[0], &ids[1]);
+
+    let (status, room) = send_json(
+        &app,
+        "PUT",
+        "/api/locations",
+        json!({ "location": { "id": room_id, "parent_location_id": branch_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(room["parent_id"], *branch_id);
+
+    let (status, _) = send(
+        &app,
+        "PUT",
+        "/api/locations",
+        json!({ "location": {
+            "id": room_id,
+            "parent_location_id": branch_id,
+            "clear_parent": true
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Leaving the parent out keeps it
+    let (status, room) = send_json(
+        &app,
+        "PUT",
+        "/api/locations",
+        json!({ "location": { "id": room_id, "floor": "1" }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(room["parent_id"], *branch_id);
+
+    let (status, room) = send_json(
+        &app,
+        "PUT",
+        "/api/locations",
+        json!({ "location": { "id": room_id, "clear_parent": true }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(room["parent_id"].is_null());
+    assert_eq!(room["floor"], "1");
+}
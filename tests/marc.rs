@@ -0,0 +1,327 @@
+use axum::body::Body;
+use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::{Request, StatusCode};
+use library_api_rir::catalog::marc::{parse, MarcError, MarcRecord};
+use library_api_rir::routes::BookFromQuery;
+use serde_json::{json, Value};
+use sqlx::types::Uuid;
+use time::{Date, Month};
+use tower::ServiceExt;
+
+mod common;
+
+use common::{create_book, send_file, send_json, test_app, unique_suffix};
+
+/// Builds an ISO 2709 record from `(tag, field)` pairs, a data field being
+/// its indicators followed by its subfields
+fn iso2709(fields: &[(&str, String)]) -> Vec<u8> {
+    let mut directory = Vec::new();
+    let mut data = Vec::new();
+    for (tag, value) in fields {
+        let mut field = value.as_bytes().to_vec();
+        field.push(0x1e);
+        directory.extend(format!("{tag}{:04}{:05}", field.len(), data.len()).bytes());
+        data.extend(field);
+    }
+    directory.push(0x1e);
+
+    let base_address = 24 + directory.len();
+    let length = base_address + data.len() + 1;
+    let mut record = format!("{length:05}nam a22{base_address:05} i 4500").into_bytes();
+    record.extend(directory);
+    record.extend(data);
+    record.push(0x1d);
+    record
+}
+
+fn left_hand_of_darkness(lccn: &str, isbn: &str) -> Vec<u8> {
+    iso2709(&[
+        ("001", "ocm00012345".to_string()),
+        ("010", format!("  \u{1f}a{lccn}")),
+        ("020", format!("  \u{1f}a{isbn} (pbk.)\u{1f}q(pbk.)")),
+        (
+            "100",
+            "1 \u{1f}aLe Guin, Ursula K.,\u{1f}eauthor.".to_string(),
+        ),
+        (
+            "245",
+            "14\u{1f}aThe left hand of darkness :\u{1f}ba novel /\u{1f}cUrsula K. Le Guin."
+                .to_string(),
+        ),
+        (
+            "260",
+            "  \u{1f}aNew York :\u{1f}bAce,\u{1f}c1970.".to_string(),
+        ),
+        (
+            "264",
+            " 1\u{1f}aNew York :\u{1f}bAce,\u{1f}c[1969]".to_string(),
+        ),
+    ])
+}
+
+#[test]
+fn iso2709_records_map_onto_books() {
+    let mut file = left_hand_of_darkness("   69011925 ", "0441478123");
+    file.extend(b"00042nam a2200037 i 4500truncated");
+
+    let records = parse(&file).unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(records[1].is_err());
+
+    let record = records[0].as_ref().unwrap();
+    assert_eq!(record.control_fields[0].value, "ocm00012345");
+    let book = record.to_book_query();
+    assert_eq!(book.lccn, "69011925");
+    assert_eq!(book.isbn, "0441478123");
+    assert_eq!(book.author, "Le Guin, Ursula K.");
+    assert_eq!(book.title, "The left hand of darkness : a novel");
+    assert_eq!(book.publish_date, "[1969]");
+}
+
+#[test]
+fn marcxml_records_map_onto_books() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <marc:collection xmlns:marc="http://www.loc.gov/MARC21/slim">
+          <marc:record>
+            <marc:leader>00000nam a2200000 a 4500</marc:leader>
+            <marc:controlfield tag="001">ocm00054321</marc:controlfield>
+            <marc:datafield tag="020" ind1=" " ind2=" ">
+              <marc:subfield code="a">9780060931469</marc:subfield>
+            </marc:datafield>
+            <marc:datafield tag="100" ind1="1" ind2=" ">
+              <marc:subfield code="a">Morrison, Toni.</marc:subfield>
+            </marc:datafield>
+            <marc:datafield tag="245" ind1="1" ind2="0">
+              <marc:subfield code="a">Beloved &amp; other stories /</marc:subfield>
+            </marc:datafield>
+            <marc:datafield tag="260" ind1=" " ind2=" ">
+              <marc:subfield code="c">c1987.</marc:subfield>
+            </marc:datafield>
+          </marc:record>
+          <marc:record>
+            <marc:leader>short</marc:leader>
+          </marc:record>
+        </marc:collection>"#;
+
+    let records = parse(xml.as_bytes()).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1], Err(MarcError::Leader));
+
+    let book = records[0].as_ref().unwrap().to_book_query();
+    assert_eq!(book.isbn, "9780060931469");
+    assert_eq!(book.lccn, "");
+    assert_eq!(book.author, "Morrison, Toni.");
+    assert_eq!(book.title, "Beloved & other stories");
+    assert_eq!(book.publish_date, "c1987");
+
+    assert!(matches!(
+        parse(b"<collection></record>"),
+        Err(MarcError::Xml(_))
+    ));
+}
+
+#[tokio::test]
+async fn uploaded_marc_files_report_each_record() {
+    let app = test_app().await;
+    let serial = unique_suffix() % 1_000_000;
+
+    let mut file = left_hand_of_darkness(&format!("zx69-{serial}"), "0441478123");
+    file.extend(left_hand_of_darkness("", "0441478124"));
+
+    let (status, report) = send_file(&app, "/api/books/import/marc", "books.mrc", &file).await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(report["imported"], 1);
+    assert_eq!(report["failed"], 1);
+    assert_eq!(report["records"][0]["record"], 1);
+    assert_eq!(report["records"][0]["control_number"], "ocm00012345");
+    assert_eq!(
+        report["records"][1]["errors"][0],
+        "isbn: check digit does not match"
+    );
+
+    let (status, book) = send_json(
+        &app,
+        "GET",
+        &format!("/api/books/by-lccn/zx69{serial:06}"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(book["id"], report["records"][0]["id"]);
+    assert_eq!(book["isbn_13"], "9780441478125");
+    assert_eq!(book["published"]["on"], "1969-01-01");
+
+    let (status, _) = send_json(&app, "POST", "/api/books/import/marc", json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn a_record_the_database_refuses_fails_alone() {
+    let app = test_app().await;
+
+    // Postgres refuses a NUL in text, which nothing checks for beforehand
+    let mut file = iso2709(&[("245", "10\u{1f}aUnsaveable\u{0} title".to_string())]);
+    file.extend(left_hand_of_darkness("", "0441478123"));
+
+    let (status, report) = send_file(&app, "/api/books/import/marc", "books.mrc", &file).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["imported"], 1);
+    assert_eq!(report["failed"], 1);
+    assert_eq!(
+        report["records"][0]["errors"][0],
+        "could not be saved to the catalog"
+    );
+    assert!(report["records"][1]["id"].is_string());
+}
+
+#[test]
+fn exported_records_read_back_as_the_same_book() {
+    let book = BookFromQuery {
+        master_book_id: Uuid::from_u128(7),
+        author: Some("Le Guin, Ursula K.".to_string()),
+        title: Some("The Dispossessed & other utopias".to_string()),
+        lccn: Some("74-1795".to_string()),
+        lccn_normalized: Some("74001795".to_string()),
+        isbn: Some("0-06-012563-2".to_string()),
+        isbn_13: Some("9780060125639".to_string()),
+        publish_date: Some("ca. 197-".to_string()),
+        published_on: Some(Date::from_calendar_date(1970, Month::January, 1).unwrap()),
+        published_precision: Some("decade".to_string()),
+        published_approximate: true,
+    };
+    let record = MarcRecord::from_book(&book);
+    assert_eq!(record.control_fields[1].value.len(), 40);
+    assert_eq!(&record.control_fields[1].value[6..11], "q197u");
+    assert_eq!(record.subfield("010", 'a'), Some("   74001795 "));
+
+    for bytes in [
+        record.to_iso2709().unwrap(),
+        format!("<collection>{}</collection>", record.to_marcxml()).into_bytes(),
+    ] {
+        let parsed = parse(&bytes).unwrap().remove(0).unwrap();
+        assert_eq!(
+            parsed.control_fields[0].value,
+            Uuid::from_u128(7).to_string()
+        );
+
+        let read_back = parsed.to_book_query();
+        assert_eq!(read_back.lccn, "74001795");
+        assert_eq!(read_back.isbn, "9780060125639");
+        assert_eq!(read_back.author, "Le Guin, Ursula K.");
+        assert_eq!(read_back.title, "The Dispossessed & other utopias");
+        assert_eq!(read_back.publish_date, "ca. 197-");
+    }
+}
+
+#[test]
+fn records_too_long_for_iso2709_are_refused() {
+    let book = |title: String| BookFromQuery {
+        master_book_id: Uuid::from_u128(8),
+        author: None,
+        title: Some(title),
+        lccn: None,
+        lccn_normalized: None,
+        isbn: None,
+        isbn_13: None,
+        publish_date: None,
+        published_on: None,
+        published_precision: None,
+        published_approximate: false,
+    };
+
+    // 245 holds the title after two indicators and a subfield code; with its
+    // terminator, 9994 bytes of title make it exactly 9999
+    let record = MarcRecord::from_book(&book("x".repeat(9994)));
+    let bytes = record.to_iso2709().unwrap();
+    let parsed = parse(&bytes).unwrap().remove(0).unwrap();
+    assert_eq!(parsed.to_book_query().title.len(), 9994);
+
+    let record = MarcRecord::from_book(&book("x".repeat(9995)));
+    assert_eq!(
+        record.to_iso2709(),
+        Err(MarcError::FieldTooLong("245".to_string()))
+    );
+
+    let mut record = MarcRecord::from_book(&book("Long notes".to_string()));
+    let note = record.data_fields[0].clone();
+    for _ in 0..12 {
+        let mut note = note.clone();
+        note.tag = "500".to_string();
+        note.subfields = vec![('a', "x".repeat(9000))];
+        record.data_fields.push(note);
+    }
+    assert_eq!(record.to_iso2709(), Err(MarcError::RecordTooLong));
+}
+
+#[tokio::test]
+async fn books_are_exported_as_marc() {
+    let app = test_app().await;
+    let title = format!("Exported Book {}", unique_suffix());
+    let id = create_book(&app, &title, "Test Author").await;
+
+    let get = |accept: &'static str| {
+        let app = app.clone();
+        let uri = format!("/api/books/{id}");
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header(ACCEPT, accept)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let content_type = response.headers()[CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .to_string();
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            (content_type, body.to_vec())
+        }
+    };
+
+    for accept in ["application/marc+xml", "text/html, application/marc;q=0.9"] {
+        let (content_type, body) = get(accept).await;
+        assert!(accept.contains(&content_type));
+        let record = parse(&body).unwrap().remove(0).unwrap();
+        assert_eq!(record.to_book_query().title, title);
+    }
+
+    let (content_type, body) = get("application/json").await;
+    assert_eq!(content_type, "application/json");
+    let book: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(book["title"], title);
+    assert_eq!(book["copies"], json!([]));
+
+    let (status, _) = send_json(
+        &app,
+        "GET",
+        &format!("/api/books/{}", Uuid::nil()),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/books/export?format=marc")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/marc");
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let records = parse(&body).unwrap();
+    assert!(records.iter().all(|record| record.is_ok()));
+    assert!(records
+        .iter()
+        .flatten()
+        .any(|record| record.control_fields[0].value == id));
+}
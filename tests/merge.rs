@@ -0,0 +1,237 @@
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+
+mod common;
+
+use common::{create_copy, create_patron, send, send_json, test_app, unique_suffix};
+
+#[tokio::test]
+async fn duplicates_are_found_and_merged_into_one_record() {
+    let app = test_app().await;
+    // A serial and edition unlikely to clash with other runs
+    let suffix = unique_suffix();
+    let serial = suffix % 1_000_000;
+    let title = format!("The Tombs of Atuan, edition {suffix}");
+
+    let mut ids = Vec::new();
+    for (title, author, lccn) in [
+        (title.clone(), "Ursula K. Le Guin", format!("zz98-{serial}")),
+        (
+            format!("Tombs of Atuan, edition {suffix}"),
+            "Le Guin, Ursula K.",
+            String::new(),
+        ),
+        (
+            format!("Atuan {suffix}"),
+            "Anonymous",
+            format!("zz98{serial:06}"),
+        ),
+    ] {
+        let (status, id) = send(
+            &app,
+            "POST",
+            "/api/books",
+            json!({ "book": {
+                "author": author,
+                "title": title,
+                "lccn": lccn,
+                "isbn": "",
+                "publish_date": ""
+            }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        ids.push(String::from_utf8(id).unwrap());
+    }
+    let (keep_id, alike_id, merge_id) = (&ids[0], &ids[1], &ids[2]);
+
+    let (status, body) = send_json(
+        &app,
+        "GET",
+        &format!("/api/books/duplicates?master_book_id={keep_id}"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let duplicates = body["duplicates"].as_array().unwrap();
+    let reason_for = |id: &str| {
+        duplicates
+            .iter()
+            .find(|pair| pair["book"]["id"] == id || pair["duplicate"]["id"] == id)
+            .map(|pair| pair["reason"].clone())
+    };
+    assert_eq!(reason_for(merge_id), Some(json!("lccn")));
+    assert_eq!(reason_for(alike_id), Some(json!("title_author")));
+    assert_eq!(duplicates[0]["score"], 1.0);
+
+    // A page at a time, likeliest first
+    let uri = format!("/api/books/duplicates?master_book_id={keep_id}&page_size=1");
+    let (_, first) = send_json(&app, "GET", &uri, Value::Null).await;
+    assert_eq!(first["duplicates"].as_array().unwrap().len(), 1);
+    assert_eq!(first["duplicates"][0]["reason"], "lccn");
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let (status, second) =
+        send_json(&app, "GET", &format!("{uri}&cursor={cursor}"), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(second["duplicates"][0]["reason"], "title_author");
+
+    // Across the catalog, each page picks up where the last left off
+    let mut seen = std::collections::HashSet::new();
+    let mut uri = "/api/books/duplicates?threshold=1&page_size=200".to_owned();
+    let found = loop {
+        let (status, page) = send_json(&app, "GET", &uri, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let duplicates = page["duplicates"].as_array().unwrap();
+        for pair in duplicates {
+            let ids = (
+                pair["book"]["id"].to_string(),
+                pair["duplicate"]["id"].to_string(),
+            );
+            assert!(seen.insert(ids), "{pair} listed twice");
+        }
+        if let Some(pair) = duplicates.iter().find(|pair| {
+            pair["book"]["id"] == *keep_id.min(merge_id)
+                && pair["duplicate"]["id"] == *keep_id.max(merge_id)
+        }) {
+            break pair.clone();
+        }
+        match page["next_cursor"].as_str() {
+            Some(cursor) => {
+                uri = format!("/api/books/duplicates?threshold=1&page_size=200&cursor={cursor}")
+            }
+            None => panic!("the pair sharing an LCCN is never listed"),
+        }
+    };
+    assert_eq!(found["reason"], "lccn");
+
+    let (status, _) = send(
+        &app,
+        "GET",
+        "/api/books/duplicates?threshold=0",
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // The merged record has a copy and a queue of two, one of whom also
+    // waits on the record kept
+    let copy_id = create_copy(&app, merge_id).await;
+    let (_, pickup_id) = send(
+        &app,
+        "POST",
+        "/api/locations",
+        json!({ "location": { "name": "Merge Branch" }}),
+    )
+    .await;
+    let pickup_id = String::from_utf8(pickup_id).unwrap();
+    let both = create_patron(&app).await;
+    let only_merged = create_patron(&app).await;
+    for (master_book_id, patron_id) in [
+        (keep_id, &both),
+        (merge_id, &both),
+        (merge_id, &only_merged),
+    ] {
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/api/holds",
+            json!({ "hold": {
+                "master_book_id": master_book_id,
+                "patron_id": patron_id,
+                "pickup_location_id": pickup_id
+            }}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/books/merge",
+        json!({ "merge": { "keep_id": keep_id, "merge_id": keep_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, merge) = send_json(
+        &app,
+        "POST",
+        "/api/books/merge",
+        json!({ "merge": {
+            "keep_id": keep_id,
+            "merge_id": merge_id,
+            "merged_by": "cataloger"
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(merge["copies_moved"], 1);
+    assert_eq!(merge["holds_moved"], 1);
+    assert_eq!(merge["holds_cancelled"], 1);
+    assert_eq!(merge["merged_record"]["title"], format!("Atuan {suffix}"));
+
+    let (_, copy) = send_json(
+        &app,
+        "GET",
+        "/api/copies",
+        json!({ "copy": { "id": copy_id }}),
+    )
+    .await;
+    assert_eq!(copy["master_id"], *keep_id);
+
+    let (_, queue) = send_json(
+        &app,
+        "GET",
+        "/api/holds/list",
+        json!({ "hold": { "master_book_id": keep_id }}),
+    )
+    .await;
+    let queue = queue["holds"].as_array().unwrap();
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue[0]["patron_id"], both);
+    assert_eq!(queue[1]["patron_id"], only_merged);
+    assert_eq!(queue[1]["queue_position"], 2);
+
+    // Merging the survivor away carries the history along with it
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/books/merge",
+        json!({ "merge": { "keep_id": alike_id, "merge_id": keep_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, merges) = send_json(
+        &app,
+        "GET",
+        &format!("/api/books/merges?master_book_id={alike_id}"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let merges = merges["merges"].as_array().unwrap();
+    assert_eq!(merges.len(), 2);
+    assert_eq!(merges[0]["merged_id"], *merge_id);
+    assert_eq!(merges[1]["merged_id"], *keep_id);
+    assert_eq!(merges[1]["holds_moved"], 2);
+
+    let uri = format!("/api/books/merges?master_book_id={alike_id}&page_size=1");
+    let (_, first) = send_json(&app, "GET", &uri, Value::Null).await;
+    assert_eq!(first["merges"].as_array().unwrap().len(), 1);
+    assert_eq!(first["merges"][0]["merged_id"], *merge_id);
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let (_, second) = send_json(&app, "GET", &format!("{uri}&cursor={cursor}"), Value::Null).await;
+    assert_eq!(second["merges"][0]["merged_id"], *keep_id);
+    assert!(second["next_cursor"].is_null());
+
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/books/merge",
+        json!({ "merge": { "keep_id": alike_id, "merge_id": keep_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
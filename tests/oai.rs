@@ -0,0 +1,243 @@
+use axum::body::Body;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use library_api_rir::catalog::oai::{MetadataFormat, OaiError, OaiRequest};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+use tower::ServiceExt;
+
+mod common;
+
+use common::{create_book, send, test_app, unique_suffix};
+
+fn arguments(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Sends an OAI-PMH request as a query string, returning the response XML
+async fn harvest(app: &Router, query: &str) -> String {
+    let (status, body) = send(app, "GET", &format!("/api/oai?{query}"), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    String::from_utf8(body).unwrap()
+}
+
+/// The text of the first `element` in `xml`
+fn element<'a>(xml: &'a str, element: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{element}>"))? + element.len() + 2;
+    let end = start + xml[start..].find(&format!("</{element}>"))?;
+    Some(&xml[start..end])
+}
+
+#[test]
+fn requests_are_checked_against_their_verb() {
+    assert_eq!(
+        OaiRequest::parse(&arguments(&[("verb", "Identify")])),
+        Ok(OaiRequest::Identify)
+    );
+    assert_eq!(
+        OaiRequest::parse(&arguments(&[
+            ("verb", "GetRecord"),
+            ("identifier", "oai:example.org:1"),
+            ("metadataPrefix", "marc21"),
+        ])),
+        Ok(OaiRequest::GetRecord {
+            identifier: "oai:example.org:1".to_string(),
+            format: MetadataFormat::Marc21,
+        })
+    );
+
+    assert_eq!(OaiRequest::parse(&[]), Err(OaiError::BadVerb));
+    assert_eq!(
+        OaiRequest::parse(&arguments(&[("verb", "ListBooks")])),
+        Err(OaiError::BadVerb)
+    );
+    assert_eq!(
+        OaiRequest::parse(&arguments(&[("verb", "Identify"), ("verb", "Identify")])),
+        Err(OaiError::BadVerb)
+    );
+    assert_eq!(
+        OaiRequest::parse(&arguments(&[("verb", "Identify"), ("from", "2020-01-01")]))
+            .unwrap_err()
+            .code(),
+        "badArgument"
+    );
+
+    let list = |pairs: &[(&str, &str)]| {
+        let mut all = vec![("verb", "ListRecords"), ("metadataPrefix", "oai_dc")];
+        all.extend_from_slice(pairs);
+        OaiRequest::parse(&arguments(&all))
+    };
+    let Ok(OaiRequest::ListRecords(harvest)) =
+        list(&[("from", "2020-01-01"), ("until", "2020-01-31")])
+    else {
+        panic!("day datestamps are a harvest");
+    };
+    assert_eq!(
+        harvest.from.unwrap().to_string(),
+        "2020-01-01 0:00:00.0 +00:00:00"
+    );
+    assert_eq!(
+        harvest.before.unwrap().to_string(),
+        "2020-02-01 0:00:00.0 +00:00:00"
+    );
+    assert_eq!(
+        list(&[("from", "2020-01-01"), ("until", "2020-01-31T12:00:00Z")])
+            .unwrap_err()
+            .code(),
+        "badArgument"
+    );
+    assert_eq!(
+        list(&[("from", "2020-01-01T12:00:00.5Z")])
+            .unwrap_err()
+            .code(),
+        "badArgument"
+    );
+    assert_eq!(list(&[("set", "fiction")]), Err(OaiError::NoSetHierarchy));
+    assert_eq!(
+        OaiRequest::parse(&arguments(&[
+            ("verb", "ListIdentifiers"),
+            ("metadataPrefix", "mods"),
+        ])),
+        Err(OaiError::CannotDisseminateFormat("mods".to_string()))
+    );
+    assert_eq!(
+        OaiRequest::parse(&arguments(&[
+            ("verb", "ListIdentifiers"),
+            ("resumptionToken", "not-a-token"),
+        ])),
+        Err(OaiError::BadResumptionToken)
+    );
+}
+
+#[tokio::test]
+async fn catalog_is_harvested_a_page_at_a_time() {
+    let app = test_app().await;
+    let title = format!("Harvested Book {}", unique_suffix());
+    let id = create_book(&app, &title, "Test Author").await;
+    let identifier = format!("oai:library.example:{id}");
+
+    let identify = harvest(&app, "verb=Identify").await;
+    assert_eq!(
+        element(&identify, "baseURL"),
+        Some("https://library.example/api/oai")
+    );
+    assert_eq!(
+        element(&identify, "adminEmail"),
+        Some("catalog@library.example")
+    );
+    assert_eq!(element(&identify, "deletedRecord"), Some("transient"));
+
+    let mut query = "verb=ListIdentifiers&metadataPrefix=oai_dc".to_string();
+    let mut pages = 0;
+    let mut found = false;
+    loop {
+        let page = harvest(&app, &query).await;
+        pages += 1;
+        found |= page.contains(&format!("<identifier>{identifier}</identifier>"));
+        match element(&page, "resumptionToken") {
+            Some(token) => query = format!("verb=ListIdentifiers&resumptionToken={token}"),
+            None => {
+                if pages > 1 {
+                    assert!(page.contains("<resumptionToken/>"));
+                }
+                break;
+            }
+        }
+    }
+    assert!(found);
+
+    let today = OffsetDateTime::now_utc().date();
+    let record = harvest(
+        &app,
+        &format!("verb=GetRecord&metadataPrefix=oai_dc&identifier={identifier}"),
+    )
+    .await;
+    assert!(record.contains(&format!("<dc:title>{title}</dc:title>")));
+    assert!(element(&record, "datestamp")
+        .unwrap()
+        .starts_with(&today.to_string()));
+
+    let record = harvest(
+        &app,
+        &format!("verb=GetRecord&metadataPrefix=marc21&identifier={identifier}"),
+    )
+    .await;
+    assert!(record.contains("<record xmlns=\"http://www.loc.gov/MARC21/slim\">"));
+
+    let missing = harvest(
+        &app,
+        "verb=GetRecord&metadataPrefix=oai_dc&identifier=oai:library.example:nothing",
+    )
+    .await;
+    assert!(missing.contains("<error code=\"idDoesNotExist\">"));
+
+    let empty = harvest(
+        &app,
+        "verb=ListRecords&metadataPrefix=oai_dc&until=1900-01-01",
+    )
+    .await;
+    assert!(empty.contains("<error code=\"noRecordsMatch\">"));
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/oai")
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(format!(
+                    "verb=ListRecords&metadataPrefix=oai_dc&from={today}"
+                )))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let records = String::from_utf8(body.to_vec()).unwrap();
+    assert!(records.contains("<ListRecords><record><header"));
+}
+
+#[tokio::test]
+async fn merged_books_are_harvested_as_deleted_records() {
+    let app = test_app().await;
+    let suffix = unique_suffix();
+    let keep_id = create_book(&app, &format!("Kept Book {suffix}"), "Test Author").await;
+    let merge_id = create_book(&app, &format!("Merged Book {suffix}"), "Test Author").await;
+    let (status, _) = send(
+        &app,
+        "POST",
+        "/api/books/merge",
+        json!({ "merge": { "keep_id": keep_id, "merge_id": merge_id }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let identifier = format!("oai:library.example:{merge_id}");
+    let deleted = format!("<header status=\"deleted\"><identifier>{identifier}</identifier>");
+
+    let record = harvest(
+        &app,
+        &format!("verb=GetRecord&metadataPrefix=oai_dc&identifier={identifier}"),
+    )
+    .await;
+    assert!(record.contains(&format!("<GetRecord><record>{deleted}")));
+    assert!(!record.contains("<metadata>"));
+
+    let today = OffsetDateTime::now_utc().date();
+    for verb in ["ListIdentifiers", "ListRecords"] {
+        let mut query = format!("verb={verb}&metadataPrefix=oai_dc&from={today}");
+        loop {
+            let page = harvest(&app, &query).await;
+            if page.contains(&deleted) {
+                break;
+            }
+            match element(&page, "resumptionToken") {
+                Some(token) => query = format!("verb={verb}&resumptionToken={token}"),
+                None => panic!("{verb} never lists the merged book as deleted"),
+            }
+        }
+    }
+}
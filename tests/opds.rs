@@ -0,0 +1,144 @@
+use axum::body::Body;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use sqlx::types::Uuid;
+use time::macros::datetime;
+use tower::ServiceExt;
+
+mod common;
+
+use common::{create_book, test_app, test_db, unique_suffix};
+
+/// Gets a feed, returning its content type and body. The test app is served
+/// at `https://library.example`, so the feeds link back there.
+async fn get(app: &Router, uri: &str) -> (String, String) {
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let content_type = response.headers()[CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// Where a link with `rel` in a feed leads
+fn link(feed: &str, rel: &str) -> Option<String> {
+    let needle = format!("<link rel=\"{rel}\" href=\"");
+    let start = feed.find(&needle)? + needle.len();
+    let end = start + feed[start..].find('"')?;
+    Some(feed[start..end].replace("&amp;", "&"))
+}
+
+#[tokio::test]
+async fn root_and_search_description_lead_into_the_catalog() {
+    let app = test_app().await;
+
+    let (content_type, feed) = get(&app, "/api/opds").await;
+    assert_eq!(
+        content_type,
+        "application/atom+xml;profile=opds-catalog;kind=navigation"
+    );
+    assert!(feed.contains("<id>https://library.example/api/opds</id>"));
+    assert_eq!(
+        link(&feed, "search").as_deref(),
+        Some("https://library.example/api/opds/opensearch.xml")
+    );
+    assert_eq!(
+        link(&feed, "subsection").as_deref(),
+        Some("https://library.example/api/opds/books?sort=title")
+    );
+    assert!(feed.contains(
+        "href=\"https://library.example/api/opds/books?sort=publish_date&amp;order=desc\""
+    ));
+
+    let (content_type, description) = get(&app, "/api/opds/opensearch.xml").await;
+    assert_eq!(content_type, "application/opensearchdescription+xml");
+    assert!(description.contains(
+        "template=\"https://library.example/api/opds/search\
+         ?q={searchTerms}&amp;startIndex={startIndex?}&amp;limit=20\""
+    ));
+}
+
+#[tokio::test]
+async fn book_list_is_paged_as_acquisition_feeds() {
+    let app = test_app().await;
+    let word = format!("quasar{}", unique_suffix());
+    let first = create_book(&app, &format!("A {word} Almanac"), "Doe, Jane").await;
+    let second = create_book(&app, &format!("B {word} & Co"), "Roe, Richard").await;
+
+    let (content_type, feed) =
+        get(&app, &format!("/api/opds/books?title={word}&page_size=1")).await;
+    assert_eq!(
+        content_type,
+        "application/atom+xml;profile=opds-catalog;kind=acquisition"
+    );
+    assert!(feed.contains(&format!("<id>urn:uuid:{first}</id>")));
+    assert!(feed.contains("<author><name>Doe, Jane</name></author>"));
+    assert!(feed.contains(&format!(
+        "<link rel=\"http://opds-spec.org/acquisition/borrow\" \
+         href=\"https://library.example/api/books/{first}\" type=\"application/json\"/>"
+    )));
+    assert!(!feed.contains(&second));
+
+    let next = link(&feed, "next").unwrap();
+    let next = next.strip_prefix("https://library.example").unwrap();
+    let (_, feed) = get(&app, next).await;
+    assert!(feed.contains(&format!("<title>B {word} &amp; Co</title>")));
+    assert!(link(&feed, "next").is_none());
+    assert_eq!(
+        link(&feed, "first").unwrap(),
+        format!("https://library.example/api/opds/books?title={word}&page_size=1")
+    );
+}
+
+#[tokio::test]
+async fn search_answers_with_pages_of_acquisition_feeds() {
+    let app = test_app().await;
+    let word = format!("nebula{}", unique_suffix());
+    let first = create_book(&app, &format!("The {word} Guide"), "Doe, Jane").await;
+    let second = create_book(&app, &format!("A {word} Primer"), "Doe, Jane").await;
+    let updated_at = datetime!(2001-02-03 04:05:06 UTC);
+    sqlx::query("update master_book set updated_at = $1 where master_book_id = any($2)")
+        .bind(updated_at)
+        .bind([first.parse::<Uuid>().unwrap(), second.parse().unwrap()])
+        .execute(&test_db().await)
+        .await
+        .unwrap();
+
+    let (content_type, feed) = get(&app, &format!("/api/opds/search?q={word}&limit=1")).await;
+    assert_eq!(
+        content_type,
+        "application/atom+xml;profile=opds-catalog;kind=acquisition"
+    );
+    assert!(feed.contains("<opensearch:totalResults>2</opensearch:totalResults>"));
+    assert!(feed.contains("<opensearch:startIndex>1</opensearch:startIndex>"));
+    assert!(feed.contains("<opensearch:itemsPerPage>1</opensearch:itemsPerPage>"));
+    assert!(feed.contains("<updated>2001-02-03T04:05:06Z</updated>"));
+    assert_eq!(
+        link(&feed, "up").as_deref(),
+        Some("https://library.example/api/opds")
+    );
+    let seen = if feed.contains(&first) {
+        &first
+    } else {
+        &second
+    };
+
+    let next = link(&feed, "next").unwrap();
+    assert_eq!(
+        next,
+        format!("https://library.example/api/opds/search?q={word}&startIndex=2&limit=1")
+    );
+    let (_, feed) = get(&app, next.strip_prefix("https://library.example").unwrap()).await;
+    assert!(feed.contains("<opensearch:startIndex>2</opensearch:startIndex>"));
+    assert!(feed.contains("<id>urn:uuid:"));
+    assert!(!feed.contains(seen.as_str()));
+    assert!(link(&feed, "next").is_none());
+}
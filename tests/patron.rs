@@ -0,0 +1,109 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+mod common;
+
+use common::{send, send_json, test_app};
+
+#[tokio::test]
+async fn patrons_are_created_and_blocked() {
+    let app = test_app().await;
+    let card_number = format!("P-{}", common::unique_suffix());
+
+    let (status, patron_id) = send(
+        &app,
+        "POST",
+        "/api/patrons",
+        json!({ "patron": {
+            "card_number": card_number,
+            "first_name": "Ada",
+            "last_name": "Lovelace",
+            "email": "ada@example.com",
+            "category": "adult",
+            "expires_on": "2030-01-01"
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let patron_id = String::from_utf8(patron_id).unwrap();
+
+    let (status, errors) = send_json(
+        &app,
+        "POST",
+        "/api/patrons",
+        json!({ "patron": {
+            "card_number": card_number,
+            "first_name": "Charles",
+            "last_name": "Babbage",
+            "category": "wizard"
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(errors["errors"]["card_number"][0], "already in use");
+
+    let (status, patron) = send_json(
+        &app,
+        "PUT",
+        "/api/patrons",
+        json!({ "patron": { "id": patron_id, "blocked": true, "blocked_reason": "lost card" }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(patron["blocked"], true);
+    assert_eq!(patron["blocked_reason"], "lost card");
+
+    let (status, patron) = send_json(
+        &app,
+        "PUT",
+        "/api/patrons",
+        json!({ "patron": { "id": patron_id, "blocked": false }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(patron["blocked"], false);
+    assert!(patron["blocked_reason"].is_null());
+}
+
+#[tokio::test]
+async fn patrons_are_listed_a_page_at_a_time() {
+    let app = test_app().await;
+    common::create_patron(&app).await;
+    common::create_patron(&app).await;
+
+    let (status, first) = send_json(
+        &app,
+        "GET",
+        "/api/patrons/list",
+        json!({ "patron": { "page_size": 1 }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(first["patrons"].as_array().unwrap().len(), 1);
+    assert!(first["next_cursor"].is_string());
+
+    let (status, second) = send_json(
+        &app,
+        "GET",
+        "/api/patrons/list",
+        json!({ "patron": { "page_size": 1, "cursor": first["next_cursor"] }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(second["patrons"].as_array().unwrap().len(), 1);
+
+    let name = |page: &serde_json::Value| {
+        (
+            page["patrons"][0]["last_name"]
+                .as_str()
+                .unwrap()
+                .to_string(),
+            page["patrons"][0]["first_name"]
+                .as_str()
+                .unwrap()
+                .to_string(),
+        )
+    };
+    assert!(name(&first) <= name(&second));
+    assert_ne!(first["patrons"][0]["id"], second["patrons"][0]["id"]);
+}
@@ -0,0 +1,169 @@
+use axum::http::StatusCode;
+use library_api_rir::catalog::backfill::Backfills;
+use library_api_rir::catalog::publish_date::{parse, DatePrecision, PublishDate};
+use serde_json::json;
+use sqlx::types::Uuid;
+use time::{Date, Month};
+
+mod common;
+
+use common::{send, send_json, test_app, test_db};
+
+fn date(year: i32, month: Month, day: u8) -> Date {
+    Date::from_calendar_date(year, month, day).unwrap()
+}
+
+fn published(on: Date, precision: DatePrecision, approximate: bool) -> Option<PublishDate> {
+    Some(PublishDate {
+        on,
+        precision,
+        approximate,
+    })
+}
+
+#[test]
+fn catalogers_dates_are_read_with_their_precision() {
+    let cases = [
+        (
+            "1998",
+            published(date(1998, Month::January, 1), DatePrecision::Year, false),
+        ),
+        (
+            "c1998.",
+            published(date(1998, Month::January, 1), DatePrecision::Year, false),
+        ),
+        (
+            "©1998",
+            published(date(1998, Month::January, 1), DatePrecision::Year, false),
+        ),
+        (
+            "[1998?]",
+            published(date(1998, Month::January, 1), DatePrecision::Year, true),
+        ),
+        (
+            "ca. 1850",
+            published(date(1850, Month::January, 1), DatePrecision::Year, true),
+        ),
+        (
+            "March 2001",
+            published(date(2001, Month::March, 1), DatePrecision::Month, false),
+        ),
+        (
+            "Mar. 4, 2001",
+            published(date(2001, Month::March, 4), DatePrecision::Day, false),
+        ),
+        (
+            "4th March 2001",
+            published(date(2001, Month::March, 4), DatePrecision::Day, false),
+        ),
+        (
+            "2001-03-04",
+            published(date(2001, Month::March, 4), DatePrecision::Day, false),
+        ),
+        (
+            "2001-03",
+            published(date(2001, Month::March, 1), DatePrecision::Month, false),
+        ),
+        (
+            "[196-]",
+            published(date(1960, Month::January, 1), DatePrecision::Decade, false),
+        ),
+        (
+            "[19--?]",
+            published(date(1900, Month::January, 1), DatePrecision::Century, true),
+        ),
+        (
+            "19uu",
+            published(date(1900, Month::January, 1), DatePrecision::Century, false),
+        ),
+        ("", None),
+        ("sometime after the war", None),
+        ("2001-13-01", None),
+    ];
+
+    for (input, expected) in cases {
+        assert_eq!(parse(input), expected, "parsing {input:?}");
+    }
+}
+
+#[test]
+fn a_date_covers_its_whole_period() {
+    let last_day = |input: &str| parse(input).unwrap().last_day();
+
+    assert_eq!(last_day("2001-03-04"), date(2001, Month::March, 4));
+    assert_eq!(last_day("February 2000"), date(2000, Month::February, 29));
+    assert_eq!(last_day("1998"), date(1998, Month::December, 31));
+    assert_eq!(last_day("196-"), date(1969, Month::December, 31));
+    assert_eq!(last_day("19--"), date(1999, Month::December, 31));
+}
+
+#[tokio::test]
+async fn books_keep_the_date_as_written_next_to_the_structured_one() {
+    let app = test_app().await;
+
+    let (status, id) = send(
+        &app,
+        "POST",
+        "/api/books",
+        json!({ "book": {
+            "author": "Test Author",
+            "title": "Dated Book",
+            "lccn": "",
+            "isbn": "",
+            "publish_date": "[ca. March 1962]"
+        }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let id = String::from_utf8(id).unwrap();
+
+    let (status, book) = send_json(&app, "GET", "/api/books", json!({ "book": { "id": id }})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(book["publish_date"], "[ca. March 1962]");
+    assert_eq!(
+        book["published"],
+        json!({ "on": "1962-03-01", "precision": "month", "approximate": true })
+    );
+
+    let (status, book) = send_json(
+        &app,
+        "PUT",
+        "/api/books",
+        json!({ "book": { "id": id, "publish_date": "unknown" }}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(book["publish_date"], "unknown");
+    assert!(book["published"].is_null());
+}
+
+#[tokio::test]
+async fn books_written_before_dates_were_read_are_backfilled() {
+    let db = test_db().await;
+    let id: Uuid = sqlx::query_scalar(
+        r#"insert into "master_book" (title, publish_date)
+        values ('Legacy Dated Book', '[c1950]') returning master_book_id"#,
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+
+    assert!(Backfills::run(&db).await.unwrap().filled >= 1);
+
+    let published: (Option<Date>, Option<String>, bool) = sqlx::query_as(
+        r#"select published_on, published_precision, published_approximate
+        from "master_book" where master_book_id = $1"#,
+    )
+    .bind(id)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        published,
+        (
+            Some(date(1950, Month::January, 1)),
+            Some("year".to_string()),
+            false
+        )
+    );
+}
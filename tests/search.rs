@@ -0,0 +1,84 @@
+use axum::http::StatusCode;
+use library_api_rir::catalog::search::{highlight_html, prefix_query};
+use serde_json::{json, Value};
+
+mod common;
+
+use common::{create_book, send, send_json, test_app, unique_suffix};
+
+#[test]
+fn search_terms_become_prefix_matches() {
+    assert_eq!(prefix_query("Left Han").as_deref(), Some("left:* & han:*"));
+    assert_eq!(
+        prefix_query("le guin's: (darkness)").as_deref(),
+        Some("le:* & guin:* & s:* & darkness:*")
+    );
+    assert_eq!(prefix_query(" !&| "), None);
+}
+
+#[tokio::test]
+async fn search_ranks_title_matches_above_author_matches() {
+    let app = test_app().await;
+    let word = format!("quasar{}", unique_suffix());
+
+    let by_author = create_book(&app, "Collected Stories", &format!("{word} Smith")).await;
+    let by_title = create_book(&app, &format!("The {word} Chronicles"), "Jane Doe").await;
+    create_book(&app, "Unrelated Title", "Someone Else").await;
+
+    let prefix = &word[..word.len() - 3];
+    let (status, body) = send_json(
+        &app,
+        "GET",
+        &format!("/api/books/search?q={prefix}"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let results = body["results"].as_array().unwrap();
+    let ids: Vec<_> = results
+        .iter()
+        .map(|result| result["book"]["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, vec![by_title.as_str(), by_author.as_str()]);
+    assert_eq!(
+        results[0]["highlights"]["title"],
+        format!("The <mark>{word}</mark> Chronicles")
+    );
+
+    let (status, _) = send(&app, "GET", "/api/books/search?q=%20", json!(null)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[test]
+fn highlights_are_escaped_around_their_marks() {
+    assert_eq!(
+        highlight_html("Tom & <Jerry> \u{1}Chronicles\u{2}"),
+        "Tom &amp; &lt;Jerry&gt; <mark>Chronicles</mark>"
+    );
+}
+
+#[tokio::test]
+async fn markup_in_a_title_is_escaped_in_its_highlight() {
+    let app = test_app().await;
+    let word = format!("nebula{}", unique_suffix());
+    create_book(
+        &app,
+        &format!("<script>alert(1)</script> {word} & Friends"),
+        "Jane Doe",
+    )
+    .await;
+
+    let (status, body) = send_json(
+        &app,
+        "GET",
+        &format!("/api/books/search?q={word}"),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["results"][0]["highlights"]["title"],
+        format!("&lt;script&gt;alert(1)&lt;/script&gt; <mark>{word}</mark> &amp; Friends")
+    );
+}
@@ -0,0 +1,243 @@
+use axum::http::StatusCode;
+use axum::Router;
+use library_api_rir::catalog::cql::{
+    parse, Boolean, CqlError, CqlQuery, Modifier, SearchClause, MAX_NESTING, MAX_TOKENS,
+};
+use library_api_rir::catalog::sru::{BookFilter, SruDiagnostic};
+use library_api_rir::routes::ServerConfig;
+use serde_json::Value;
+
+mod common;
+
+use common::{create_book, send, test_app, test_config, unique_suffix};
+
+fn clause(index: &str, relation: &str, term: &str) -> CqlQuery {
+    CqlQuery::Clause(SearchClause {
+        index: index.to_string(),
+        relation: relation.to_string(),
+        relation_modifiers: Vec::new(),
+        term: term.to_string(),
+    })
+}
+
+fn filter(query: &str) -> Result<BookFilter, SruDiagnostic> {
+    BookFilter::from_cql(&parse(query).unwrap())
+}
+
+fn like(columns: &'static [&'static str], pattern: &str) -> BookFilter {
+    BookFilter::Like {
+        columns,
+        pattern: pattern.to_string(),
+        negated: false,
+    }
+}
+
+/// Sends an SRU request, returning the response XML
+async fn sru(app: &Router, query: &str) -> String {
+    let (status, body) = send(app, "GET", &format!("/api/sru?{query}"), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    String::from_utf8(body).unwrap()
+}
+
+#[test]
+fn cql_queries_are_parsed() {
+    assert_eq!(
+        parse("darkness"),
+        Ok(clause("cql.serverchoice", "=", "darkness"))
+    );
+    assert_eq!(
+        parse(r#"DC.Title ANY "left hand""#),
+        Ok(clause("dc.title", "any", "left hand"))
+    );
+    assert_eq!(
+        parse("a or dc.creator==b and c"),
+        Ok(CqlQuery::Boolean {
+            boolean: Boolean::And,
+            modifiers: Vec::new(),
+            left: Box::new(CqlQuery::Boolean {
+                boolean: Boolean::Or,
+                modifiers: Vec::new(),
+                left: Box::new(clause("cql.serverchoice", "=", "a")),
+                right: Box::new(clause("dc.creator", "==", "b")),
+            }),
+            right: Box::new(clause("cql.serverchoice", "=", "c")),
+        })
+    );
+    assert_eq!(
+        parse("a or (dc.creator==b and c)"),
+        Ok(CqlQuery::Boolean {
+            boolean: Boolean::Or,
+            modifiers: Vec::new(),
+            left: Box::new(clause("cql.serverchoice", "=", "a")),
+            right: Box::new(CqlQuery::Boolean {
+                boolean: Boolean::And,
+                modifiers: Vec::new(),
+                left: Box::new(clause("dc.creator", "==", "b")),
+                right: Box::new(clause("cql.serverchoice", "=", "c")),
+            }),
+        })
+    );
+    assert_eq!(
+        parse("dc.title =/stem fish"),
+        Ok(CqlQuery::Clause(SearchClause {
+            index: "dc.title".to_string(),
+            relation: "=".to_string(),
+            relation_modifiers: vec![Modifier {
+                name: "stem".to_string(),
+                comparison: None,
+            }],
+            term: "fish".to_string(),
+        }))
+    );
+
+    assert_eq!(parse("fish sortBy dc.title"), Err(CqlError::SortBy));
+    assert_eq!(
+        parse(r#"> dc = "info:srw/cql-context-set/1/dc-v1.1" dc.title = fish"#),
+        Err(CqlError::PrefixAssignment)
+    );
+    assert!(matches!(parse("(fish"), Err(CqlError::Syntax(_))));
+    assert!(matches!(parse("dc.title ="), Err(CqlError::Syntax(_))));
+    assert!(matches!(parse(r#""fish"#), Err(CqlError::Syntax(_))));
+}
+
+#[test]
+fn cql_queries_become_book_filters() {
+    const BOTH: &[&str] = &["title", "author"];
+    const TITLE: &[&str] = &["title"];
+
+    assert_eq!(filter("darkness"), Ok(like(BOTH, "%darkness%")));
+    assert_eq!(
+        filter(r#"dc.title = "^the left*""#),
+        Ok(like(TITLE, "the left%%"))
+    );
+    assert_eq!(
+        filter(r#"title exact "100\% Cotton\?""#),
+        Ok(like(TITLE, "100\\% Cotton?"))
+    );
+    assert_eq!(
+        filter(r#"dc.creator all "le guin""#),
+        Ok(BookFilter::And(
+            Box::new(like(&["author"], "%le%")),
+            Box::new(like(&["author"], "%guin%"))
+        ))
+    );
+    assert_eq!(
+        filter("bath.isbn = 0-441-47812-3 not cql.allRecords = 1"),
+        Ok(BookFilter::AndNot(
+            Box::new(BookFilter::Isbn("9780441478125".to_string())),
+            Box::new(BookFilter::All)
+        ))
+    );
+    assert_eq!(filter("isbn = 12345"), Ok(BookFilter::Nothing));
+
+    assert_eq!(
+        filter("dc.subject = fish"),
+        Err(SruDiagnostic::UnsupportedIndex("dc.subject".to_string()))
+    );
+    assert_eq!(
+        filter("dc.title within fish"),
+        Err(SruDiagnostic::UnsupportedRelation("within".to_string()))
+    );
+    assert_eq!(
+        filter("a prox b"),
+        Err(SruDiagnostic::UnsupportedBoolean("prox"))
+    );
+    assert_eq!(filter(r#"dc.title = """#), Err(SruDiagnostic::EmptyTerm));
+    assert_eq!(filter("dc.title =/stem fish").unwrap_err().number(), 20);
+}
+
+#[test]
+fn servers_are_described_by_the_configured_base_url() {
+    let config = ServerConfig {
+        base_url: "http://catalog.example:8080/library".to_string(),
+        ..test_config()
+    };
+    assert_eq!(config.host(), "catalog.example");
+    assert_eq!(config.port(), 8080);
+    assert_eq!(config.path(), "/library");
+
+    let config = test_config();
+    assert_eq!(config.host(), "library.example");
+    assert_eq!(config.port(), 443);
+    assert_eq!(config.path(), "");
+}
+
+#[tokio::test]
+async fn catalog_is_searched_over_sru() {
+    let app = test_app().await;
+    let word = format!("pulsar{}", unique_suffix());
+    let first = create_book(&app, &format!("A {word} Atlas"), "Doe, Jane").await;
+    let second = create_book(&app, &format!("The {word} Bestiary"), "Roe, Richard").await;
+
+    let response = sru(
+        &app,
+        &format!("version=1.2&operation=searchRetrieve&query=dc.title%3D{word}&maximumRecords=1"),
+    )
+    .await;
+    assert!(response.contains("xmlns:srw=\"http://www.loc.gov/zing/srw/\""));
+    assert!(response.contains("<srw:numberOfRecords>2</srw:numberOfRecords>"));
+    assert!(response.contains(&format!("<dc:identifier>urn:uuid:{first}</dc:identifier>")));
+    assert!(response.contains("<srw:nextRecordPosition>2</srw:nextRecordPosition>"));
+
+    let response = sru(
+        &app,
+        &format!(
+            "query=dc.title%3D{word}%20and%20dc.creator%3Droe&recordSchema=marcxml&startRecord=1"
+        ),
+    )
+    .await;
+    assert!(response.contains("<sru:version>2.0</sru:version>"));
+    assert!(response.contains("<sru:numberOfRecords>1</sru:numberOfRecords>"));
+    assert!(
+        response.contains("<sru:recordSchema>info:srw/schema/1/marcxml-v1.1</sru:recordSchema>")
+    );
+    assert!(response.contains(&format!(
+        "<controlfield tag=\"001\">{second}</controlfield>"
+    )));
+    assert!(!response.contains("nextRecordPosition"));
+
+    let response = sru(&app, &format!("query={word}&startRecord=3")).await;
+    assert!(response.contains("<diag:uri>info:srw/diagnostic/1/61</diag:uri>"));
+
+    let response = sru(
+        &app,
+        "version=1.2&operation=searchRetrieve&query=dc.subject%3Dfish",
+    )
+    .await;
+    assert!(response.contains("<srw:numberOfRecords>0</srw:numberOfRecords>"));
+    assert!(response.contains("<diag:uri>info:srw/diagnostic/1/16</diag:uri>"));
+    assert!(response.contains("<diag:details>dc.subject</diag:details>"));
+
+    let response = sru(&app, "version=3.0&operation=explain").await;
+    assert!(response.contains("<diag:uri>info:srw/diagnostic/1/5</diag:uri>"));
+
+    let response = sru(&app, "version=1.2&operation=explain").await;
+    assert!(response.contains("<srw:explainResponse"));
+    assert!(response.contains("<name set=\"bath\">isbn</name>"));
+    assert!(response.contains("<host>library.example</host><port>443</port>"));
+    assert!(response.contains("<database>api/sru</database>"));
+}
+
+#[tokio::test]
+async fn queries_too_complex_to_read_are_refused() {
+    let nested = |depth: usize| format!("{}fish{}", "(".repeat(depth), ")".repeat(depth));
+    assert!(parse(&nested(MAX_NESTING)).is_ok());
+    assert_eq!(parse(&nested(MAX_NESTING + 1)), Err(CqlError::TooComplex));
+    assert_eq!(parse(&nested(100_000)), Err(CqlError::TooComplex));
+
+    let chained = |terms: usize| vec!["fish"; terms].join(" and ");
+    assert!(parse(&chained(MAX_TOKENS / 2)).is_ok());
+    assert_eq!(parse(&chained(MAX_TOKENS)), Err(CqlError::TooComplex));
+
+    let app = test_app().await;
+    let response = sru(
+        &app,
+        &format!(
+            "version=1.2&operation=searchRetrieve&query={}",
+            nested(5_000)
+        ),
+    )
+    .await;
+    assert!(response.contains("<diag:uri>info:srw/diagnostic/1/10</diag:uri>"));
+    assert!(response.contains("<diag:details>query too complex</diag:details>"));
+}